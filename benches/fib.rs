@@ -0,0 +1,72 @@
+//! A recursive `fib(30)`, which spends nearly all its time looking up and
+//! calling local and global variables. Run with `cargo bench`, and again
+//! with `--features nan-boxing` to compare the VM's value representations.
+//!
+//! The tree-walker finds locals by the slot the resolver gave them, and
+//! globals by hashing their names, the way it found every variable before
+//! slots. So it runs `fib(30)` twice, once with `fib` global and once with
+//! it declared in a block, where every call finds it by slot. The
+//! `variables` group does the same for a loop that does nothing but read
+//! and write two variables.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rlox::vm::{self, Vm};
+use rlox::Engine;
+
+const FIB: &str = "
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 2) + fib(n - 1);
+}
+fib(30);
+";
+
+/// The loop `variables` runs, with `{}` replaced by its declarations.
+const LOOP: &str = "
+{}
+while (i < 100000) {
+  sum = sum + i;
+  i = i + 1;
+}
+";
+
+fn fib(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fib(30)");
+    group.sample_size(10);
+
+    let local = format!("{{{}}}", FIB);
+    for (name, source) in [
+        ("tree, hash (global fib)", FIB),
+        ("tree, slots (local fib)", &local),
+    ] {
+        let stmts = Engine::new().compile(source).unwrap();
+        group.bench_function(name, |b| b.iter(|| Engine::new().execute(&stmts).unwrap()));
+    }
+
+    let script = vm::compile(FIB).unwrap();
+    group.bench_function("vm", |b| {
+        b.iter(|| Vm::new().interpret(script.clone()).unwrap())
+    });
+
+    group.finish();
+}
+
+fn variables(c: &mut Criterion) {
+    let mut group = c.benchmark_group("variables");
+    group.sample_size(10);
+
+    let declarations = "var i = 0;\nvar sum = 0;";
+    let globals = LOOP.replace("{}", declarations);
+    // A block makes them locals, and the loop inside it uses their slots
+    let locals = format!("{{{}}}", globals);
+
+    for (name, source) in [("hash (globals)", globals), ("slots (locals)", locals)] {
+        let stmts = Engine::new().compile(&source).unwrap();
+        group.bench_function(name, |b| b.iter(|| Engine::new().execute(&stmts).unwrap()));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, fib, variables);
+criterion_main!(benches);
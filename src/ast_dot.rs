@@ -0,0 +1,298 @@
+//! Renders the AST as a Graphviz DOT graph, e.g. `rlox ast --dot f.lox | dot -Tsvg`.
+//!
+//! Each node is labelled with its kind, its operator, name or literal value,
+//! and the line it came from. Edges are
+//! labelled with the role the child plays in its parent.
+
+use crate::{
+    ast_printer::{format_number, format_string},
+    syntax::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, ExpressionStmt, FunctionStmt,
+        GetExpr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt,
+        SetExpr, Stmt, SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+    },
+    visit::MutVisitor,
+};
+
+pub struct ASTDotVisitor {
+    nodes: Vec<String>,
+    edges: Vec<String>,
+}
+
+fn escape_label(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn with_line(label: String, line: usize) -> String {
+    format!("{}\nline {}", label, line)
+}
+
+impl ASTDotVisitor {
+    pub fn new() -> ASTDotVisitor {
+        ASTDotVisitor {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    pub fn render(mut self, statements: &[Stmt]) -> String {
+        let root = self.node("Program".to_string());
+        self.statement_edges(root, "stmt", statements);
+
+        let mut dot = String::from("digraph AST {\n");
+        dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for (id, label) in self.nodes.iter().enumerate() {
+            dot.push_str(&format!("  n{} [label=\"{}\"];\n", id, escape_label(label)));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!("  {};\n", edge));
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    fn node(&mut self, label: String) -> usize {
+        self.nodes.push(label);
+        self.nodes.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize, role: &str) {
+        self.edges.push(format!(
+            "n{} -> n{} [label=\"{}\"]",
+            from,
+            to,
+            escape_label(role)
+        ));
+    }
+
+    fn statement_edges(&mut self, parent: usize, role: &str, statements: &[Stmt]) {
+        for (i, stmt) in statements.iter().enumerate() {
+            let child = self.visit_statement(stmt);
+            self.edge(parent, child, &format!("{} {}", role, i));
+        }
+    }
+
+    fn function(&mut self, stmt: &FunctionStmt, kind: &str) -> usize {
+        let params = stmt
+            .params
+            .iter()
+            .map(|param| param.token_type.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let id = self.node(with_line(
+            format!("{} {}({})", kind, stmt.name.token_type, params),
+            stmt.name.line,
+        ));
+        self.statement_edges(id, "body", &stmt.body);
+        id
+    }
+}
+
+impl Default for ASTDotVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MutVisitor for ASTDotVisitor {
+    type E = usize;
+
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> usize {
+        let id = self.node(with_line("Expression".to_string(), stmt.line));
+        let expression = self.visit_expression(&stmt.expression);
+        self.edge(id, expression, "expression");
+        id
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> usize {
+        let id = self.node(with_line("Print".to_string(), stmt.line));
+        let expression = self.visit_expression(&stmt.expression);
+        self.edge(id, expression, "expression");
+        id
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> usize {
+        let id = self.node(with_line(
+            format!("Var {}", stmt.name.token_type),
+            stmt.name.line,
+        ));
+        if let Some(initializer) = &stmt.initializer {
+            let initializer = self.visit_expression(initializer);
+            self.edge(id, initializer, "initializer");
+        }
+        id
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> usize {
+        let id = self.node(with_line("Block".to_string(), stmt.line));
+        self.statement_edges(id, "stmt", &stmt.statements);
+        id
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> usize {
+        let id = self.node(with_line("If".to_string(), stmt.line));
+        let condition = self.visit_expression(&stmt.condition);
+        self.edge(id, condition, "condition");
+        let then_branch = self.visit_statement(&stmt.then_branch);
+        self.edge(id, then_branch, "then");
+        if let Some(else_branch) = &stmt.else_branch {
+            let else_branch = self.visit_statement(else_branch);
+            self.edge(id, else_branch, "else");
+        }
+        id
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> usize {
+        let id = self.node(with_line("While".to_string(), stmt.line));
+        let condition = self.visit_expression(&stmt.condition);
+        self.edge(id, condition, "condition");
+        let body = self.visit_statement(&stmt.body);
+        self.edge(id, body, "body");
+        id
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) -> usize {
+        self.function(stmt, "Fun")
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> usize {
+        let id = self.node(with_line("Return".to_string(), stmt.keyword.line));
+        if let Some(value) = &stmt.value {
+            let value = self.visit_expression(value);
+            self.edge(id, value, "value");
+        }
+        id
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> usize {
+        let id = self.node(with_line(
+            format!("Class {}", stmt.name.token_type),
+            stmt.name.line,
+        ));
+        if let Some(superclass) = &stmt.superclass {
+            let superclass = self.visit_variable(superclass);
+            self.edge(id, superclass, "superclass");
+        }
+        for method in &stmt.methods {
+            let method = self.function(method, "Method");
+            self.edge(id, method, "method");
+        }
+        id
+    }
+
+    fn visit_binary(&mut self, expr: &BinaryExpr) -> usize {
+        let id = self.node(with_line(
+            format!("Binary {}", expr.operator.token_type),
+            expr.operator.line,
+        ));
+        let left = self.visit_expression(&expr.left);
+        self.edge(id, left, "left");
+        let right = self.visit_expression(&expr.right);
+        self.edge(id, right, "right");
+        id
+    }
+
+    fn visit_grouping(&mut self, expr: &Grouping) -> usize {
+        let id = self.node(with_line("Grouping".to_string(), expr.line));
+        let expression = self.visit_expression(&expr.expression);
+        self.edge(id, expression, "expression");
+        id
+    }
+
+    fn visit_literal(&mut self, expr: &LiteralExpr) -> usize {
+        let value = match &expr.value {
+            LiteralValue::Float(float) => format_number(*float),
+            LiteralValue::LoxString(string) => format_string(string),
+            LiteralValue::Bool(bool) => bool.to_string(),
+            LiteralValue::None => "nil".to_string(),
+        };
+        self.node(with_line(format!("Literal {}", value), expr.line))
+    }
+
+    fn visit_unary(&mut self, expr: &UnaryExpr) -> usize {
+        let id = self.node(with_line(
+            format!("Unary {}", expr.operator.token_type),
+            expr.operator.line,
+        ));
+        let right = self.visit_expression(&expr.right);
+        self.edge(id, right, "operand");
+        id
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) -> usize {
+        self.node(with_line(
+            format!("Variable {}", expr.name.token_type),
+            expr.name.line,
+        ))
+    }
+
+    fn visit_assign(&mut self, expr: &AssignExpr) -> usize {
+        let id = self.node(with_line(
+            format!("Assign {}", expr.name.token_type),
+            expr.name.line,
+        ));
+        let value = self.visit_expression(&expr.value);
+        self.edge(id, value, "value");
+        id
+    }
+
+    fn visit_logical(&mut self, expr: &LogicalExpr) -> usize {
+        let id = self.node(with_line(
+            format!("Logical {}", expr.operator.token_type),
+            expr.operator.line,
+        ));
+        let left = self.visit_expression(&expr.left);
+        self.edge(id, left, "left");
+        let right = self.visit_expression(&expr.right);
+        self.edge(id, right, "right");
+        id
+    }
+
+    fn visit_call(&mut self, expr: &CallExpr) -> usize {
+        let id = self.node(with_line("Call".to_string(), expr.paren.line));
+        let callee = self.visit_expression(&expr.callee);
+        self.edge(id, callee, "callee");
+        for (i, argument) in expr.arguments.iter().enumerate() {
+            let argument = self.visit_expression(argument);
+            self.edge(id, argument, &format!("arg {}", i));
+        }
+        id
+    }
+
+    fn visit_get(&mut self, expr: &GetExpr) -> usize {
+        let id = self.node(with_line(
+            format!("Get .{}", expr.name.token_type),
+            expr.name.line,
+        ));
+        let object = self.visit_expression(&expr.object);
+        self.edge(id, object, "object");
+        id
+    }
+
+    fn visit_set(&mut self, expr: &SetExpr) -> usize {
+        let id = self.node(with_line(
+            format!("Set .{}", expr.name.token_type),
+            expr.name.line,
+        ));
+        let object = self.visit_expression(&expr.object);
+        self.edge(id, object, "object");
+        let value = self.visit_expression(&expr.value);
+        self.edge(id, value, "value");
+        id
+    }
+
+    fn visit_this(&mut self, expr: &ThisExpr) -> usize {
+        self.node(with_line("This".to_string(), expr.keyword.line))
+    }
+
+    fn visit_super(&mut self, expr: &SuperExpr) -> usize {
+        self.node(with_line(
+            format!("Super .{}", expr.method.token_type),
+            expr.keyword.line,
+        ))
+    }
+}
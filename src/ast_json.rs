@@ -0,0 +1,192 @@
+//! JSON form of tokens and ASTs, for tools that don't link against the crate.
+//!
+//! Every document is an object carrying the schema `version` alongside either
+//! `tokens` or `statements`. The version is bumped whenever a field is renamed
+//! or removed; new optional fields don't change it.
+//!
+//! Schema version 1:
+//!
+//! ```text
+//! Document   {"version": 1, "statements": [Stmt...]}
+//!            {"version": 1, "tokens": [Token...]}
+//!
+//! Token      {"kind": <TokenType variant>, "value"?: <payload>, "line": <number>}
+//!            `value` is only present for Identifier and LoxString (a string)
+//!            and Number (a number), e.g. {"kind": "Identifier", "value": "x", "line": 3}
+//!
+//! Stmt       {"type": "Expression", "expression": Expr, "line": <number>}
+//!            {"type": "Print", "expression": Expr, "line": <number>}
+//!            {"type": "Var", "name": Token, "initializer": Expr | null}
+//!            {"type": "Block", "statements": [Stmt...], "line": <number>}
+//!            {"type": "If", "condition": Expr, "then_branch": Stmt, "else_branch": Stmt | null,
+//!             "line": <number>}
+//!            {"type": "While", "condition": Expr, "body": Stmt, "line": <number>}
+//!            {"type": "Function", "name": Token, "params": [Token...], "body": [Stmt...]}
+//!            {"type": "Return", "keyword": Token, "value": Expr | null}
+//!            {"type": "Class", "name": Token, "superclass": {"name": Token} | null,
+//!             "methods": [{"name": Token, "params": [Token...], "body": [Stmt...]}...]}
+//!
+//! Expr       {"type": "Literal", "value": <number> | <string> | <bool> | null, "line": <number>}
+//!            {"type": "Binary", "left": Expr, "operator": Token, "right": Expr}
+//!            {"type": "Logical", "left": Expr, "operator": Token, "right": Expr}
+//!            {"type": "Unary", "operator": Token, "right": Expr}
+//!            {"type": "Grouping", "expression": Expr, "line": <number>}
+//!            {"type": "Variable", "name": Token}
+//!            {"type": "Assign", "name": Token, "value": Expr}
+//!            {"type": "Call", "callee": Expr, "paren": Token, "arguments": [Expr...]}
+//!            {"type": "Get", "object": Expr, "name": Token}
+//!            {"type": "Set", "object": Expr, "name": Token, "value": Expr}
+//!            {"type": "This", "keyword": Token}
+//!            {"type": "Super", "keyword": Token, "method": Token}
+//! ```
+//!
+//! Nodes without a token of their own carry a `line`, which is read as 0 when
+//! it's missing.
+//!
+//! JSON has no representation for NaN or infinities, so wherever a number
+//! appears, one that isn't finite is written as `{"number": "NaN"}`,
+//! `{"number": "Infinity"}` or `{"number": "-Infinity"}` instead.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{syntax::Stmt, token::Token};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum JsonError {
+    Malformed(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Malformed(err) => {
+                write!(f, "JSON Error: {}", err)?;
+            }
+            JsonError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "JSON Error: Unsupported schema version {}, expecting {}",
+                    version, SCHEMA_VERSION
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(err: serde_json::Error) -> Self {
+        JsonError::Malformed(err)
+    }
+}
+
+#[derive(Serialize)]
+struct StatementsOut<'a> {
+    version: u32,
+    statements: &'a [Stmt],
+}
+
+#[derive(Deserialize)]
+struct StatementsIn {
+    version: u32,
+    statements: Vec<Stmt>,
+}
+
+#[derive(Serialize)]
+struct TokensOut<'a> {
+    version: u32,
+    tokens: &'a [Token],
+}
+
+#[derive(Deserialize)]
+struct TokensIn {
+    version: u32,
+    tokens: Vec<Token>,
+}
+
+fn check_version(version: u32) -> Result<(), JsonError> {
+    match version {
+        SCHEMA_VERSION => Ok(()),
+        version => Err(JsonError::UnsupportedVersion(version)),
+    }
+}
+
+pub fn statements_to_json(statements: &[Stmt]) -> String {
+    serde_json::to_string_pretty(&StatementsOut {
+        version: SCHEMA_VERSION,
+        statements,
+    })
+    .expect("AST nodes always serialize")
+}
+
+pub fn statements_from_json(json: &str) -> Result<Vec<Stmt>, JsonError> {
+    let document: StatementsIn = serde_json::from_str(json)?;
+    check_version(document.version)?;
+
+    Ok(document.statements)
+}
+
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    serde_json::to_string_pretty(&TokensOut {
+        version: SCHEMA_VERSION,
+        tokens,
+    })
+    .expect("tokens always serialize")
+}
+
+pub fn tokens_from_json(json: &str) -> Result<Vec<Token>, JsonError> {
+    let document: TokensIn = serde_json::from_str(json)?;
+    check_version(document.version)?;
+
+    Ok(document.tokens)
+}
+
+/// Reads and writes an `f64` as a JSON number, falling back to the
+/// `{"number": ...}` form for NaN and the infinities.
+pub(crate) mod number {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Finite(f64),
+        NonFinite { number: String },
+    }
+
+    pub fn serialize<S: Serializer>(number: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        let number = if number.is_nan() {
+            Number::NonFinite {
+                number: "NaN".to_string(),
+            }
+        } else if *number == f64::INFINITY {
+            Number::NonFinite {
+                number: "Infinity".to_string(),
+            }
+        } else if *number == f64::NEG_INFINITY {
+            Number::NonFinite {
+                number: "-Infinity".to_string(),
+            }
+        } else {
+            Number::Finite(*number)
+        };
+        number.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        match Number::deserialize(deserializer)? {
+            Number::Finite(number) => Ok(number),
+            Number::NonFinite { number } => match number.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                _ => Err(D::Error::custom(format!("unknown number {:?}", number))),
+            },
+        }
+    }
+}
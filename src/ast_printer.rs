@@ -1,49 +1,256 @@
+//! Prints the AST in the canonical S-expression form read back by
+//! `ast_reader::ASTReader`. See that module for the grammar.
+
 use std::fmt;
 
 use crate::{
-    syntax::{BinaryExpr, Expr, Grouping, UnaryExpr},
+    syntax::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, ExpressionStmt, FunctionStmt,
+        GetExpr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt,
+        SetExpr, Stmt, SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+    },
+    token::{Token, TokenType},
     visit::Visitor,
 };
 
 pub struct ASTStringVisitor<'a> {
-    pub expressions: &'a [Expr],
+    pub statements: &'a [Stmt],
+}
+
+impl<'a> ASTStringVisitor<'a> {
+    fn list(&self, head: &str, items: impl IntoIterator<Item = String>) -> String {
+        let mut string = format!("({}", head);
+        for item in items {
+            string.push(' ');
+            string.push_str(&item);
+        }
+        string.push(')');
+        string
+    }
+
+    fn function(&self, stmt: &FunctionStmt) -> String {
+        let params = stmt.params.iter().map(name).collect::<Vec<String>>();
+        self.list(
+            "fun",
+            [name(&stmt.name), format!("({})", params.join(" "))]
+                .into_iter()
+                .chain(stmt.body.iter().map(|stmt| self.visit_statement(stmt))),
+        )
+    }
+}
+
+fn name(token: &Token) -> String {
+    token.token_type.to_string()
+}
+
+pub fn format_number(number: f64) -> String {
+    if number.is_nan() {
+        "#nan".to_string()
+    } else if number.is_infinite() && number > 0.0 {
+        "#inf".to_string()
+    } else if number.is_infinite() {
+        "#-inf".to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+pub fn format_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
 
 impl<'a> Visitor for ASTStringVisitor<'a> {
     type E = String;
 
-    fn visit_expression(&self, expr: &Expr) -> String {
-        match expr {
-            Expr::Binary(BinaryExpr {
-                left: left_expr,
-                right: right_expr,
-                operator,
-            }) => format!(
-                "(Binary {} {} {})",
-                operator.token_type,
-                self.visit_expression(left_expr),
-                self.visit_expression(right_expr)
+    fn visit_expression_stmt(&self, stmt: &ExpressionStmt) -> String {
+        self.list("expr", [self.visit_expression(&stmt.expression)])
+    }
+
+    fn visit_print_stmt(&self, stmt: &PrintStmt) -> String {
+        self.list("print", [self.visit_expression(&stmt.expression)])
+    }
+
+    fn visit_var_stmt(&self, stmt: &VarStmt) -> String {
+        self.list(
+            "var",
+            [name(&stmt.name)].into_iter().chain(
+                stmt.initializer
+                    .iter()
+                    .map(|expr| self.visit_expression(expr)),
             ),
-            Expr::Unary(UnaryExpr {
-                operator,
-                right: right_expr,
-            }) => format!(
-                "(Unary {} {})",
-                operator.token_type,
-                self.visit_expression(right_expr)
+        )
+    }
+
+    fn visit_block_stmt(&self, stmt: &BlockStmt) -> String {
+        self.list(
+            "block",
+            stmt.statements
+                .iter()
+                .map(|stmt| self.visit_statement(stmt)),
+        )
+    }
+
+    fn visit_if_stmt(&self, stmt: &IfStmt) -> String {
+        self.list(
+            "if",
+            [
+                self.visit_expression(&stmt.condition),
+                self.visit_statement(&stmt.then_branch),
+            ]
+            .into_iter()
+            .chain(
+                stmt.else_branch
+                    .iter()
+                    .map(|branch| self.visit_statement(branch)),
             ),
-            Expr::Grouping(Grouping { expression: expr }) => {
-                format!("(Grouping {})", self.visit_expression(expr))
-            }
-            Expr::Literal(literal_value) => format!("(Literal {})", literal_value),
+        )
+    }
+
+    fn visit_while_stmt(&self, stmt: &WhileStmt) -> String {
+        self.list(
+            "while",
+            [
+                self.visit_expression(&stmt.condition),
+                self.visit_statement(&stmt.body),
+            ],
+        )
+    }
+
+    fn visit_function_stmt(&self, stmt: &FunctionStmt) -> String {
+        self.function(stmt)
+    }
+
+    fn visit_return_stmt(&self, stmt: &ReturnStmt) -> String {
+        self.list(
+            "return",
+            stmt.value.iter().map(|expr| self.visit_expression(expr)),
+        )
+    }
+
+    fn visit_class_stmt(&self, stmt: &ClassStmt) -> String {
+        self.list(
+            "class",
+            [name(&stmt.name)]
+                .into_iter()
+                .chain(
+                    stmt.superclass
+                        .iter()
+                        .map(|superclass| format!("(< {})", name(&superclass.name))),
+                )
+                .chain(stmt.methods.iter().map(|method| self.function(method))),
+        )
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> String {
+        self.list(
+            &name(&expr.operator),
+            [
+                self.visit_expression(&expr.left),
+                self.visit_expression(&expr.right),
+            ],
+        )
+    }
+
+    fn visit_grouping(&self, expr: &Grouping) -> String {
+        self.list("group", [self.visit_expression(&expr.expression)])
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> String {
+        match &expr.value {
+            LiteralValue::Float(float) => format_number(*float),
+            LiteralValue::LoxString(string) => format_string(string),
+            LiteralValue::Bool(bool) => bool.to_string(),
+            LiteralValue::None => "nil".to_string(),
         }
     }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> String {
+        self.list(&name(&expr.operator), [self.visit_expression(&expr.right)])
+    }
+
+    fn visit_variable(&self, expr: &Variable) -> String {
+        name(&expr.name)
+    }
+
+    fn visit_assign(&self, expr: &AssignExpr) -> String {
+        self.list(
+            "assign",
+            [name(&expr.name), self.visit_expression(&expr.value)],
+        )
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> String {
+        let operator = match expr.operator.token_type {
+            TokenType::And => "and",
+            _ => "or",
+        };
+        self.list(
+            operator,
+            [
+                self.visit_expression(&expr.left),
+                self.visit_expression(&expr.right),
+            ],
+        )
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> String {
+        self.list(
+            "call",
+            [self.visit_expression(&expr.callee)].into_iter().chain(
+                expr.arguments
+                    .iter()
+                    .map(|argument| self.visit_expression(argument)),
+            ),
+        )
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> String {
+        self.list(
+            "get",
+            [self.visit_expression(&expr.object), name(&expr.name)],
+        )
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> String {
+        self.list(
+            "set",
+            [
+                self.visit_expression(&expr.object),
+                name(&expr.name),
+                self.visit_expression(&expr.value),
+            ],
+        )
+    }
+
+    fn visit_this(&self, _expr: &ThisExpr) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> String {
+        self.list("super", [name(&expr.method)])
+    }
 }
 
 impl<'a> fmt::Display for ASTStringVisitor<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for expr in self.expressions {
-            write!(f, "{}", self.visit_expression(expr))?;
+        for (i, stmt) in self.statements.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", self.visit_statement(stmt))?;
         }
 
         Ok(())
@@ -0,0 +1,512 @@
+//! Reads the canonical S-expression form printed by `ASTStringVisitor` back
+//! into `Stmt`s and `Expr`s, so ASTs can be written by hand without going
+//! through the `Scanner` and `Parser`.
+//!
+//! A program is a sequence of statement forms:
+//!
+//! ```text
+//! (expr <expr>)                  (print <expr>)
+//! (var <name>)                   (var <name> <expr>)
+//! (block <stmt>...)              (while <expr> <stmt>)
+//! (if <expr> <stmt>)             (if <expr> <stmt> <stmt>)
+//! (return)                       (return <expr>)
+//! (fun <name> (<name>...) <stmt>...)
+//! (class <name> (< <name>)? (fun ...)...)
+//! ```
+//!
+//! Expressions are atoms or forms:
+//!
+//! ```text
+//! 123  1.5  #inf  #-inf  #nan    numbers
+//! "text"                         strings, with \" \\ \n \r \t escapes
+//! true  false  nil  this         literals and `this`
+//! <name>                         variables
+//! (<op> <expr> <expr>)           binary, for + - * / == != < <= > >=
+//! (<op> <expr>)                  unary, for - and !
+//! (and <expr> <expr>)            (or <expr> <expr>)
+//! (group <expr>)                 (assign <name> <expr>)
+//! (call <expr> <expr>...)        (get <expr> <name>)
+//! (set <expr> <name> <expr>)     (super <name>)
+//! ```
+//!
+//! Source lines aren't part of the format. Every token is given the line its
+//! atom or form starts on in the text being read.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::{
+    scanner::keyword,
+    symbol::Symbol,
+    syntax::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, Expr, ExpressionStmt, FunctionStmt,
+        GetExpr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt,
+        SetExpr, Stmt, SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+    },
+    token::{Token, TokenType as TT},
+};
+
+pub type ReaderResult<T> = Result<T, ReaderError>;
+
+#[derive(Debug)]
+pub enum ReaderError {
+    UnexpectedEof(usize),
+    UnexpectedCloseParen(usize),
+    UnterminatedString(usize),
+    InvalidEscape(char, usize),
+    InvalidNumber(String, usize),
+    UnknownForm(String, usize),
+    MalformedForm(String, usize),
+    ExpectedName(usize),
+}
+
+impl fmt::Display for ReaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReaderError::UnexpectedEof(line) => {
+                write!(f, "Reader Error: Unexpected end of input at line {}", line)?;
+            }
+            ReaderError::UnexpectedCloseParen(line) => {
+                write!(f, "Reader Error: Unbalanced ')' at line {}", line)?;
+            }
+            ReaderError::UnterminatedString(line) => {
+                write!(f, "Reader Error: Unterminated string at line {}", line)?;
+            }
+            ReaderError::InvalidEscape(c, line) => {
+                write!(
+                    f,
+                    "Reader Error: Invalid escape sequence \\{} at line {}",
+                    c, line
+                )?;
+            }
+            ReaderError::InvalidNumber(number, line) => {
+                write!(
+                    f,
+                    "Reader Error: Invalid number {} at line {}",
+                    number, line
+                )?;
+            }
+            ReaderError::UnknownForm(head, line) => {
+                write!(
+                    f,
+                    "Reader Error: Unknown form ({} ...) at line {}",
+                    head, line
+                )?;
+            }
+            ReaderError::MalformedForm(head, line) => {
+                write!(
+                    f,
+                    "Reader Error: Wrong number or kind of arguments to ({} ...) at line {}",
+                    head, line
+                )?;
+            }
+            ReaderError::ExpectedName(line) => {
+                write!(f, "Reader Error: Expecting a name at line {}", line)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ReaderError {
+    pub fn line(&self) -> usize {
+        match *self {
+            ReaderError::UnexpectedEof(line) => line,
+            ReaderError::UnexpectedCloseParen(line) => line,
+            ReaderError::UnterminatedString(line) => line,
+            ReaderError::InvalidEscape(_, line) => line,
+            ReaderError::InvalidNumber(_, line) => line,
+            ReaderError::UnknownForm(_, line) => line,
+            ReaderError::MalformedForm(_, line) => line,
+            ReaderError::ExpectedName(line) => line,
+        }
+    }
+}
+
+enum Datum {
+    Atom(String, usize),
+    Str(String, usize),
+    List(Vec<Datum>, usize),
+}
+
+impl Datum {
+    fn line(&self) -> usize {
+        match *self {
+            Datum::Atom(_, line) => line,
+            Datum::Str(_, line) => line,
+            Datum::List(_, line) => line,
+        }
+    }
+}
+
+pub struct ASTReader<'a> {
+    source: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> ASTReader<'a> {
+    pub fn new(source: &str) -> ASTReader<'_> {
+        ASTReader {
+            source: source.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    pub fn read_statements(&mut self) -> ReaderResult<Vec<Stmt>> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while let Some(datum) = self.read_datum()? {
+            statements.push(statement(&datum)?);
+        }
+
+        Ok(statements)
+    }
+
+    pub fn read_expression(&mut self) -> ReaderResult<Expr> {
+        let datum = self
+            .read_datum()?
+            .ok_or(ReaderError::UnexpectedEof(self.line))?;
+        let expr = expression(&datum)?;
+
+        match self.read_datum()? {
+            Some(extra) => Err(ReaderError::MalformedForm(
+                "expression".to_string(),
+                extra.line(),
+            )),
+            None => Ok(expr),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.source.peek() {
+            if c == '\n' {
+                self.line += 1;
+            } else if !c.is_whitespace() {
+                return;
+            }
+            self.source.next();
+        }
+    }
+
+    fn read_datum(&mut self) -> ReaderResult<Option<Datum>> {
+        self.skip_whitespace();
+        let line = self.line;
+
+        match self.source.next() {
+            None => Ok(None),
+            Some('(') => {
+                let mut items: Vec<Datum> = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.source.peek() {
+                        Some(')') => {
+                            self.source.next();
+                            return Ok(Some(Datum::List(items, line)));
+                        }
+                        Some(_) => items.push(self.read_datum()?.unwrap()),
+                        None => return Err(ReaderError::UnexpectedEof(self.line)),
+                    }
+                }
+            }
+            Some(')') => Err(ReaderError::UnexpectedCloseParen(line)),
+            Some('"') => self.read_string(line).map(Some),
+            Some(c) => {
+                let mut atom = String::from(c);
+                while let Some(&c) = self.source.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    atom.push(c);
+                    self.source.next();
+                }
+                Ok(Some(Datum::Atom(atom, line)))
+            }
+        }
+    }
+
+    fn read_string(&mut self, line: usize) -> ReaderResult<Datum> {
+        let mut string = String::new();
+
+        while let Some(c) = self.source.next() {
+            match c {
+                '"' => return Ok(Datum::Str(string, line)),
+                '\\' => match self.source.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some(c) => return Err(ReaderError::InvalidEscape(c, self.line)),
+                    None => break,
+                },
+                '\n' => {
+                    self.line += 1;
+                    string.push(c);
+                }
+                c => string.push(c),
+            }
+        }
+
+        Err(ReaderError::UnterminatedString(line))
+    }
+}
+
+fn name(datum: &Datum) -> ReaderResult<Token> {
+    match datum {
+        Datum::Atom(name, line) if is_identifier(name) => {
+            Ok(Token::new(TT::Identifier(Symbol::intern(name)), *line))
+        }
+        _ => Err(ReaderError::ExpectedName(datum.line())),
+    }
+}
+
+fn literal(value: LiteralValue, line: usize) -> Expr {
+    Expr::Literal(LiteralExpr { value, line })
+}
+
+fn number(atom: &str, line: usize) -> ReaderResult<f64> {
+    match atom {
+        "#nan" => Ok(f64::NAN),
+        "#inf" => Ok(f64::INFINITY),
+        "#-inf" => Ok(f64::NEG_INFINITY),
+        _ => atom
+            .parse()
+            .map_err(|_| ReaderError::InvalidNumber(atom.to_string(), line)),
+    }
+}
+
+fn is_identifier(atom: &str) -> bool {
+    atom.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && atom.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && keyword(atom).is_none()
+}
+
+fn is_number(atom: &str) -> bool {
+    let digits = atom.strip_prefix('-').unwrap_or(atom);
+    atom.starts_with('#') || digits.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn operator(atom: &str) -> Option<TT> {
+    let token_type = match atom {
+        "-" => TT::Minus,
+        "+" => TT::Plus,
+        "/" => TT::Slash,
+        "*" => TT::Star,
+        "!" => TT::Bang,
+        "!=" => TT::BangEqual,
+        "==" => TT::EqualEqual,
+        ">" => TT::Greater,
+        ">=" => TT::GreaterEqual,
+        "<" => TT::Less,
+        "<=" => TT::LessEqual,
+        _ => return None,
+    };
+
+    Some(token_type)
+}
+
+fn function(datum: &Datum) -> ReaderResult<FunctionStmt> {
+    let malformed = || ReaderError::MalformedForm("fun".to_string(), datum.line());
+
+    match datum {
+        Datum::List(items, _) => match items.as_slice() {
+            [Datum::Atom(head, _), fun_name, Datum::List(params, _), body @ ..]
+                if head == "fun" =>
+            {
+                Ok(FunctionStmt {
+                    name: name(fun_name)?,
+                    params: params.iter().map(name).collect::<ReaderResult<_>>()?,
+                    body: body.iter().map(statement).collect::<ReaderResult<_>>()?,
+                })
+            }
+            _ => Err(malformed()),
+        },
+        _ => Err(malformed()),
+    }
+}
+
+fn statement(datum: &Datum) -> ReaderResult<Stmt> {
+    let (head, args, line) = match datum {
+        Datum::List(items, line) => match items.split_first() {
+            Some((Datum::Atom(head, _), args)) => (head.as_str(), args, *line),
+            _ => return Err(ReaderError::UnknownForm(String::new(), *line)),
+        },
+        _ => {
+            return Err(ReaderError::MalformedForm(
+                "statement".to_string(),
+                datum.line(),
+            ))
+        }
+    };
+    let malformed = || ReaderError::MalformedForm(head.to_string(), line);
+
+    let stmt = match (head, args) {
+        ("expr", [expr]) => Stmt::Expression(ExpressionStmt {
+            expression: expression(expr)?,
+            line,
+        }),
+        ("print", [expr]) => Stmt::Print(PrintStmt {
+            expression: expression(expr)?,
+            line,
+        }),
+        ("var", [var_name]) => Stmt::Var(VarStmt {
+            name: name(var_name)?,
+            initializer: None,
+        }),
+        ("var", [var_name, initializer]) => Stmt::Var(VarStmt {
+            name: name(var_name)?,
+            initializer: Some(expression(initializer)?),
+        }),
+        ("block", stmts) => Stmt::Block(BlockStmt {
+            statements: stmts.iter().map(statement).collect::<ReaderResult<_>>()?,
+            line,
+        }),
+        ("if", [condition, then_branch]) => Stmt::If(IfStmt {
+            condition: expression(condition)?,
+            then_branch: Box::new(statement(then_branch)?),
+            else_branch: None,
+            line,
+        }),
+        ("if", [condition, then_branch, else_branch]) => Stmt::If(IfStmt {
+            condition: expression(condition)?,
+            then_branch: Box::new(statement(then_branch)?),
+            else_branch: Some(Box::new(statement(else_branch)?)),
+            line,
+        }),
+        ("while", [condition, body]) => Stmt::While(WhileStmt {
+            condition: expression(condition)?,
+            body: Box::new(statement(body)?),
+            line,
+        }),
+        ("fun", _) => Stmt::Function(function(datum)?),
+        ("return", []) => Stmt::Return(ReturnStmt {
+            keyword: Token::new(TT::Return, line),
+            value: None,
+            tail_call: Default::default(),
+        }),
+        ("return", [value]) => Stmt::Return(ReturnStmt {
+            keyword: Token::new(TT::Return, line),
+            value: Some(expression(value)?),
+            tail_call: Default::default(),
+        }),
+        ("class", [class_name, rest @ ..]) => {
+            let (superclass, methods) = match rest.split_first() {
+                Some((Datum::List(items, _), methods)) => match items.as_slice() {
+                    [Datum::Atom(less, _), superclass] if less == "<" => (
+                        Some(Variable {
+                            name: name(superclass)?,
+                            slot: Default::default(),
+                        }),
+                        methods,
+                    ),
+                    _ => (None, rest),
+                },
+                _ => (None, rest),
+            };
+
+            Stmt::Class(ClassStmt {
+                name: name(class_name)?,
+                superclass,
+                methods: methods.iter().map(function).collect::<ReaderResult<_>>()?,
+            })
+        }
+        ("expr" | "print" | "var" | "if" | "while" | "return" | "class", _) => {
+            return Err(malformed())
+        }
+        _ => return Err(ReaderError::UnknownForm(head.to_string(), line)),
+    };
+
+    Ok(stmt)
+}
+
+fn expression(datum: &Datum) -> ReaderResult<Expr> {
+    let (head, args, line) = match datum {
+        Datum::Str(string, line) => return Ok(literal(string.clone().into(), *line)),
+        Datum::Atom(atom, line) => {
+            let expr = match atom.as_str() {
+                "true" => literal(true.into(), *line),
+                "false" => literal(false.into(), *line),
+                "nil" => literal(LiteralValue::None, *line),
+                "this" => Expr::This(ThisExpr {
+                    keyword: Token::new(TT::This, *line),
+                    slot: Default::default(),
+                }),
+                atom if is_number(atom) => literal(number(atom, *line)?.into(), *line),
+                _ => Expr::Variable(Variable {
+                    name: name(datum)?,
+                    slot: Default::default(),
+                }),
+            };
+            return Ok(expr);
+        }
+        Datum::List(items, line) => match items.split_first() {
+            Some((Datum::Atom(head, _), args)) => (head.as_str(), args, *line),
+            _ => return Err(ReaderError::UnknownForm(String::new(), *line)),
+        },
+    };
+    let malformed = || ReaderError::MalformedForm(head.to_string(), line);
+    let boxed = |datum: &Datum| expression(datum).map(Box::new);
+
+    let expr = match (head, args) {
+        ("group", [expr]) => Expr::Grouping(Grouping {
+            expression: boxed(expr)?,
+            line,
+        }),
+        ("assign", [var_name, value]) => Expr::Assign(AssignExpr {
+            name: name(var_name)?,
+            value: boxed(value)?,
+            slot: Default::default(),
+        }),
+        ("and", [left, right]) => Expr::Logical(LogicalExpr {
+            left: boxed(left)?,
+            operator: Token::new(TT::And, line),
+            right: boxed(right)?,
+        }),
+        ("or", [left, right]) => Expr::Logical(LogicalExpr {
+            left: boxed(left)?,
+            operator: Token::new(TT::Or, line),
+            right: boxed(right)?,
+        }),
+        ("call", [callee, arguments @ ..]) => Expr::Call(CallExpr {
+            callee: boxed(callee)?,
+            paren: Token::new(TT::RightParen, line),
+            arguments: arguments
+                .iter()
+                .map(expression)
+                .collect::<ReaderResult<_>>()?,
+        }),
+        ("get", [object, property]) => Expr::Get(GetExpr {
+            object: boxed(object)?,
+            name: name(property)?,
+        }),
+        ("set", [object, property, value]) => Expr::Set(SetExpr {
+            object: boxed(object)?,
+            name: name(property)?,
+            value: boxed(value)?,
+        }),
+        ("super", [method]) => Expr::Super(SuperExpr {
+            keyword: Token::new(TT::Super, line),
+            method: name(method)?,
+            slot: Default::default(),
+        }),
+        ("-" | "!", [right]) => Expr::Unary(UnaryExpr {
+            operator: Token::new(operator(head).unwrap(), line),
+            right: boxed(right)?,
+        }),
+        (head, [left, right]) if operator(head).is_some() && head != "!" => {
+            Expr::Binary(BinaryExpr {
+                left: boxed(left)?,
+                operator: Token::new(operator(head).unwrap(), line),
+                right: boxed(right)?,
+            })
+        }
+        ("group" | "assign" | "and" | "or" | "call" | "get" | "set" | "super", _) => {
+            return Err(malformed())
+        }
+        (head, _) if operator(head).is_some() => return Err(malformed()),
+        _ => return Err(ReaderError::UnknownForm(head.to_string(), line)),
+    };
+
+    Ok(expr)
+}
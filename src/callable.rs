@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError, TracedError, Unwind};
+use crate::native::{Arity, NativeError, UserData};
+use crate::symbol::Symbol;
+use crate::syntax::{FunctionStmt, Slot};
+use crate::token::Token;
+use crate::value::Value;
+
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionStmt>,
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn name(&self) -> String {
+        self.declaration.name.token_type.to_string()
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.push(Value::Instance(instance));
+
+        LoxFunction {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    /// Runs the function's body. A call it returns in tail position isn't
+    /// made here but handed back as `Unwind::TailCall`, for the caller to
+    /// make once this call is gone.
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Unwind> {
+        // Parameters are the first locals, in order
+        let environment = Environment::with_slots(self.closure.clone(), arguments);
+        let environment = interpreter.environment(environment);
+
+        let result = interpreter.execute_block(&self.declaration.body, environment);
+
+        let value = match result {
+            Ok(_) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(unwind) => return Err(unwind),
+        };
+
+        if self.is_initializer {
+            let this = Slot { depth: 0, index: 0 };
+            return Ok(self.closure.borrow().get_at(this));
+        }
+
+        Ok(value)
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+/// A native's body, taking arguments whose count matches its arity. An
+/// `Err` is raised as a runtime error at the call site.
+pub type NativeFn = Box<dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, NativeError>>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Arity,
+    pub function: NativeFn,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// A method on a class, written either in Lox or in Rust.
+#[derive(Clone)]
+pub enum Method {
+    Lox(Rc<LoxFunction>),
+    /// A native whose first argument is the instance the method was called on.
+    Native(Rc<NativeFunction>),
+}
+
+impl Method {
+    pub fn arity(&self) -> Arity {
+        match self {
+            Method::Lox(function) => Arity::Fixed(function.arity()),
+            // `NativeClass::method` makes sure there's a parameter for `this`
+            Method::Native(native) => match native.arity {
+                Arity::Fixed(arity) => Arity::Fixed(arity - 1),
+                Arity::AtLeast(arity) => Arity::AtLeast(arity - 1),
+            },
+        }
+    }
+
+    /// Binds `this` to `instance`, giving a value that can be called.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> Value {
+        match self {
+            Method::Lox(function) => Value::Function(Rc::new(function.bind(instance))),
+            Method::Native(native) => {
+                let native = native.clone();
+                let is_initializer = native.name == "init";
+                Value::Native(Rc::new(NativeFunction {
+                    name: native.name.clone(),
+                    arity: self.arity(),
+                    function: Box::new(move |interpreter, arguments| {
+                        let this = Value::Instance(instance.clone());
+                        let arguments = [std::slice::from_ref(&this), arguments].concat();
+                        let value = (native.function)(interpreter, &arguments)?;
+                        if !is_initializer {
+                            return Ok(value);
+                        }
+
+                        // A native initializer hands back the Rust value the
+                        // instance wraps, and like any initializer returns `this`
+                        if let Value::UserData(data) = value {
+                            instance.borrow_mut().data = Some(data);
+                        }
+                        Ok(this)
+                    }),
+                }))
+            }
+        }
+    }
+}
+
+/// The name of a class's initializer.
+pub(crate) fn init_symbol() -> Symbol {
+    static INIT: OnceLock<Symbol> = OnceLock::new();
+    *INIT.get_or_init(|| Symbol::intern("init"))
+}
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<Symbol, Method>,
+}
+
+impl LoxClass {
+    pub fn find_method(&self, name: Symbol) -> Option<Method> {
+        match self.methods.get(&name) {
+            Some(method) => Some(method.clone()),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.find_method(init_symbol())
+            .map_or(Arity::Fixed(0), |initializer| initializer.arity())
+    }
+
+    pub fn instantiate(
+        class: &Rc<LoxClass>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        line: Option<usize>,
+    ) -> Result<Value, TracedError> {
+        let instance = interpreter.instance(LoxInstance {
+            class: class.clone(),
+            fields: HashMap::new(),
+            data: None,
+        });
+
+        if let Some(initializer) = class.find_method(init_symbol()) {
+            interpreter.call_value(initializer.bind(instance.clone()), arguments, line)?;
+        }
+
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<Symbol, Value>,
+    /// The Rust value behind an instance of a class defined by the host.
+    pub data: Option<UserData>,
+}
+
+impl LoxInstance {
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeError> {
+        let key = name.symbol();
+
+        if let Some(value) = instance.borrow().fields.get(&key) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(key);
+        match method {
+            Some(method) => Ok(method.bind(instance.clone())),
+            None => Err(RuntimeError::UndefinedProperty(key.to_string(), name.line)),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.symbol(), value);
+    }
+}
+
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
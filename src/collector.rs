@@ -0,0 +1,243 @@
+//! Frees what the tree-walker's reference counting can't on its own: cycles,
+//! like an instance holding one of its own bound methods, or a function
+//! stored in the scope it closes over.
+//!
+//! Only environments and instances change after they're made, so every
+//! cycle runs through one of them, and they're the objects the collector
+//! keeps track of. Every so often it finds all it can reach from them and
+//! counts how many references each object has from the others. An object
+//! with more references than that is held from outside, by a running call or
+//! by the host, and so is everything it reaches. Whatever is left is only
+//! kept alive by cycles, and emptying it breaks them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::callable::{LoxClass, LoxFunction, LoxInstance, Method};
+use crate::environment::Environment;
+use crate::value::Value;
+
+/// How many objects are tracked before the first collection.
+const FIRST_COLLECTION: usize = 1024;
+
+pub(crate) struct Collector {
+    environments: Vec<Weak<RefCell<Environment>>>,
+    instances: Vec<Weak<RefCell<LoxInstance>>>,
+    /// Collect once this many objects are tracked, dead or alive. Each
+    /// collection sets it to twice what survived, so the time spent
+    /// collecting stays in proportion to the time spent allocating.
+    collect_at: usize,
+}
+
+impl Collector {
+    pub(crate) fn new() -> Collector {
+        Collector {
+            environments: Vec::new(),
+            instances: Vec::new(),
+            collect_at: FIRST_COLLECTION,
+        }
+    }
+
+    pub(crate) fn environment(&mut self, environment: Environment) -> Rc<RefCell<Environment>> {
+        self.collect_if_due();
+        let environment = Rc::new(RefCell::new(environment));
+        self.environments.push(Rc::downgrade(&environment));
+        environment
+    }
+
+    pub(crate) fn instance(&mut self, instance: LoxInstance) -> Rc<RefCell<LoxInstance>> {
+        self.collect_if_due();
+        let instance = Rc::new(RefCell::new(instance));
+        self.instances.push(Rc::downgrade(&instance));
+        instance
+    }
+
+    /// How many tracked objects are alive, or at least not yet collected.
+    pub(crate) fn object_count(&self) -> usize {
+        let environments = self
+            .environments
+            .iter()
+            .filter(|weak| weak.strong_count() > 0);
+        let instances = self.instances.iter().filter(|weak| weak.strong_count() > 0);
+        environments.count() + instances.count()
+    }
+
+    fn collect_if_due(&mut self) {
+        if self.environments.len() + self.instances.len() >= self.collect_at {
+            self.collect();
+            let tracked = self.environments.len() + self.instances.len();
+            self.collect_at = FIRST_COLLECTION.max(tracked * 2);
+        }
+    }
+
+    /// Frees every object only cycles keep alive.
+    pub(crate) fn collect(&mut self) {
+        self.environments.retain(|weak| weak.strong_count() > 0);
+        self.instances.retain(|weak| weak.strong_count() > 0);
+
+        let mut pending: Vec<Object> = self
+            .environments
+            .iter()
+            .filter_map(|weak| weak.upgrade().map(Object::Environment))
+            .chain(
+                self.instances
+                    .iter()
+                    .filter_map(|weak| weak.upgrade().map(Object::Instance)),
+            )
+            .collect();
+
+        // Everything reachable from the tracked objects, each with the
+        // addresses of what it references, once per reference
+        let mut nodes: HashMap<usize, Node> = HashMap::new();
+        while let Some(object) = pending.pop() {
+            let address = object.address();
+            if nodes.contains_key(&address) {
+                continue;
+            }
+            let references = object.references().map(|references| {
+                let addresses = references.iter().map(Object::address).collect();
+                pending.extend(references);
+                addresses
+            });
+            nodes.insert(
+                address,
+                Node {
+                    object,
+                    references,
+                    referenced: 0,
+                    reached: false,
+                },
+            );
+        }
+
+        let edges: Vec<usize> = nodes
+            .values()
+            .flat_map(|node| node.references.iter().flatten().copied())
+            .collect();
+        for address in edges {
+            if let Some(node) = nodes.get_mut(&address) {
+                node.referenced += 1;
+            }
+        }
+
+        // The only other reference the collector holds is the node's own.
+        // One borrowed right now can't be looked into, so it has to stay.
+        let mut reached: Vec<usize> = nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.references.is_none() || node.object.strong_count() > node.referenced + 1
+            })
+            .map(|(&address, _)| address)
+            .collect();
+        while let Some(address) = reached.pop() {
+            let Some(node) = nodes.get_mut(&address) else {
+                continue;
+            };
+            if node.reached {
+                continue;
+            }
+            node.reached = true;
+            reached.extend(node.references.iter().flatten().copied());
+        }
+
+        // Dropped once nothing is borrowed, in case freeing a host value
+        // runs code that looks at the objects
+        let mut scopes = Vec::new();
+        let mut fields = Vec::new();
+        for node in nodes.values().filter(|node| !node.reached) {
+            match &node.object {
+                Object::Environment(environment) => {
+                    if let Ok(mut environment) = environment.try_borrow_mut() {
+                        scopes.push(std::mem::take(&mut *environment));
+                    }
+                }
+                Object::Instance(instance) => {
+                    if let Ok(mut instance) = instance.try_borrow_mut() {
+                        fields.push(std::mem::take(&mut instance.fields));
+                    }
+                }
+                Object::Function(_) | Object::Class(_) => {}
+            }
+        }
+        drop(nodes);
+        drop(scopes);
+        drop(fields);
+    }
+}
+
+struct Node {
+    object: Object,
+    /// What the object references, or `None` if it's borrowed mutably and
+    /// can't be looked at.
+    references: Option<Vec<usize>>,
+    /// How many references to it other nodes hold.
+    referenced: usize,
+    reached: bool,
+}
+
+/// The objects that can be part of a cycle. Natives, strings and user data
+/// can't reference Lox values the collector knows about, so they're left
+/// out and anything they do hold counts as held from outside.
+enum Object {
+    Environment(Rc<RefCell<Environment>>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+}
+
+impl Object {
+    fn of(value: &Value) -> Option<Object> {
+        match value {
+            Value::Function(function) => Some(Object::Function(function.clone())),
+            Value::Class(class) => Some(Object::Class(class.clone())),
+            Value::Instance(instance) => Some(Object::Instance(instance.clone())),
+            _ => None,
+        }
+    }
+
+    fn address(&self) -> usize {
+        match self {
+            Object::Environment(environment) => Rc::as_ptr(environment) as *const () as usize,
+            Object::Instance(instance) => Rc::as_ptr(instance) as *const () as usize,
+            Object::Function(function) => Rc::as_ptr(function) as *const () as usize,
+            Object::Class(class) => Rc::as_ptr(class) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Environment(environment) => Rc::strong_count(environment),
+            Object::Instance(instance) => Rc::strong_count(instance),
+            Object::Function(function) => Rc::strong_count(function),
+            Object::Class(class) => Rc::strong_count(class),
+        }
+    }
+
+    fn references(&self) -> Option<Vec<Object>> {
+        let mut references = Vec::new();
+        match self {
+            Object::Environment(environment) => {
+                let environment = environment.try_borrow().ok()?;
+                references.extend(environment.values().filter_map(Object::of));
+                references.extend(environment.enclosing().cloned().map(Object::Environment));
+            }
+            Object::Instance(instance) => {
+                let instance = instance.try_borrow().ok()?;
+                references.push(Object::Class(instance.class.clone()));
+                references.extend(instance.fields.values().filter_map(Object::of));
+            }
+            Object::Function(function) => {
+                references.push(Object::Environment(function.closure.clone()));
+            }
+            Object::Class(class) => {
+                references.extend(class.superclass.clone().map(Object::Class));
+                references.extend(class.methods.values().filter_map(|method| match method {
+                    Method::Lox(function) => Some(Object::Function(function.clone())),
+                    Method::Native(_) => None,
+                }));
+            }
+        }
+        Some(references)
+    }
+}
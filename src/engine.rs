@@ -0,0 +1,274 @@
+//! The embedding API: an `Engine` owns a Lox session that host code can feed
+//! source to, read results back from, and share global variables with.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::interpreter::{Interpreter, RuntimeError, TracedError};
+use crate::native::{Arity, FromValue, IntoArguments, NativeCallable, NativeClass, NativeError};
+use crate::optimizer;
+use crate::parser::{Parser, ParserError};
+use crate::resolver::{Resolver, ResolverError};
+use crate::scanner::{Scanner, ScannerError};
+use crate::symbol::Symbol;
+use crate::syntax::{Expr, Stmt};
+use crate::value::Value;
+use crate::vm::cache::CacheError;
+use crate::vm::compiler::CompileError;
+
+/// Everything that can go wrong running Lox code. Scanning, parsing and
+/// resolving report every error they find, so those carry a list.
+#[derive(Debug)]
+pub enum Error {
+    Scan(Vec<ScannerError>),
+    Parse(Vec<ParserError>),
+    Resolve(Vec<ResolverError>),
+    Compile(Vec<CompileError>),
+    Cache(CacheError),
+    Runtime(TracedError),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn lines(f: &mut fmt::Formatter, errors: &[impl fmt::Display]) -> fmt::Result {
+            for (i, err) in errors.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "{}", err)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Error::Scan(errors) => {
+                let errors = errors
+                    .iter()
+                    .map(|err| format!("[line {}] Error: {}", err.line(), err))
+                    .collect::<Vec<String>>();
+                lines(f, &errors)?
+            }
+            Error::Parse(errors) => lines(f, errors)?,
+            Error::Resolve(errors) => lines(f, errors)?,
+            Error::Compile(errors) => lines(f, errors)?,
+            Error::Cache(err) => write!(f, "{}", err)?,
+            Error::Runtime(err) => write!(f, "{}", err)?,
+            Error::Io(err) => write!(f, "IO Error: {}", err)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(err.into())
+    }
+}
+
+impl From<TracedError> for Error {
+    fn from(err: TracedError) -> Self {
+        Error::Runtime(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Scans, parses, resolves and optimizes a program, the front end both
+/// backends share.
+pub(crate) fn compile(source: &str) -> Result<Vec<Stmt>, Error> {
+    let tokens = Scanner::new(source).scan_tokens().map_err(Error::Scan)?;
+    let stmts = Parser::new(tokens).parse().map_err(Error::Parse)?;
+    Resolver::new().resolve(&stmts).map_err(Error::Resolve)?;
+
+    Ok(optimizer::optimize(stmts))
+}
+
+/// A Lox session. Globals persist across calls, so each piece of source sees
+/// what earlier ones defined.
+pub struct Engine {
+    interpreter: Interpreter,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.interpreter.set_output(output);
+    }
+
+    /// Frees what only reference cycles keep alive, like an instance storing
+    /// one of its own bound methods. This also happens on its own as a
+    /// session makes more scopes and instances.
+    pub fn collect_garbage(&mut self) {
+        self.interpreter.collect_garbage();
+    }
+
+    /// How many scopes and instances are alive, or at least not yet
+    /// collected.
+    pub fn object_count(&self) -> usize {
+        self.interpreter.object_count()
+    }
+
+    /// Sets how much of the Rust stack Lox calls can take before they fail
+    /// with a stack overflow, `rlox::interpreter::DEFAULT_STACK_LIMIT` by
+    /// default. Raise it when running on a thread with a bigger stack than
+    /// that to allow deeper recursion.
+    pub fn set_stack_limit(&mut self, bytes: usize) {
+        self.interpreter.set_stack_limit(bytes);
+    }
+
+    /// Runs a program.
+    pub fn run(&mut self, source: &str) -> Result<(), Error> {
+        let stmts = self.compile(source)?;
+        self.execute(&stmts)
+    }
+
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let source = fs::read_to_string(path)?;
+        self.run(&source)
+    }
+
+    /// Runs `source` and returns its value: the value of a bare expression
+    /// like `1 + 2`, or of the final statement if it's an expression
+    /// statement, and `nil` otherwise.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let tokens = Scanner::new(source).scan_tokens().map_err(Error::Scan)?;
+
+        let stmts = match Parser::new(tokens.clone()).parse() {
+            Ok(stmts) => stmts,
+            Err(errors) => match Parser::new(tokens).parse_expression() {
+                Ok(expr) => {
+                    Resolver::new()
+                        .resolve_expression(&expr)
+                        .map_err(Error::Resolve)?;
+                    return self.evaluate(&expr);
+                }
+                Err(_) => return Err(Error::Parse(errors)),
+            },
+        };
+        Resolver::new().resolve(&stmts).map_err(Error::Resolve)?;
+        let stmts = optimizer::optimize(stmts);
+
+        match stmts.split_last() {
+            Some((Stmt::Expression(last), rest)) => {
+                self.execute(rest)?;
+                self.evaluate(&last.expression)
+            }
+            _ => self.execute(&stmts).map(|_| Value::Nil),
+        }
+    }
+
+    /// Scans, parses, resolves and optimizes `source` without running it.
+    pub fn compile(&self, source: &str) -> Result<Vec<Stmt>, Error> {
+        compile(source)
+    }
+
+    /// Runs statements that have already been through `compile`.
+    pub fn execute(&mut self, stmts: &[Stmt]) -> Result<(), Error> {
+        Ok(self.interpreter.interpret(stmts)?)
+    }
+
+    /// Evaluates an expression that has already been resolved.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, Error> {
+        Ok(self.interpreter.evaluate(expr)?)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) {
+        self.interpreter
+            .globals
+            .borrow_mut()
+            .define(name, value.into());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let name = Symbol::find(name)?;
+        self.interpreter.globals.borrow().lookup(name)
+    }
+
+    /// The names of all globals, sorted.
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names = self.interpreter.globals.borrow().names();
+        names.sort();
+        names
+    }
+
+    /// Defines a global native function working directly on `Value`s.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Value]) -> Result<Value, NativeError> + 'static,
+    ) {
+        self.interpreter.define_native(name, arity, function);
+    }
+
+    /// Defines a global native function from a Rust closure, converting its
+    /// arguments and result. See `native` for what it can take and return.
+    pub fn register<Args>(&mut self, name: &str, function: impl NativeCallable<Args>) {
+        self.interpreter.register(name, function);
+    }
+
+    /// Calls a Lox function, native or class, converting the result to `R`.
+    ///
+    /// ```
+    /// let mut engine = rlox::Engine::new();
+    /// engine.run("fun greet(name) { return \"Hello, \" + name; }").unwrap();
+    /// let greeting: String = engine.call_global("greet", ("Lox",)).unwrap();
+    /// assert_eq!(greeting, "Hello, Lox");
+    /// ```
+    pub fn call<R: FromValue>(
+        &mut self,
+        callee: &Value,
+        arguments: impl IntoArguments,
+    ) -> Result<R, Error> {
+        Ok(self.interpreter.call(callee, arguments)?)
+    }
+
+    /// Calls the global function `name`.
+    pub fn call_global<R: FromValue>(
+        &mut self,
+        name: &str,
+        arguments: impl IntoArguments,
+    ) -> Result<R, Error> {
+        Ok(self.interpreter.call_global(name, arguments)?)
+    }
+
+    /// Calls the method `name` on an instance.
+    pub fn call_method<R: FromValue>(
+        &mut self,
+        receiver: &Value,
+        name: &str,
+        arguments: impl IntoArguments,
+    ) -> Result<R, Error> {
+        Ok(self.interpreter.call_method(receiver, name, arguments)?)
+    }
+
+    /// Defines a class implemented in Rust as a global.
+    pub fn define_class(&mut self, class: NativeClass) {
+        let class = class.build();
+        self.set_global(&class.name.clone(), Value::Class(Rc::new(class)));
+    }
+}
@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::RuntimeError;
+use crate::symbol::Symbol;
+use crate::syntax::Slot;
+use crate::token::Token;
+use crate::value::Value;
+
+/// One scope's variables. Globals are looked up by name, since they can be
+/// used before they're defined, but the resolver has already worked out
+/// where every local is, so those live in `slots` in the order they were
+/// declared and are reached by index.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    slots: Vec<Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment::with_slots(enclosing, Vec::new())
+    }
+
+    /// A scope whose first locals are `slots`, like a call's arguments.
+    pub fn with_slots(enclosing: Rc<RefCell<Environment>>, slots: Vec<Value>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            slots,
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Defines a global.
+    pub fn define(&mut self, name: impl Into<Symbol>, value: Value) {
+        self.values.insert(name.into(), value);
+    }
+
+    /// Defines the next local, which takes the slot after the last one.
+    pub fn push(&mut self, value: Value) {
+        self.slots.push(value);
+    }
+
+    pub fn lookup(&self, name: Symbol) -> Option<Value> {
+        self.values.get(&name).cloned()
+    }
+
+    /// The values of all the scope's variables, globals and locals alike.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        self.values.values().chain(&self.slots)
+    }
+
+    pub(crate) fn enclosing(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.enclosing.as_ref()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.values.keys().map(|name| name.to_string()).collect()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        let key = name.symbol();
+        self.lookup(key)
+            .ok_or(RuntimeError::UndefinedVariable(key.to_string(), name.line))
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        let key = name.symbol();
+        match self.values.get_mut(&key) {
+            Some(variable) => {
+                *variable = value;
+                Ok(())
+            }
+            None => Err(RuntimeError::UndefinedVariable(key.to_string(), name.line)),
+        }
+    }
+
+    pub fn get_at(&self, slot: Slot) -> Value {
+        match slot.depth {
+            0 => self.slots[slot.index].clone(),
+            depth => self.ancestor().borrow().get_at(Slot {
+                depth: depth - 1,
+                ..slot
+            }),
+        }
+    }
+
+    pub fn assign_at(&mut self, slot: Slot, value: Value) {
+        match slot.depth {
+            0 => self.slots[slot.index] = value,
+            depth => self.ancestor().borrow_mut().assign_at(
+                Slot {
+                    depth: depth - 1,
+                    ..slot
+                },
+                value,
+            ),
+        }
+    }
+
+    fn ancestor(&self) -> &Rc<RefCell<Environment>> {
+        self.enclosing
+            .as_ref()
+            .expect("the resolver only counts scopes that exist")
+    }
+}
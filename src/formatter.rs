@@ -0,0 +1,311 @@
+//! Pretty-prints an AST back into Lox source with four-space indentation, for
+//! `rlox fmt`.
+//!
+//! Only what survives parsing can be printed: comments are dropped and `for`
+//! loops come back out as the `while` loops the parser desugars them into.
+//! `lost_in_formatting` says whether a source has either, so `rlox fmt -w`
+//! can refuse to overwrite it.
+
+use crate::{
+    scanner::Scanner,
+    syntax::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, ExpressionStmt, FunctionStmt,
+        GetExpr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt,
+        SetExpr, Stmt, SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+    },
+    token::{Token, TokenType},
+    visit::Visitor,
+};
+
+const INDENT: &str = "    ";
+
+/// What of `source` formatting would lose, if anything: its first comment or
+/// `for` loop, described for an error message. `None` for a source that
+/// doesn't scan, since it won't format either.
+pub fn lost_in_formatting(source: &str) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens().ok()?;
+
+    let comment = scanner
+        .comment_lines()
+        .first()
+        .map(|&line| (line, "comment"));
+    let for_loop = tokens
+        .iter()
+        .find(|token| token.token_type == TokenType::For)
+        .map(|token| (token.line, "for loop"));
+
+    let (line, what) = [comment, for_loop].into_iter().flatten().min()?;
+    Some(format!("the {} on line {}", what, line))
+}
+
+pub struct SourceFormatter {
+    depth: usize,
+}
+
+impl SourceFormatter {
+    pub fn new() -> SourceFormatter {
+        SourceFormatter { depth: 0 }
+    }
+
+    /// Formats a whole program, separating functions and classes from their
+    /// neighbours with a blank line.
+    pub fn format(&self, statements: &[Stmt]) -> String {
+        let mut source = String::new();
+        for (i, stmt) in statements.iter().enumerate() {
+            let spaced = |stmt: &Stmt| matches!(stmt, Stmt::Function(_) | Stmt::Class(_));
+            if i > 0 && (spaced(stmt) || spaced(&statements[i - 1])) {
+                source.push('\n');
+            }
+            source.push_str(&self.visit_statement(stmt));
+            source.push('\n');
+        }
+        source
+    }
+
+    fn pad(&self) -> String {
+        INDENT.repeat(self.depth)
+    }
+
+    fn nested(&self) -> SourceFormatter {
+        SourceFormatter {
+            depth: self.depth + 1,
+        }
+    }
+
+    /// The `{ ... }` of a block, starting at the brace so it can follow a
+    /// header on the same line.
+    fn braces(&self, statements: &[Stmt]) -> String {
+        if statements.is_empty() {
+            return "{}".to_string();
+        }
+
+        let nested = self.nested();
+        let body = statements
+            .iter()
+            .map(|stmt| nested.visit_statement(stmt))
+            .collect::<Vec<String>>();
+        format!("{{\n{}\n{}}}", body.join("\n"), self.pad())
+    }
+
+    /// The body of an `if`, `else` or `while`: a block stays on the header's
+    /// line, anything else goes on the next line one level deeper.
+    fn body(&self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Block(block) => format!(" {}", self.braces(&block.statements)),
+            stmt => format!("\n{}", self.nested().visit_statement(stmt)),
+        }
+    }
+
+    fn function(&self, stmt: &FunctionStmt) -> String {
+        let params = stmt.params.iter().map(name).collect::<Vec<String>>();
+        format!(
+            "{}({}) {}",
+            name(&stmt.name),
+            params.join(", "),
+            self.braces(&stmt.body)
+        )
+    }
+}
+
+fn name(token: &Token) -> String {
+    token.token_type.to_string()
+}
+
+impl Default for SourceFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visitor for SourceFormatter {
+    type E = String;
+
+    fn visit_expression_stmt(&self, stmt: &ExpressionStmt) -> String {
+        format!("{}{};", self.pad(), self.visit_expression(&stmt.expression))
+    }
+
+    fn visit_print_stmt(&self, stmt: &PrintStmt) -> String {
+        format!(
+            "{}print {};",
+            self.pad(),
+            self.visit_expression(&stmt.expression)
+        )
+    }
+
+    fn visit_var_stmt(&self, stmt: &VarStmt) -> String {
+        match &stmt.initializer {
+            Some(initializer) => format!(
+                "{}var {} = {};",
+                self.pad(),
+                name(&stmt.name),
+                self.visit_expression(initializer)
+            ),
+            None => format!("{}var {};", self.pad(), name(&stmt.name)),
+        }
+    }
+
+    fn visit_block_stmt(&self, stmt: &BlockStmt) -> String {
+        format!("{}{}", self.pad(), self.braces(&stmt.statements))
+    }
+
+    fn visit_if_stmt(&self, stmt: &IfStmt) -> String {
+        let mut source = format!(
+            "{}if ({}){}",
+            self.pad(),
+            self.visit_expression(&stmt.condition),
+            self.body(&stmt.then_branch)
+        );
+
+        if let Some(else_branch) = &stmt.else_branch {
+            match *stmt.then_branch {
+                Stmt::Block(_) => source.push_str(" else"),
+                _ => source.push_str(&format!("\n{}else", self.pad())),
+            }
+            match else_branch.as_ref() {
+                Stmt::If(_) => {
+                    source.push(' ');
+                    source.push_str(self.visit_statement(else_branch).trim_start());
+                }
+                else_branch => source.push_str(&self.body(else_branch)),
+            }
+        }
+
+        source
+    }
+
+    fn visit_while_stmt(&self, stmt: &WhileStmt) -> String {
+        format!(
+            "{}while ({}){}",
+            self.pad(),
+            self.visit_expression(&stmt.condition),
+            self.body(&stmt.body)
+        )
+    }
+
+    fn visit_function_stmt(&self, stmt: &FunctionStmt) -> String {
+        format!("{}fun {}", self.pad(), self.function(stmt))
+    }
+
+    fn visit_return_stmt(&self, stmt: &ReturnStmt) -> String {
+        match &stmt.value {
+            Some(value) => format!("{}return {};", self.pad(), self.visit_expression(value)),
+            None => format!("{}return;", self.pad()),
+        }
+    }
+
+    fn visit_class_stmt(&self, stmt: &ClassStmt) -> String {
+        let mut source = format!("{}class {}", self.pad(), name(&stmt.name));
+        if let Some(superclass) = &stmt.superclass {
+            source.push_str(&format!(" < {}", name(&superclass.name)));
+        }
+
+        if stmt.methods.is_empty() {
+            source.push_str(" {}");
+            return source;
+        }
+
+        let nested = self.nested();
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| format!("{}{}", nested.pad(), nested.function(method)))
+            .collect::<Vec<String>>();
+        source.push_str(&format!(" {{\n{}\n{}}}", methods.join("\n\n"), self.pad()));
+        source
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> String {
+        format!(
+            "{} {} {}",
+            self.visit_expression(&expr.left),
+            name(&expr.operator),
+            self.visit_expression(&expr.right)
+        )
+    }
+
+    fn visit_grouping(&self, expr: &Grouping) -> String {
+        format!("({})", self.visit_expression(&expr.expression))
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> String {
+        match &expr.value {
+            LiteralValue::Float(float) => float.to_string(),
+            // Lox strings have no escapes, so the contents go out verbatim
+            LiteralValue::LoxString(string) => format!("\"{}\"", string),
+            LiteralValue::Bool(bool) => bool.to_string(),
+            LiteralValue::None => "nil".to_string(),
+        }
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> String {
+        format!(
+            "{}{}",
+            name(&expr.operator),
+            self.visit_expression(&expr.right)
+        )
+    }
+
+    fn visit_variable(&self, expr: &Variable) -> String {
+        name(&expr.name)
+    }
+
+    fn visit_assign(&self, expr: &AssignExpr) -> String {
+        format!(
+            "{} = {}",
+            name(&expr.name),
+            self.visit_expression(&expr.value)
+        )
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> String {
+        let operator = match expr.operator.token_type {
+            TokenType::And => "and",
+            _ => "or",
+        };
+        format!(
+            "{} {} {}",
+            self.visit_expression(&expr.left),
+            operator,
+            self.visit_expression(&expr.right)
+        )
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> String {
+        let arguments = expr
+            .arguments
+            .iter()
+            .map(|argument| self.visit_expression(argument))
+            .collect::<Vec<String>>();
+        format!(
+            "{}({})",
+            self.visit_expression(&expr.callee),
+            arguments.join(", ")
+        )
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> String {
+        format!(
+            "{}.{}",
+            self.visit_expression(&expr.object),
+            name(&expr.name)
+        )
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> String {
+        format!(
+            "{}.{} = {}",
+            self.visit_expression(&expr.object),
+            name(&expr.name),
+            self.visit_expression(&expr.value)
+        )
+    }
+
+    fn visit_this(&self, _expr: &ThisExpr) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> String {
+        format!("super.{}", name(&expr.method))
+    }
+}
@@ -1,39 +1,953 @@
-use crate::syntax::{BinaryExpr, Expr, Grouping, UnaryExpr};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::callable::{init_symbol, LoxClass, LoxFunction, LoxInstance, Method, NativeFunction};
+use crate::collector::Collector;
+use crate::environment::Environment;
+use crate::native::{Arity, Callsite, FromValue, IntoArguments, NativeCallable, NativeError};
+use crate::symbol::Symbol;
+use crate::syntax::{
+    AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, Expr, ExpressionStmt, FunctionStmt,
+    GetExpr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt,
+    SetExpr, Slot, Stmt, SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+};
+use crate::token::{Token, TokenType};
+use crate::value::{Strings, Value};
 use crate::visit::MutVisitor;
 
-pub struct Interpreter {}
+#[derive(Debug)]
+pub enum RuntimeError {
+    OperandMustBeNumber(TokenType, usize),
+    OperandsMustBeNumbers(TokenType, usize),
+    OperandsMustBeNumbersOrStrings(usize),
+    UndefinedVariable(String, usize),
+    UndefinedProperty(String, usize),
+    NotCallable(usize),
+    ArityMismatch(usize, usize, usize),
+    TooFewArguments(usize, usize, usize),
+    Native(String, usize),
+    OnlyInstancesHaveProperties(usize),
+    OnlyInstancesHaveFields(usize),
+    SuperclassMustBeClass(usize),
+    StackOverflow(usize),
+    /// A call made from Rust went wrong before reaching any Lox code, so
+    /// there's no line to point at.
+    HostCall(String),
+}
 
-impl MutVisitor for Interpreter {
-    type E = Result<Types, String>;
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::OperandMustBeNumber(operator, line) => {
+                write!(
+                    f,
+                    "Runtime Error: Operand of {} must be a number at line {}",
+                    operator, line
+                )?;
+            }
+            RuntimeError::OperandsMustBeNumbers(operator, line) => {
+                write!(
+                    f,
+                    "Runtime Error: Operands of {} must be numbers at line {}",
+                    operator, line
+                )?;
+            }
+            RuntimeError::OperandsMustBeNumbersOrStrings(line) => {
+                write!(
+                    f,
+                    "Runtime Error: Operands of + must be two numbers or two strings at line {}",
+                    line
+                )?;
+            }
+            RuntimeError::UndefinedVariable(name, line) => {
+                write!(
+                    f,
+                    "Runtime Error: Undefined variable '{}' at line {}",
+                    name, line
+                )?;
+            }
+            RuntimeError::UndefinedProperty(name, line) => {
+                write!(
+                    f,
+                    "Runtime Error: Undefined property '{}' at line {}",
+                    name, line
+                )?;
+            }
+            RuntimeError::NotCallable(line) => {
+                write!(
+                    f,
+                    "Runtime Error: Can only call functions and classes at line {}",
+                    line
+                )?;
+            }
+            RuntimeError::ArityMismatch(expected, got, line) => {
+                write!(
+                    f,
+                    "Runtime Error: Expected {} arguments but got {} at line {}",
+                    expected, got, line
+                )?;
+            }
+            RuntimeError::TooFewArguments(minimum, got, line) => {
+                write!(
+                    f,
+                    "Runtime Error: Expected at least {} arguments but got {} at line {}",
+                    minimum, got, line
+                )?;
+            }
+            RuntimeError::Native(message, line) => {
+                write!(f, "Runtime Error: {} at line {}", message, line)?;
+            }
+            RuntimeError::OnlyInstancesHaveProperties(line) => {
+                write!(
+                    f,
+                    "Runtime Error: Only instances have properties at line {}",
+                    line
+                )?;
+            }
+            RuntimeError::OnlyInstancesHaveFields(line) => {
+                write!(
+                    f,
+                    "Runtime Error: Only instances have fields at line {}",
+                    line
+                )?;
+            }
+            RuntimeError::SuperclassMustBeClass(line) => {
+                write!(
+                    f,
+                    "Runtime Error: Superclass must be a class at line {}",
+                    line
+                )?;
+            }
+            RuntimeError::StackOverflow(line) => {
+                write!(f, "Runtime Error: Stack overflow at line {}", line)?;
+            }
+            RuntimeError::HostCall(message) => {
+                write!(f, "Runtime Error: {}", message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RuntimeError {
+    pub fn line(&self) -> usize {
+        match *self {
+            RuntimeError::OperandMustBeNumber(_, line) => line,
+            RuntimeError::OperandsMustBeNumbers(_, line) => line,
+            RuntimeError::OperandsMustBeNumbersOrStrings(line) => line,
+            RuntimeError::UndefinedVariable(_, line) => line,
+            RuntimeError::UndefinedProperty(_, line) => line,
+            RuntimeError::NotCallable(line) => line,
+            RuntimeError::ArityMismatch(_, _, line) => line,
+            RuntimeError::TooFewArguments(_, _, line) => line,
+            RuntimeError::Native(_, line) => line,
+            RuntimeError::OnlyInstancesHaveProperties(line) => line,
+            RuntimeError::OnlyInstancesHaveFields(line) => line,
+            RuntimeError::SuperclassMustBeClass(line) => line,
+            RuntimeError::StackOverflow(line) => line,
+            RuntimeError::HostCall(_) => 0,
+        }
+    }
+}
+
+/// A call a runtime error unwound through on its way out.
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub function: String,
+    /// The line of the call, or `None` when Rust code made it.
+    pub called_from: Option<usize>,
+    pub native: bool,
+}
+
+/// How many of the calls replaced by tail calls a call keeps for its stack
+/// trace, on top of the one it started with.
+pub(crate) const TAIL_CALLS_TRACED: usize = 64;
+
+/// Records that `function` was replaced by a call it made in tail position
+/// at `line`, so a stack trace can still show it. To keep a loop written as
+/// recursion in constant space, a function repeating the last tail call
+/// isn't recorded again, and past `TAIL_CALLS_TRACED` the oldest records
+/// but the first are dropped.
+pub(crate) fn record_tail_call<F>(
+    callers: &mut Vec<(F, usize)>,
+    function: F,
+    line: usize,
+    same: impl Fn(&F, &F) -> bool,
+) {
+    if let Some((last, last_line)) = callers.last() {
+        if *last_line == line && same(last, &function) {
+            return;
+        }
+    }
+    if callers.len() > TAIL_CALLS_TRACED {
+        callers.remove(1);
+    }
+    callers.push((function, line));
+}
+
+/// A runtime error along with the stack trace of the calls it escaped,
+/// innermost first. Errors outside any function have an empty trace.
+#[derive(Debug)]
+pub struct TracedError {
+    pub error: RuntimeError,
+    pub trace: Vec<TraceFrame>,
+}
+
+impl TracedError {
+    pub fn new(error: RuntimeError) -> TracedError {
+        TracedError {
+            error,
+            trace: Vec::new(),
+        }
+    }
+
+    fn through(mut self, function: &str, called_from: Option<usize>, native: bool) -> Self {
+        self.trace.push(TraceFrame {
+            function: function.to_string(),
+            called_from,
+            native,
+        });
+        self
+    }
+}
+
+/// How many frames of a long stack trace are shown from each end. The ones
+/// between are only counted, so a stack overflow doesn't print thousands of
+/// lines.
+const TRACE_ENDS: usize = 10;
+
+impl fmt::Display for TracedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+
+        let hidden = match self.trace.len() {
+            len if len > 2 * TRACE_ENDS + 1 => TRACE_ENDS..len - TRACE_ENDS,
+            _ => 0..0,
+        };
 
-    fn visit_expression(&mut self, expr: &Expr) -> Self::E {
-        match expr {
-            Expr::Binary(BinaryExpr {
-                left: left_expr,
-                operator,
-                right: right_expr,
-            }) => {
-                let right = self.visit_expression(right_expr)?;
-                let left = self.visit_expression(left_expr)?;
+        // Each frame was executing the line its callee was called from
+        let mut line = Some(self.error.line());
+        for (i, frame) in self.trace.iter().enumerate() {
+            if i == hidden.start && !hidden.is_empty() {
+                write!(f, "\n    ... {} more calls", hidden.len())?;
             }
-            Expr::Unary(UnaryExpr {
-                operator,
-                right: right_expr,
-            }) => {
-                let right = self.visit_expression(right_expr)?;
+            if hidden.contains(&i) {
+                line = frame.called_from;
+                continue;
             }
-            Expr::Literal(lit) => {}
-            Expr::Grouping(Grouping { expression }) => {
-                let expr = self.visit_expression(expression)?;
+            match (frame.native, line) {
+                (false, Some(line)) => write!(f, "\n    in {}() at line {}", frame.function, line)?,
+                _ => write!(f, "\n    in {}()", frame.function)?,
             }
+            line = frame.called_from;
+        }
+        if let (Some(line), false) = (line, self.trace.is_empty()) {
+            write!(f, "\n    in script at line {}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<RuntimeError> for TracedError {
+    fn from(err: RuntimeError) -> Self {
+        TracedError::new(err)
+    }
+}
+
+/// Why execution left a statement early: either a `return` carrying its
+/// value up to the enclosing call, or a runtime error.
+#[derive(Debug)]
+pub enum Unwind {
+    Return(Value),
+    /// A `return` of a call to a Lox function, carried up to the call
+    /// being returned from so it can make this one in its place instead
+    /// of nesting it.
+    TailCall(Rc<LoxFunction>, Vec<Value>, usize),
+    Error(TracedError),
+}
 
+impl From<RuntimeError> for Unwind {
+    fn from(err: RuntimeError) -> Self {
+        Unwind::Error(TracedError::new(err))
+    }
+}
+
+impl From<TracedError> for Unwind {
+    fn from(err: TracedError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+type ExecResult = Result<Value, Unwind>;
+
+/// A tree-walking interpreter. Globals live as long as the interpreter does,
+/// so one instance can run many programs that build on each other's
+/// definitions, as the REPL does.
+pub struct Interpreter {
+    pub globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    strings: Strings,
+    objects: Collector,
+    output: Box<dyn Write>,
+    /// How many calls are running.
+    depth: usize,
+    /// Where on the Rust stack the outermost running call started.
+    stack_base: usize,
+    stack_limit: usize,
+}
+
+/// How deep calls can nest before a stack overflow, on either backend.
+pub(crate) const MAX_CALL_DEPTH: usize = 4096;
+
+/// How much of the Rust stack calls can take by default. Each Lox call
+/// takes a few kilobytes of it, and more in debug builds, so this keeps
+/// well inside the 2 MiB a Rust thread gets by default rather than
+/// reaching `MAX_CALL_DEPTH`.
+pub const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+/// Roughly where the Rust stack has got to, for measuring how much of it
+/// calls take.
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+pub(crate) fn clock() -> f64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_secs_f64()
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let interpreter = Interpreter {
+            environment: globals.clone(),
+            globals,
+            strings: Strings::new(),
+            objects: Collector::new(),
+            output: Box::new(io::stdout()),
+            depth: 0,
+            stack_base: 0,
+            stack_limit: DEFAULT_STACK_LIMIT,
+        };
+        interpreter.register("clock", clock);
+        interpreter
+    }
+
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    /// Makes a scope, which the collector frees if it ends up in a cycle.
+    pub(crate) fn environment(&mut self, environment: Environment) -> Rc<RefCell<Environment>> {
+        self.objects.environment(environment)
+    }
+
+    /// Makes an instance, which the collector frees if it ends up in a cycle.
+    pub(crate) fn instance(&mut self, instance: LoxInstance) -> Rc<RefCell<LoxInstance>> {
+        self.objects.instance(instance)
+    }
+
+    /// Frees the scopes and instances only reference cycles keep alive,
+    /// which otherwise happens as more are made.
+    pub fn collect_garbage(&mut self) {
+        self.objects.collect();
+    }
+
+    /// How many scopes and instances are alive, or at least not yet
+    /// collected.
+    pub fn object_count(&self) -> usize {
+        self.objects.object_count()
+    }
+
+    /// Sets how much of the Rust stack calls can take before they fail with
+    /// a stack overflow, rather than overflowing the stack and aborting the
+    /// process. On a thread with more stack than the default this lets
+    /// recursion go deeper, up to `MAX_CALL_DEPTH` calls.
+    pub fn set_stack_limit(&mut self, bytes: usize) {
+        self.stack_limit = bytes;
+    }
+
+    /// Defines a global native function working directly on `Value`s. It's
+    /// given the interpreter so it can call back into Lox.
+    pub fn define_native(
+        &self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Value]) -> Result<Value, NativeError> + 'static,
+    ) {
+        self.globals.borrow_mut().define(
+            name,
+            Value::Native(Rc::new(NativeFunction {
+                name: name.to_string(),
+                arity,
+                function: Box::new(function),
+            })),
+        );
+    }
+
+    /// Defines a global native function from a Rust closure, converting its
+    /// arguments and result. See `native` for what it can take and return.
+    pub fn register<Args>(&self, name: &str, function: impl NativeCallable<Args>) {
+        fn arity<Args, F: NativeCallable<Args>>(_: &F) -> Arity {
+            F::arity()
+        }
+
+        let owned_name = name.to_string();
+        self.define_native(name, arity(&function), move |interpreter, arguments| {
+            let callsite = Callsite {
+                name: &owned_name,
+                is_method: false,
+            };
+            function.call(interpreter, callsite, arguments)
+        });
+    }
+
+    /// Calls a Lox function, native or class from Rust. This works from
+    /// inside a native function too, while Lox code further up the stack is
+    /// waiting for it to return.
+    pub fn call<R: FromValue>(
+        &mut self,
+        callee: &Value,
+        arguments: impl IntoArguments,
+    ) -> Result<R, TracedError> {
+        let arguments = arguments.into_arguments();
+        let name = match callee {
+            Value::Function(function) => function.name(),
+            Value::Native(native) => native.name.clone(),
+            Value::Class(class) => class.name.clone(),
+            value => {
+                let message = format!("Can't call {}", value.type_name());
+                return Err(RuntimeError::HostCall(message).into());
+            }
+        };
+
+        match callable_arity(callee) {
+            Some(arity) if !arity.accepts(arguments.len()) => {
+                let message = format!("'{}' can't take {} arguments", name, arguments.len());
+                return Err(RuntimeError::HostCall(message).into());
+            }
+            _ => {}
+        }
+
+        let value = self.call_value(callee.clone(), arguments, None)?;
+        R::from_value(&value).ok_or_else(|| {
+            let message = format!(
+                "Expected '{}' to return {}, got {}",
+                name,
+                R::EXPECTED,
+                value.type_name()
+            );
+            RuntimeError::HostCall(message).into()
+        })
+    }
+
+    /// Calls the global function `name`.
+    pub fn call_global<R: FromValue>(
+        &mut self,
+        name: &str,
+        arguments: impl IntoArguments,
+    ) -> Result<R, TracedError> {
+        let callee = Symbol::find(name).and_then(|name| self.globals.borrow().lookup(name));
+        match callee {
+            Some(callee) => self.call(&callee, arguments),
+            None => {
+                let message = format!("Undefined global '{}'", name);
+                Err(RuntimeError::HostCall(message).into())
+            }
+        }
+    }
+
+    /// Calls the method `name` on `receiver`, which has to be an instance.
+    pub fn call_method<R: FromValue>(
+        &mut self,
+        receiver: &Value,
+        name: &str,
+        arguments: impl IntoArguments,
+    ) -> Result<R, TracedError> {
+        let instance = match receiver {
+            Value::Instance(instance) => instance,
+            value => {
+                let message = format!("Only instances have methods, got {}", value.type_name());
+                return Err(RuntimeError::HostCall(message).into());
+            }
+        };
+
+        // A name that was never interned can't be anyone's method
+        let method = Symbol::find(name).and_then(|symbol| {
+            let token = Token::new(TokenType::Identifier(symbol), 0);
+            LoxInstance::get(instance, &token).ok()
+        });
+        let Some(method) = method else {
+            let message = format!("Undefined method '{}'", name);
+            return Err(RuntimeError::HostCall(message).into());
+        };
+        self.call(&method, arguments)
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), TracedError> {
+        for stmt in statements {
+            match self.visit_statement(stmt) {
+                Ok(_) => {}
+                Err(Unwind::Error(err)) => {
+                    // A failure inside a block or call can't leave us in its scope
+                    self.environment = self.globals.clone();
+                    return Err(err);
+                }
+                // A return outside of any function just ends the program.
+                // The resolver only makes tail calls inside one
+                Err(Unwind::Return(_) | Unwind::TailCall(..)) => return Ok(()),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, TracedError> {
+        match self.visit_expression(expr) {
+            Ok(value) => Ok(value),
+            Err(Unwind::Error(err)) => {
+                self.environment = self.globals.clone();
+                Err(err)
+            }
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::TailCall(..)) => unreachable!("only functions make tail calls"),
+        }
+    }
+
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> ExecResult {
+        let previous = std::mem::replace(&mut self.environment, environment);
+
+        let mut result = Ok(Value::Nil);
+        for stmt in statements {
+            result = self.visit_statement(stmt);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.environment = previous;
+        result.map(|_| Value::Nil)
+    }
+
+    /// Calls a function, native or class with arguments that have already
+    /// been evaluated. `line` is where the call happens, if it's in Lox.
+    pub fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        line: Option<usize>,
+    ) -> Result<Value, TracedError> {
+        let error_line = line.unwrap_or(0);
+        let arity = match callable_arity(&callee) {
+            Some(arity) => arity,
+            None => return Err(RuntimeError::NotCallable(error_line).into()),
+        };
+
+        match arity {
+            arity if arity.accepts(arguments.len()) => {}
+            Arity::Fixed(arity) => {
+                let err = RuntimeError::ArityMismatch(arity, arguments.len(), error_line);
+                return Err(err.into());
+            }
+            Arity::AtLeast(minimum) => {
+                let err = RuntimeError::TooFewArguments(minimum, arguments.len(), error_line);
+                return Err(err.into());
+            }
+        }
+
+        let here = stack_position();
+        if self.depth == 0 {
+            self.stack_base = here;
+        }
+        // The script counts towards the depth, as it does on the VM
+        if self.depth + 1 == MAX_CALL_DEPTH || self.stack_base.abs_diff(here) > self.stack_limit {
+            return Err(RuntimeError::StackOverflow(error_line).into());
+        }
+
+        self.depth += 1;
+        let result = match callee {
+            Value::Function(function) => self.call_function(function, arguments, line),
+            // A native's own errors point at its call site, so only errors
+            // from Lox code it called back into need it in their trace
+            Value::Native(native) => (native.function)(self, &arguments).map_err(|err| match err {
+                NativeError::Message(message) => RuntimeError::Native(message, error_line).into(),
+                NativeError::Error(err) => err.through(&native.name, line, true),
+            }),
+            Value::Class(class) => LoxClass::instantiate(&class, self, arguments, line),
             _ => unreachable!(),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    /// Calls a Lox function, then each function it tail-calls in turn, so
+    /// a chain of them takes no more of the Rust stack than one call. The
+    /// functions replaced along the way are remembered for stack traces,
+    /// as `record_tail_call` describes.
+    fn call_function(
+        &mut self,
+        mut function: Rc<LoxFunction>,
+        mut arguments: Vec<Value>,
+        line: Option<usize>,
+    ) -> Result<Value, TracedError> {
+        let mut callers: Vec<(Rc<LoxFunction>, usize)> = Vec::new();
+        loop {
+            match function.call(self, arguments) {
+                Ok(value) => return Ok(value),
+                Err(Unwind::TailCall(callee, next, tail_line)) => {
+                    let caller = std::mem::replace(&mut function, callee);
+                    record_tail_call(&mut callers, caller, tail_line, |a, b| {
+                        a.declaration.name.token_type == b.declaration.name.token_type
+                    });
+                    arguments = next;
+                }
+                Err(Unwind::Error(err)) => {
+                    // Each function was called from where the one it
+                    // replaced made the tail call
+                    let called_from = |index: usize| match index {
+                        0 => line,
+                        index => Some(callers[index - 1].1),
+                    };
+                    let mut err = err.through(&function.name(), called_from(callers.len()), false);
+                    for (index, (caller, _)) in callers.iter().enumerate().rev() {
+                        err = err.through(&caller.name(), called_from(index), false);
+                    }
+                    return Err(err);
+                }
+                Err(Unwind::Return(_)) => unreachable!("calls catch their returns"),
+            }
         }
+    }
+
+    /// Evaluates a call being returned. A Lox function that will accept the
+    /// arguments is handed to the enclosing call to make; anything else is
+    /// called here, so errors calling it still show the function it's
+    /// called from.
+    fn tail_call(&mut self, expr: &CallExpr) -> ExecResult {
+        let callee = self.visit_expression(&expr.callee)?;
+
+        let mut arguments: Vec<Value> = Vec::with_capacity(expr.arguments.len());
+        for argument in &expr.arguments {
+            arguments.push(self.visit_expression(argument)?);
+        }
+
+        match callee {
+            Value::Function(function) if function.arity() == arguments.len() => {
+                Err(Unwind::TailCall(function, arguments, expr.paren.line))
+            }
+            callee => Ok(self.call_value(callee, arguments, Some(expr.paren.line))?),
+        }
+    }
 
-        return Err(String::from("error"));
+    /// Declares a variable in the current scope: by name if that's the
+    /// global one, and otherwise in the next slot, which is the one the
+    /// resolver gave it.
+    fn declare(&mut self, name: &Token, value: Value) {
+        if Rc::ptr_eq(&self.environment, &self.globals) {
+            self.globals.borrow_mut().define(name.symbol(), value);
+        } else {
+            self.environment.borrow_mut().push(value);
+        }
+    }
+
+    fn look_up_variable(&self, name: &Token, slot: Option<Slot>) -> Result<Value, RuntimeError> {
+        match slot {
+            Some(slot) => Ok(self.environment.borrow().get_at(slot)),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn make_function(&self, declaration: &FunctionStmt, is_initializer: bool) -> LoxFunction {
+        LoxFunction {
+            declaration: Rc::new(declaration.clone()),
+            closure: self.environment.clone(),
+            is_initializer,
+        }
+    }
+}
+
+fn callable_arity(callee: &Value) -> Option<Arity> {
+    match callee {
+        Value::Function(function) => Some(Arity::Fixed(function.arity())),
+        Value::Native(native) => Some(native.arity),
+        Value::Class(class) => Some(class.arity()),
+        _ => None,
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub enum Types {
-    Number(f64),
+impl MutVisitor for Interpreter {
+    type E = ExecResult;
+
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> Self::E {
+        self.visit_expression(&stmt.expression)
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> Self::E {
+        let value = self.visit_expression(&stmt.expression)?;
+        // Like a closed pipe, a failing output shouldn't stop the program
+        let _ = writeln!(self.output, "{}", value);
+        Ok(Value::Nil)
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Self::E {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.visit_expression(initializer)?,
+            None => Value::Nil,
+        };
+
+        self.declare(&stmt.name, value);
+        Ok(Value::Nil)
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> Self::E {
+        let environment = Environment::with_enclosing(self.environment.clone());
+        let environment = self.environment(environment);
+        self.execute_block(&stmt.statements, environment)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> Self::E {
+        if self.visit_expression(&stmt.condition)?.is_truthy() {
+            self.visit_statement(&stmt.then_branch)?;
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.visit_statement(else_branch)?;
+        }
+
+        Ok(Value::Nil)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> Self::E {
+        while self.visit_expression(&stmt.condition)?.is_truthy() {
+            self.visit_statement(&stmt.body)?;
+        }
+
+        Ok(Value::Nil)
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) -> Self::E {
+        let function = self.make_function(stmt, false);
+        self.declare(&stmt.name, Value::Function(Rc::new(function)));
+        Ok(Value::Nil)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> Self::E {
+        let value = match &stmt.value {
+            Some(Expr::Call(call)) if stmt.tail_call.get() => self.tail_call(call)?,
+            Some(value) => self.visit_expression(value)?,
+            None => Value::Nil,
+        };
+
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> Self::E {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.visit_variable(superclass)? {
+                Value::Class(class) => Some(class),
+                _ => return Err(RuntimeError::SuperclassMustBeClass(superclass.name.line).into()),
+            },
+            None => None,
+        };
+
+        let name = stmt.name.token_type.to_string();
+
+        // Methods only look the class up once they're called, so it can be
+        // declared after they're made
+        let enclosing = self.environment.clone();
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(enclosing.clone());
+            environment.push(Value::Class(superclass.clone()));
+            self.environment = self.environment(environment);
+        }
+
+        let methods: HashMap<Symbol, Method> = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let method_name = method.name.symbol();
+                let function = self.make_function(method, method_name == init_symbol());
+                (method_name, Method::Lox(Rc::new(function)))
+            })
+            .collect();
+
+        self.environment = enclosing;
+
+        let class = LoxClass {
+            name,
+            superclass,
+            methods,
+        };
+        self.declare(&stmt.name, Value::Class(Rc::new(class)));
+        Ok(Value::Nil)
+    }
+
+    fn visit_binary(&mut self, expr: &BinaryExpr) -> Self::E {
+        let left = self.visit_expression(&expr.left)?;
+        let right = self.visit_expression(&expr.right)?;
+        let operator = &expr.operator.token_type;
+        let line = expr.operator.line;
+
+        let value = match (operator, left, right) {
+            (TokenType::Minus, Value::Number(l), Value::Number(r)) => Value::Number(l - r),
+            (TokenType::Slash, Value::Number(l), Value::Number(r)) => Value::Number(l / r),
+            (TokenType::Star, Value::Number(l), Value::Number(r)) => Value::Number(l * r),
+            (TokenType::Plus, Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+            (TokenType::Plus, Value::LoxString(l), Value::LoxString(r)) => {
+                self.strings.intern(&[&*l, &*r].concat())
+            }
+            (TokenType::Greater, Value::Number(l), Value::Number(r)) => Value::Bool(l > r),
+            (TokenType::GreaterEqual, Value::Number(l), Value::Number(r)) => Value::Bool(l >= r),
+            (TokenType::Less, Value::Number(l), Value::Number(r)) => Value::Bool(l < r),
+            (TokenType::LessEqual, Value::Number(l), Value::Number(r)) => Value::Bool(l <= r),
+            (TokenType::EqualEqual, l, r) => Value::Bool(l == r),
+            (TokenType::BangEqual, l, r) => Value::Bool(l != r),
+            (TokenType::Plus, _, _) => {
+                return Err(RuntimeError::OperandsMustBeNumbersOrStrings(line).into())
+            }
+            (operator, _, _) => {
+                return Err(RuntimeError::OperandsMustBeNumbers(operator.clone(), line).into())
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn visit_grouping(&mut self, expr: &Grouping) -> Self::E {
+        self.visit_expression(&expr.expression)
+    }
+
+    fn visit_literal(&mut self, expr: &LiteralExpr) -> Self::E {
+        match &expr.value {
+            LiteralValue::LoxString(string) => Ok(self.strings.literal(string)),
+            LiteralValue::Float(number) => Ok(Value::Number(*number)),
+            LiteralValue::Bool(bool) => Ok(Value::Bool(*bool)),
+            LiteralValue::None => Ok(Value::Nil),
+        }
+    }
+
+    fn visit_unary(&mut self, expr: &UnaryExpr) -> Self::E {
+        let right = self.visit_expression(&expr.right)?;
+
+        match (&expr.operator.token_type, right) {
+            (TokenType::Minus, Value::Number(number)) => Ok(Value::Number(-number)),
+            (TokenType::Bang, value) => Ok(Value::Bool(!value.is_truthy())),
+            (operator, _) => {
+                Err(RuntimeError::OperandMustBeNumber(operator.clone(), expr.operator.line).into())
+            }
+        }
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) -> Self::E {
+        Ok(self.look_up_variable(&expr.name, expr.slot.get())?)
+    }
+
+    fn visit_assign(&mut self, expr: &AssignExpr) -> Self::E {
+        let value = self.visit_expression(&expr.value)?;
+        match expr.slot.get() {
+            Some(slot) => self.environment.borrow_mut().assign_at(slot, value.clone()),
+            None => self
+                .globals
+                .borrow_mut()
+                .assign(&expr.name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_logical(&mut self, expr: &LogicalExpr) -> Self::E {
+        let left = self.visit_expression(&expr.left)?;
+
+        let short_circuits = match expr.operator.token_type {
+            TokenType::Or => left.is_truthy(),
+            _ => !left.is_truthy(),
+        };
+        if short_circuits {
+            return Ok(left);
+        }
+
+        self.visit_expression(&expr.right)
+    }
+
+    fn visit_call(&mut self, expr: &CallExpr) -> Self::E {
+        let callee = self.visit_expression(&expr.callee)?;
+
+        let mut arguments: Vec<Value> = Vec::with_capacity(expr.arguments.len());
+        for argument in &expr.arguments {
+            arguments.push(self.visit_expression(argument)?);
+        }
+
+        Ok(self.call_value(callee, arguments, Some(expr.paren.line))?)
+    }
+
+    fn visit_get(&mut self, expr: &GetExpr) -> Self::E {
+        match self.visit_expression(&expr.object)? {
+            Value::Instance(instance) => Ok(LoxInstance::get(&instance, &expr.name)?),
+            _ => Err(RuntimeError::OnlyInstancesHaveProperties(expr.name.line).into()),
+        }
+    }
+
+    fn visit_set(&mut self, expr: &SetExpr) -> Self::E {
+        let instance = match self.visit_expression(&expr.object)? {
+            Value::Instance(instance) => instance,
+            _ => return Err(RuntimeError::OnlyInstancesHaveFields(expr.name.line).into()),
+        };
+
+        let value = self.visit_expression(&expr.value)?;
+        instance.borrow_mut().set(&expr.name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_this(&mut self, expr: &ThisExpr) -> Self::E {
+        Ok(self.look_up_variable(&expr.keyword, expr.slot.get())?)
+    }
+
+    fn visit_super(&mut self, expr: &SuperExpr) -> Self::E {
+        let Some(slot) = expr.slot.get() else {
+            let err = RuntimeError::UndefinedVariable("super".to_string(), expr.keyword.line);
+            return Err(err.into());
+        };
+
+        // `this` is bound in the scope just inside the one holding `super`
+        let environment = self.environment.borrow();
+        let superclass = environment.get_at(slot);
+        let instance = environment.get_at(Slot {
+            depth: slot.depth - 1,
+            index: 0,
+        });
+
+        let method_name = expr.method.symbol();
+        match (superclass, instance) {
+            (Value::Class(superclass), Value::Instance(instance)) => {
+                match superclass.find_method(method_name) {
+                    Some(method) => Ok(method.bind(instance)),
+                    None => Err(RuntimeError::UndefinedProperty(
+                        method_name.to_string(),
+                        expr.method.line,
+                    )
+                    .into()),
+                }
+            }
+            _ => {
+                Err(RuntimeError::UndefinedVariable("super".to_string(), expr.keyword.line).into())
+            }
+        }
+    }
 }
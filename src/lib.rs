@@ -0,0 +1,38 @@
+//! A Lox interpreter that can be embedded in Rust programs.
+//!
+//! ```
+//! let mut engine = rlox::Engine::new();
+//! engine.set_global("limit", 3.0);
+//! let value = engine.eval("limit * 2").unwrap();
+//! assert_eq!(value, rlox::Value::Number(6.0));
+//! ```
+//!
+//! `Engine` is the entry point. The modules behind it are public too, for
+//! tools that work with tokens or syntax trees directly.
+
+pub mod ast_dot;
+pub mod ast_json;
+pub mod ast_printer;
+pub mod ast_reader;
+pub mod callable;
+mod collector;
+mod engine;
+mod environment;
+pub mod formatter;
+pub mod interpreter;
+pub mod native;
+pub mod optimizer;
+pub mod parser;
+pub mod repl;
+pub mod resolver;
+pub mod rpn_printer;
+pub mod scanner;
+pub mod symbol;
+pub mod syntax;
+pub mod token;
+pub mod value;
+pub mod visit;
+pub mod vm;
+
+pub use engine::{Engine, Error};
+pub use value::Value;
@@ -0,0 +1,91 @@
+//! Line editing for the REPL: history kept in `~/.rlox_history` and tab
+//! completion of keywords and the session's global names.
+
+use std::env;
+use std::path::PathBuf;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use rlox::scanner;
+
+const HISTORY_FILE: &str = ".rlox_history";
+
+pub fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+pub struct LoxHelper {
+    keywords: Vec<String>,
+    globals: Vec<String>,
+}
+
+impl LoxHelper {
+    pub fn new() -> LoxHelper {
+        LoxHelper {
+            keywords: scanner::KEYWORDS
+                .iter()
+                .map(|keyword| keyword.to_string())
+                .collect(),
+            globals: Vec::new(),
+        }
+    }
+
+    /// Replaces the global names offered as completions, which the REPL
+    /// refreshes after every entry.
+    pub fn set_globals(&mut self, globals: Vec<String>) {
+        self.globals = globals;
+    }
+}
+
+impl Completer for LoxHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let prefix = &line[start..pos];
+
+        let mut names: Vec<String> = self
+            .keywords
+            .iter()
+            .cloned()
+            .chain(self.globals.iter().cloned())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let candidates = names
+            .into_iter()
+            .map(|name| Pair {
+                display: name.clone(),
+                replacement: name,
+            })
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
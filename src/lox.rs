@@ -1,72 +1,397 @@
-use crate::ast_printer::ASTStringVisitor;
-use crate::parser::Parser;
-use crate::scanner;
-use crate::token::Token;
+use crate::line_editor::{self, LoxHelper};
+use rlox::ast_dot::ASTDotVisitor;
+use rlox::ast_json;
+use rlox::ast_printer::ASTStringVisitor;
+use rlox::formatter::{self, SourceFormatter};
+use rlox::native::Arity;
+use rlox::parser::Parser;
+use rlox::repl::Repl;
+use rlox::scanner;
+use rlox::syntax::Stmt;
+use rlox::vm::{self, Vm};
+use rlox::{Engine, Error};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
 use std::io::prelude::*;
+use std::rc::Rc;
 use std::{fs, io, path, process};
 
+// Exit codes from BSD's sysexits.h, the same ones the reference Lox
+// implementations use.
+pub const EX_USAGE: i32 = 64;
+pub const EX_DATAERR: i32 = 65;
+pub const EX_NOINPUT: i32 = 66;
+pub const EX_SOFTWARE: i32 = 70;
+pub const EX_CANTCREAT: i32 = 73;
+pub const EX_IOERR: i32 = 74;
+
+/// The stack the command line runs on: enough for scripts on the
+/// tree-walker to recurse as deep as the VM lets them, even in a debug build.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// How programs run: by walking the syntax tree, or compiled to bytecode.
+#[derive(Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Backend {
+    #[default]
+    Tree,
+    Vm,
+}
+
+pub enum AstFormat {
+    Sexpr,
+    Json,
+    Dot,
+}
+
+/// The command-line front end over an `Engine`: runs scripts and the REPL,
+/// reports errors on stderr and turns them into exit codes.
 pub struct Lox {
     had_error: bool,
+    had_runtime_error: bool,
+    backend: Backend,
+    engine: Engine,
+    vm: Vm,
 }
 
 impl Lox {
+    /// A front end for running on a thread with a `STACK_SIZE` stack.
     pub fn new() -> Lox {
-        Lox { had_error: false }
+        let mut engine = Engine::new();
+        // Leaves room for what runs before the first call and between the
+        // checks calls make
+        engine.set_stack_limit(STACK_SIZE - STACK_SIZE / 8);
+
+        Lox {
+            had_error: false,
+            had_runtime_error: false,
+            backend: Backend::Tree,
+            engine,
+            vm: Vm::new(),
+        }
     }
 
-    pub fn run(&mut self, source: String) {
-        let mut scanner: scanner::Scanner = scanner::Scanner::new(&source);
-        let mut tokens: Vec<Token> = Vec::new();
-        match scanner.scan_tokens() {
-            Ok(ts) => tokens = ts,
-            Err(errors) => errors
-                .iter()
-                .for_each(|err| self.report(err.line(), format!("{}", err))),
+    /// Picks the backend scripts run on. The REPL always walks the tree.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Makes the VM print its stack and each instruction as it runs, which
+    /// means running on the VM.
+    pub fn set_trace(&mut self, trace: bool) {
+        if trace {
+            self.backend = Backend::Vm;
+        }
+        self.vm.set_trace(trace);
+    }
+
+    /// Makes the VM collect garbage on every allocation, which means running
+    /// on the VM.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        if stress {
+            self.backend = Backend::Vm;
+        }
+        self.vm.set_gc_stress(stress);
+    }
+
+    /// Scans and parses `source`, reporting any errors. Doesn't resolve, so
+    /// tools that only look at the syntax accept any well-formed program.
+    fn parse(&mut self, source: &str) -> Option<Vec<Stmt>> {
+        let tokens = match scanner::Scanner::new(source).scan_tokens() {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                self.error(Error::Scan(errors));
+                return None;
+            }
         };
 
-        let mut parser = Parser::new(tokens);
-        let expression = parser.parse();
+        match Parser::new(tokens).parse() {
+            Ok(stmts) => Some(stmts),
+            Err(errors) => {
+                self.error(Error::Parse(errors));
+                None
+            }
+        }
+    }
 
-        match expression {
-            Ok(expr) => println!(
-                "{}",
-                ASTStringVisitor {
-                    expressions: &[expr]
-                }
-            ),
-            Err(err) => println!("{}", err),
+    pub fn run(&mut self, source: String) {
+        let result = match self.backend {
+            Backend::Tree => self.engine.run(&source),
+            Backend::Vm => self.vm.run(&source),
+        };
+        if let Err(err) = result {
+            self.error(err);
         }
     }
 
     pub fn run_prompt(&mut self) -> io::Result<()> {
-        let mut input = String::new();
-        let stdin = io::stdin();
+        let mut editor: Editor<LoxHelper, DefaultHistory> =
+            Editor::new().map_err(io::Error::other)?;
+        editor.set_helper(Some(LoxHelper::new()));
+
+        let history = line_editor::history_path();
+        if let Some(path) = &history {
+            // There's no history to load on the first run
+            let _ = editor.load_history(path);
+        }
+
+        let mut repl = Repl::new();
         loop {
-            print!("> ");
-            let _ = io::stdout().flush();
-            stdin.lock().read_line(&mut input)?;
-            self.run(input.clone());
-            input.clear();
-            self.had_error = false;
+            let line = match editor.readline(repl.prompt()) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => {
+                    repl.interrupt();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(io::Error::other(err)),
+            };
+
+            if !line.trim().is_empty() {
+                let _ = editor.add_history_entry(line.as_str());
+            }
+
+            repl.read_line(&line)?;
+            if let Some(helper) = editor.helper_mut() {
+                helper.set_globals(repl.global_names());
+            }
+        }
+
+        if let Some(path) = &history {
+            let _ = editor.save_history(path);
         }
+
+        Ok(())
     }
 
-    pub fn runfile(&mut self, path: path::PathBuf) -> io::Result<()> {
-        let mut source = String::new();
-        let mut file = fs::File::open(path)?;
+    /// Makes the arguments that follow the script name available to it
+    /// through `argc()` and `arg(n)`, which is `nil` past the end.
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        let count = args.len() as f64;
+        self.engine.register("argc", move || count);
+        let tree_args = args.clone();
+        self.engine
+            .register("arg", move |n: f64| script_arg(&tree_args, n).cloned());
+
+        self.vm.define_native("argc", Arity::Fixed(0), move |_, _| {
+            Ok(vm::value::Value::number(count))
+        });
+        self.vm.define_native(
+            "arg",
+            Arity::Fixed(1),
+            move |heap, arguments| match arguments[0].as_number() {
+                Some(n) => Ok(match script_arg(&args, n) {
+                    Some(arg) => heap.alloc_string(arg),
+                    None => vm::value::Value::NIL,
+                }),
+                None => Err(format!(
+                    "Argument 1 of 'arg' must be a number, got {}",
+                    heap.type_name(arguments[0])
+                )),
+            },
+        );
+    }
+
+    /// Runs a script, or bytecode from `rlox compile` if it's a `.loxc`
+    /// file. On the VM a script's up-to-date `.loxc` is used if there is one.
+    pub fn runfile(&mut self, path: &path::Path) -> io::Result<()> {
+        if path.extension().is_some_and(|ext| ext == "loxc") {
+            let bytes = fs::read(path)?;
+            let function = vm::cache::decode(&bytes, None).map_err(Error::Cache);
+            self.run_bytecode(function);
+        } else if self.backend == Backend::Vm && !is_stdin(path) {
+            let source = read_source(path)?;
+            self.run_bytecode(vm::compile_cached(path, &source));
+        } else {
+            let source = read_source(path)?;
+            self.run(source);
+        }
+        self.exit_on_error();
+
+        Ok(())
+    }
 
-        file.read_to_string(&mut source)?;
+    fn run_bytecode(&mut self, function: Result<Rc<vm::chunk::Function>, Error>) {
+        let result = function.and_then(|function| Ok(self.vm.interpret(function)?));
+        if let Err(err) = result {
+            self.error(err);
+        }
+    }
+
+    pub fn run_code(&mut self, source: String) {
         self.run(source);
+        self.exit_on_error();
+    }
+
+    fn exit_on_error(&self) {
+        if self.had_error {
+            process::exit(EX_DATAERR);
+        }
+        if self.had_runtime_error {
+            process::exit(EX_SOFTWARE);
+        }
+    }
+
+    pub fn print_tokens(&mut self, path: &path::Path, json: bool) -> io::Result<()> {
+        let source = read_source(path)?;
+
+        match scanner::Scanner::new(&source).scan_tokens() {
+            Ok(tokens) if json => println!("{}", ast_json::tokens_to_json(&tokens)),
+            Ok(tokens) => tokens.iter().for_each(|token| println!("{}", token)),
+            Err(errors) => self.error(Error::Scan(errors)),
+        }
+
+        if self.had_error {
+            process::exit(EX_DATAERR);
+        }
+
+        Ok(())
+    }
+
+    pub fn print_ast(&mut self, path: &path::Path, format: AstFormat) -> io::Result<()> {
+        let source = read_source(path)?;
+
+        if let Some(stmts) = self.parse(&source) {
+            match format {
+                AstFormat::Sexpr => println!("{}", ASTStringVisitor { statements: &stmts }),
+                AstFormat::Json => println!("{}", ast_json::statements_to_json(&stmts)),
+                AstFormat::Dot => print!("{}", ASTDotVisitor::new().render(&stmts)),
+            }
+        }
 
         if self.had_error {
-            process::exit(1);
+            process::exit(EX_DATAERR);
+        }
+
+        Ok(())
+    }
+
+    /// Scans and parses without running anything.
+    pub fn check(&mut self, path: &path::Path) -> io::Result<()> {
+        let source = read_source(path)?;
+
+        if let Err(err) = self.engine.compile(&source) {
+            self.error(err);
+            process::exit(EX_DATAERR);
         }
 
         Ok(())
     }
 
-    pub fn report(&mut self, line: usize, message: String) {
-        println!("[line {}] Error: {}", line, message);
-        self.had_error = true;
+    /// Prints the bytecode the file compiles to.
+    pub fn disassemble(&mut self, path: &path::Path) -> io::Result<()> {
+        let source = read_source(path)?;
+
+        match vm::compile(&source) {
+            Ok(function) => print!("{}", vm::disassembler::disassemble(&function)),
+            Err(err) => {
+                self.error(err);
+                process::exit(EX_DATAERR);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the file to bytecode and saves it, by default next to it as
+    /// a `.loxc` file. Bytecode compiled from standard input goes to
+    /// standard output unless there's somewhere else to put it.
+    pub fn compile(&mut self, path: &path::Path, output: Option<&path::Path>) -> io::Result<()> {
+        let source = read_source(path)?;
+
+        let function = match vm::compile(&source) {
+            Ok(function) => function,
+            Err(err) => {
+                self.error(err);
+                process::exit(EX_DATAERR);
+            }
+        };
+        let bytes = vm::cache::encode(&function, &source);
+
+        let output = match output {
+            Some(output) => output.to_path_buf(),
+            None if is_stdin(path) => {
+                if let Err(err) = io::stdout().write_all(&bytes) {
+                    unwritable(path::Path::new("standard output"), err);
+                }
+                return Ok(());
+            }
+            None => vm::cache::cache_path(path),
+        };
+        if let Err(err) = fs::write(&output, bytes) {
+            unwritable(&output, err);
+        }
+
+        Ok(())
+    }
+
+    /// Prints the file reformatted, or rewrites it in place with `write`.
+    /// Formatting drops comments and turns `for` loops into `while` loops,
+    /// so a file with either is only ever printed.
+    pub fn format(&mut self, path: &path::Path, write: bool) -> io::Result<()> {
+        let source = read_source(path)?;
+
+        let Some(stmts) = self.parse(&source) else {
+            process::exit(EX_DATAERR);
+        };
+        let formatted = SourceFormatter::new().format(&stmts);
+
+        if !write || is_stdin(path) {
+            print!("{}", formatted);
+            return Ok(());
+        }
+
+        if let Some(lost) = formatter::lost_in_formatting(&source) {
+            eprintln!(
+                "rlox: not rewriting {}: formatting would lose {}; run without -w to see the result",
+                path.display(),
+                lost
+            );
+            process::exit(EX_DATAERR);
+        }
+        if let Err(err) = fs::write(path, formatted) {
+            unwritable(path, err);
+        }
+
+        Ok(())
+    }
+
+    fn error(&mut self, err: Error) {
+        eprintln!("{}", err);
+        match err {
+            Error::Runtime(_) => self.had_runtime_error = true,
+            _ => self.had_error = true,
+        }
+    }
+}
+
+/// The script argument `arg(n)` returns, if `n` is an index into them.
+fn script_arg(args: &[String], n: f64) -> Option<&String> {
+    if n >= 0.0 && n.fract() == 0.0 {
+        args.get(n as usize)
+    } else {
+        None
+    }
+}
+
+/// Reports a file that couldn't be written, which ends the run. Errors
+/// reading input go back to `main` instead.
+fn unwritable(path: &path::Path, err: io::Error) -> ! {
+    eprintln!("rlox: could not write {}: {}", path.display(), err);
+    process::exit(EX_CANTCREAT);
+}
+
+fn is_stdin(path: &path::Path) -> bool {
+    path == path::Path::new("-")
+}
+
+/// Reads a script from `path`, or from standard input when it is `-`.
+fn read_source(path: &path::Path) -> io::Result<String> {
+    if is_stdin(path) {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        fs::read_to_string(path)
     }
 }
@@ -1,44 +1,239 @@
-mod ast_printer;
-mod interpreter;
+mod line_editor;
 mod lox;
-mod parser;
-mod scanner;
-mod syntax;
-mod token;
-mod visit;
-
-use std::env;
-use std::path;
-
-// use ast_printer::ASTStringVisitor;
-// use syntax::BinaryExpr;
-// use syntax::Expr;
-// use syntax::Grouping;
-// use syntax::UnaryExpr;
-// use token::Token;
+
+use std::path::{Path, PathBuf};
+use std::{io, panic, process, thread};
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(
+    name = "rlox",
+    version,
+    about = "A Lox interpreter",
+    args_conflicts_with_subcommands = true,
+    after_help = "\
+With no script and no subcommand, rlox starts an interactive session.
+Wherever a file is expected, `-` reads it from standard input.
+Arguments after the script are passed to it, read with argc() and arg(n).
+
+Examples:
+  rlox hello.lox              Run hello.lox
+  rlox hello.lox a b          Run hello.lox with arg(0) == \"a\", arg(1) == \"b\"
+  rlox -e 'print 1 + 2;'      Run a snippet
+  rlox -e 'print arg(0);' a   Run a snippet with arg(0) == \"a\"
+  rlox --backend=vm f.lox     Run f.lox compiled to bytecode
+  rlox disasm f.lox           Print the bytecode f.lox compiles to
+  rlox compile f.lox          Save f.lox's bytecode as f.loxc, used by --backend=vm
+  echo 'print 1;' | rlox -    Run a script read from standard input
+  rlox ast --dot f.lox        Print the syntax tree of f.lox as a Graphviz graph"
+)]
+struct Cli {
+    /// Run CODE instead of a script, passing it the arguments that follow
+    #[arg(short = 'e', value_name = "CODE")]
+    eval: Option<String>,
+
+    /// How to run the program
+    #[arg(long, value_enum, default_value_t)]
+    backend: lox::Backend,
+
+    /// Print the VM stack and each instruction on stderr as it runs
+    /// (implies --backend=vm)
+    #[arg(long)]
+    trace: bool,
+
+    /// Collect garbage on every allocation, to test the collector
+    /// (implies --backend=vm)
+    #[arg(long)]
+    gc_stress: bool,
+
+    /// The script to run
+    script: Option<PathBuf>,
+
+    /// Arguments passed through to the script
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a script
+    Run {
+        /// How to run the program
+        #[arg(long, value_enum, default_value_t)]
+        backend: lox::Backend,
+
+        /// Print the VM stack and each instruction on stderr as it runs
+        /// (implies --backend=vm)
+        #[arg(long)]
+        trace: bool,
+
+        /// Collect garbage on every allocation, to test the collector
+        /// (implies --backend=vm)
+        #[arg(long)]
+        gc_stress: bool,
+
+        script: PathBuf,
+
+        /// Arguments passed through to the script
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Start an interactive session
+    Repl,
+    /// Print the tokens the scanner produces for a file
+    Tokens {
+        /// Print the tokens as versioned JSON
+        #[arg(long)]
+        json: bool,
+
+        file: PathBuf,
+    },
+    /// Print the syntax tree of a file, as an S-expression by default
+    Ast {
+        /// Print the tree as versioned JSON
+        #[arg(long, conflicts_with = "dot")]
+        json: bool,
+
+        /// Print the tree as a Graphviz DOT graph
+        #[arg(long)]
+        dot: bool,
+
+        file: PathBuf,
+    },
+    /// Report syntax errors in a file without running it
+    Check { file: PathBuf },
+    /// Print the bytecode a file compiles to
+    Disasm { file: PathBuf },
+    /// Compile a file to bytecode, saved next to it as a .loxc file that
+    /// --backend=vm loads instead while it's up to date
+    Compile {
+        /// Where to write the bytecode
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        file: PathBuf,
+    },
+    /// Reformat a file, printing the result
+    Fmt {
+        /// Rewrite the file in place instead of printing it. Refused for a
+        /// file with comments or `for` loops, which formatting loses
+        #[arg(short, long)]
+        write: bool,
+
+        file: PathBuf,
+    },
+}
 
 fn main() {
+    let session = thread::Builder::new()
+        .stack_size(lox::STACK_SIZE)
+        .spawn(run)
+        .expect("could not start the interpreter thread");
+    if let Err(payload) = session.join() {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn run() {
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        let _ = err.print();
+        // --help and --version come through here too, and aren't failures
+        process::exit(match err.use_stderr() {
+            true => lox::EX_USAGE,
+            false => 0,
+        });
+    });
     let mut lox = lox::Lox::new();
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() == 1 {
-        let _ = lox.run_prompt();
-    } else if args.len() == 2 {
-        let _ = lox.runfile(path::PathBuf::from(&args[1]));
-    } else {
-        println!("Usage: rlox [script]");
+
+    let command = match (cli.command, cli.eval, cli.script) {
+        (Some(command), _, _) => command,
+        (None, Some(code), script) => {
+            // With no script to name, the first argument lands in its place
+            let args = script
+                .map(|first| first.to_string_lossy().into_owned())
+                .into_iter()
+                .chain(cli.args)
+                .collect();
+            lox.set_backend(cli.backend);
+            lox.set_trace(cli.trace);
+            lox.set_gc_stress(cli.gc_stress);
+            lox.set_script_args(args);
+            lox.run_code(code);
+            return;
+        }
+        (None, None, Some(script)) => Command::Run {
+            backend: cli.backend,
+            trace: cli.trace,
+            gc_stress: cli.gc_stress,
+            script,
+            args: cli.args,
+        },
+        (None, None, None) => Command::Repl,
+    };
+
+    let (path, result) = match command {
+        Command::Run {
+            backend,
+            trace,
+            gc_stress,
+            script,
+            args,
+        } => {
+            lox.set_backend(backend);
+            lox.set_trace(trace);
+            lox.set_gc_stress(gc_stress);
+            lox.set_script_args(args);
+            let result = lox.runfile(&script);
+            (script, result)
+        }
+        Command::Repl => {
+            if let Err(err) = lox.run_prompt() {
+                eprintln!("rlox: {}", err);
+                process::exit(lox::EX_IOERR);
+            }
+            return;
+        }
+        Command::Tokens { json, file } => {
+            let result = lox.print_tokens(&file, json);
+            (file, result)
+        }
+        Command::Ast { json, dot, file } => {
+            let format = match (json, dot) {
+                (true, _) => lox::AstFormat::Json,
+                (_, true) => lox::AstFormat::Dot,
+                _ => lox::AstFormat::Sexpr,
+            };
+            let result = lox.print_ast(&file, format);
+            (file, result)
+        }
+        Command::Check { file } => {
+            let result = lox.check(&file);
+            (file, result)
+        }
+        Command::Disasm { file } => {
+            let result = lox.disassemble(&file);
+            (file, result)
+        }
+        Command::Compile { output, file } => {
+            let result = lox.compile(&file, output.as_deref());
+            (file, result)
+        }
+        Command::Fmt { write, file } => {
+            let result = lox.format(&file, write);
+            (file, result)
+        }
+    };
+
+    if let Err(err) = result {
+        unreadable(&path, err);
     }
 }
 
-// fn test_working_expressions() -> Expr {
-//     Expr::Binary(BinaryExpr {
-//         left: Box::new(Expr::Unary(UnaryExpr {
-//             operator: Token::new(token::TokenType::Minus, 1),
-//             right: Box::new(Expr::Literal(123.0.into())),
-//         })),
-//         operator: Token::new(token::TokenType::Star, 1),
-//         right: Box::new(Expr::Grouping(Grouping {
-//             expression: Box::new(Expr::Literal("WHAT".to_string().into())),
-//         })),
-//     })
-// }
+fn unreadable(path: &Path, err: io::Error) -> ! {
+    eprintln!("rlox: could not read {}: {}", path.display(), err);
+    process::exit(lox::EX_NOINPUT);
+}
@@ -0,0 +1,562 @@
+//! Calling Rust from Lox: conversions between `Value` and Rust types, and
+//! the `NativeCallable` trait that lets a plain Rust closure be registered as
+//! a Lox function.
+//!
+//! ```
+//! let mut engine = rlox::Engine::new();
+//! engine.register("hypot", |x: f64, y: f64| (x * x + y * y).sqrt());
+//! engine.register("sum", |numbers: Vec<f64>| numbers.iter().sum::<f64>());
+//! assert_eq!(engine.eval("hypot(3, 4) + sum(1, 2, 3)").unwrap(), rlox::Value::Number(11.0));
+//! ```
+//!
+//! A closure's parameters are converted with `FromValue`, and its return
+//! value with `IntoNativeResult`, so returning `Err` from a closure raises a
+//! Lox runtime error at the call site. Lox has no list type, so a trailing
+//! `Vec<T>` parameter doesn't take a single argument: it makes the function
+//! variadic and collects all the remaining arguments.
+//!
+//! A closure that takes `&mut Interpreter` first can call back into Lox with
+//! `Interpreter::call`, for natives that take Lox functions as callbacks. An
+//! error from the callback keeps its stack trace.
+//!
+//! Rust values that scripts shouldn't see inside travel as opaque userdata:
+//! wrap one in a `Handle` and it becomes a value scripts can store and pass
+//! back, and that natives can take as a `Handle` parameter again. A
+//! `NativeClass` goes further, giving the Rust value methods that scripts can
+//! call and that Lox classes can inherit.
+
+use std::any::{self, Any};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::callable::{LoxClass, LoxInstance, Method, NativeFunction};
+use crate::interpreter::{Interpreter, RuntimeError, TracedError};
+use crate::symbol::Symbol;
+use crate::value::Value;
+
+/// How many arguments a native function takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Fixed(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Fixed(arity) => count == arity,
+            Arity::AtLeast(arity) => count >= arity,
+        }
+    }
+}
+
+/// Rust types a Lox argument can be converted to.
+pub trait FromValue: Sized {
+    /// What the conversion accepts, for error messages, e.g. "a number".
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "a value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const EXPECTED: &'static str = "a boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::LoxString(string) => Some(string.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// `nil` converts to `None`, anything else has to convert to `T`.
+impl<T: FromValue> FromValue for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// An instance's fields are reachable from a native method that takes
+/// `this` as an instance.
+impl FromValue for Rc<RefCell<LoxInstance>> {
+    const EXPECTED: &'static str = "an instance";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Instance(instance) => Some(instance.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// A Rust value behind a `Value::UserData`. Scripts can only pass it around;
+/// there's nothing in it for them to read.
+#[derive(Clone)]
+pub struct UserData {
+    type_name: &'static str,
+    data: Rc<dyn Any>,
+}
+
+impl UserData {
+    /// The short name of the Rust type inside, for printing.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn ptr_eq(&self, other: &UserData) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl fmt::Debug for UserData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<userdata {}>", self.type_name)
+    }
+}
+
+/// A shared Rust value that can be handed to Lox as userdata. Cloning a
+/// handle shares the value, so changes made through one are seen by all.
+pub struct Handle<T>(Rc<RefCell<T>>);
+
+impl<T: 'static> Handle<T> {
+    pub fn new(value: T) -> Handle<T> {
+        Handle(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+
+    fn from_data(data: &UserData) -> Option<Handle<T>> {
+        data.data.clone().downcast::<RefCell<T>>().ok().map(Handle)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(self.0.clone())
+    }
+}
+
+impl<T: 'static> From<Handle<T>> for Value {
+    fn from(handle: Handle<T>) -> Self {
+        let type_name = any::type_name::<T>();
+        Value::UserData(UserData {
+            type_name: type_name.rsplit("::").next().unwrap_or(type_name),
+            data: handle.0,
+        })
+    }
+}
+
+/// Accepts userdata holding a `T`, and instances of a native class whose
+/// initializer returned one, so methods can take `this` as a `Handle`.
+impl<T: 'static> FromValue for Handle<T> {
+    const EXPECTED: &'static str = "userdata";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::UserData(data) => Handle::from_data(data),
+            Value::Instance(instance) => {
+                instance.borrow().data.as_ref().and_then(Handle::from_data)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Rust types that can be handed back to Lox.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl<T: Into<Value>> IntoValue for T {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+/// Why a native failed: a message to raise as a runtime error at the call
+/// site, or an error from Lox code the native called back into, which keeps
+/// its own line and stack trace.
+#[derive(Debug)]
+pub enum NativeError {
+    Message(String),
+    Error(TracedError),
+}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        NativeError::Message(message)
+    }
+}
+
+impl From<&str> for NativeError {
+    fn from(message: &str) -> Self {
+        NativeError::Message(message.to_string())
+    }
+}
+
+impl From<TracedError> for NativeError {
+    fn from(err: TracedError) -> Self {
+        NativeError::Error(err)
+    }
+}
+
+impl From<RuntimeError> for NativeError {
+    fn from(err: RuntimeError) -> Self {
+        NativeError::Error(err.into())
+    }
+}
+
+/// What a native closure may return: a value, or a `Result` whose error
+/// becomes a Lox runtime error.
+pub trait IntoNativeResult {
+    fn into_native_result(self) -> Result<Value, NativeError>;
+}
+
+impl<T: IntoValue> IntoNativeResult for T {
+    fn into_native_result(self) -> Result<Value, NativeError> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue, E: Into<NativeError>> IntoNativeResult for Result<T, E> {
+    fn into_native_result(self) -> Result<Value, NativeError> {
+        self.map(IntoValue::into_value).map_err(Into::into)
+    }
+}
+
+/// Argument lists Rust code can pass when calling into Lox: `()`, tuples of
+/// values, or a `Vec<Value>` built at runtime.
+pub trait IntoArguments {
+    fn into_arguments(self) -> Vec<Value>;
+}
+
+impl IntoArguments for Vec<Value> {
+    fn into_arguments(self) -> Vec<Value> {
+        self
+    }
+}
+
+macro_rules! impl_into_arguments {
+    ($($param:ident $arg:ident),*) => {
+        impl<$($param: IntoValue),*> IntoArguments for ($($param,)*) {
+            #[allow(clippy::unused_unit)]
+            fn into_arguments(self) -> Vec<Value> {
+                let ($($arg,)*) = self;
+                vec![$($arg.into_value()),*]
+            }
+        }
+    };
+}
+
+impl_into_arguments!();
+impl_into_arguments!(A a);
+impl_into_arguments!(A a, B b);
+impl_into_arguments!(A a, B b, C c);
+impl_into_arguments!(A a, B b, C c, D d);
+impl_into_arguments!(A a, B b, C c, D d, E e);
+impl_into_arguments!(A a, B b, C c, D d, E e, G g);
+
+/// Which native a conversion is for, so its errors can say where they are.
+#[derive(Clone, Copy)]
+pub struct Callsite<'a> {
+    pub name: &'a str,
+    /// Methods get `this` as their first argument, which the script didn't
+    /// write and shouldn't be counted.
+    pub is_method: bool,
+}
+
+fn convert<T: FromValue>(callsite: Callsite, index: usize, value: &Value) -> Result<T, String> {
+    let argument = match (callsite.is_method, index) {
+        (true, 0) => "'this'".to_string(),
+        (true, index) => format!("Argument {}", index),
+        (false, index) => format!("Argument {}", index + 1),
+    };
+
+    T::from_value(value).ok_or_else(|| {
+        format!(
+            "{} of '{}' must be {}, got {}",
+            argument,
+            callsite.name,
+            T::EXPECTED,
+            value.type_name()
+        )
+    })
+}
+
+fn next_argument<'a, T: FromValue>(
+    callsite: Callsite,
+    arguments: &mut impl Iterator<Item = (usize, &'a Value)>,
+) -> Result<T, String> {
+    match arguments.next() {
+        Some((index, value)) => convert(callsite, index, value),
+        None => unreachable!("arity is checked before calling"),
+    }
+}
+
+/// Rust functions that can be registered as Lox natives. `Args` is the tuple
+/// of parameter types, which only serves to tell the implementations apart.
+///
+/// A closure whose first parameter is `&mut Interpreter` gets the interpreter
+/// that called it, and can use it to call back into Lox.
+pub trait NativeCallable<Args>: 'static {
+    fn arity() -> Arity;
+
+    /// Converts `arguments`, whose count `arity` has already checked, and
+    /// calls the function.
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        callsite: Callsite,
+        arguments: &[Value],
+    ) -> Result<Value, NativeError>;
+}
+
+/// Marks the `NativeCallable` implementations for closures that take the
+/// interpreter.
+pub struct WithInterpreter;
+
+macro_rules! count {
+    () => { 0 };
+    ($head:ident $($tail:ident)*) => { 1 + count!($($tail)*) };
+}
+
+macro_rules! impl_native_callable {
+    ($($param:ident $arg:ident),*) => {
+        impl<F, R, $($param),*> NativeCallable<($($param,)*)> for F
+        where
+            F: Fn($($param),*) -> R + 'static,
+            R: IntoNativeResult,
+            $($param: FromValue,)*
+        {
+            fn arity() -> Arity {
+                Arity::Fixed(count!($($param)*))
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn call(
+                &self,
+                _: &mut Interpreter,
+                callsite: Callsite,
+                arguments: &[Value],
+            ) -> Result<Value, NativeError> {
+                let mut arguments = arguments.iter().enumerate();
+                $(let $arg = next_argument::<$param>(callsite, &mut arguments)?;)*
+                self($($arg),*).into_native_result()
+            }
+        }
+
+        impl<F, R, $($param,)* T> NativeCallable<($($param,)* Vec<T>,)> for F
+        where
+            F: Fn($($param,)* Vec<T>) -> R + 'static,
+            R: IntoNativeResult,
+            $($param: FromValue,)*
+            T: FromValue,
+        {
+            fn arity() -> Arity {
+                Arity::AtLeast(count!($($param)*))
+            }
+
+            #[allow(unused_mut)]
+            fn call(
+                &self,
+                _: &mut Interpreter,
+                callsite: Callsite,
+                arguments: &[Value],
+            ) -> Result<Value, NativeError> {
+                let mut arguments = arguments.iter().enumerate();
+                $(let $arg = next_argument::<$param>(callsite, &mut arguments)?;)*
+                let rest = arguments
+                    .map(|(index, value)| convert::<T>(callsite, index, value))
+                    .collect::<Result<Vec<T>, String>>()?;
+                self($($arg,)* rest).into_native_result()
+            }
+        }
+
+        impl<F, R, $($param),*> NativeCallable<(WithInterpreter, $($param,)*)> for F
+        where
+            F: Fn(&mut Interpreter, $($param),*) -> R + 'static,
+            R: IntoNativeResult,
+            $($param: FromValue,)*
+        {
+            fn arity() -> Arity {
+                Arity::Fixed(count!($($param)*))
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn call(
+                &self,
+                interpreter: &mut Interpreter,
+                callsite: Callsite,
+                arguments: &[Value],
+            ) -> Result<Value, NativeError> {
+                let mut arguments = arguments.iter().enumerate();
+                $(let $arg = next_argument::<$param>(callsite, &mut arguments)?;)*
+                self(interpreter, $($arg),*).into_native_result()
+            }
+        }
+
+        impl<F, R, $($param,)* T> NativeCallable<(WithInterpreter, $($param,)* Vec<T>,)> for F
+        where
+            F: Fn(&mut Interpreter, $($param,)* Vec<T>) -> R + 'static,
+            R: IntoNativeResult,
+            $($param: FromValue,)*
+            T: FromValue,
+        {
+            fn arity() -> Arity {
+                Arity::AtLeast(count!($($param)*))
+            }
+
+            #[allow(unused_mut)]
+            fn call(
+                &self,
+                interpreter: &mut Interpreter,
+                callsite: Callsite,
+                arguments: &[Value],
+            ) -> Result<Value, NativeError> {
+                let mut arguments = arguments.iter().enumerate();
+                $(let $arg = next_argument::<$param>(callsite, &mut arguments)?;)*
+                let rest = arguments
+                    .map(|(index, value)| convert::<T>(callsite, index, value))
+                    .collect::<Result<Vec<T>, String>>()?;
+                self(interpreter, $($arg,)* rest).into_native_result()
+            }
+        }
+    };
+}
+
+impl_native_callable!();
+impl_native_callable!(A a);
+impl_native_callable!(A a, B b);
+impl_native_callable!(A a, B b, C c);
+impl_native_callable!(A a, B b, C c, D d);
+impl_native_callable!(A a, B b, C c, D d, E e);
+impl_native_callable!(A a, B b, C c, D d, E e, G g);
+
+/// Builds a class whose methods are Rust closures, for `Engine::define_class`.
+///
+/// Each method's first parameter receives the instance it was called on,
+/// either as a `Value`, an instance, or a `Handle` to the Rust value the
+/// instance wraps. That value comes from the `init` method: when it returns
+/// userdata, the new instance keeps it, and a Lox subclass that calls
+/// `super.init(...)` gets it too.
+///
+/// ```
+/// use rlox::native::{Handle, NativeClass};
+///
+/// struct Counter(f64);
+///
+/// let mut engine = rlox::Engine::new();
+/// engine.define_class(
+///     NativeClass::new("Counter")
+///         .method("init", |_this: rlox::Value, start: f64| Handle::new(Counter(start)))
+///         .method("next", |this: Handle<Counter>| {
+///             this.borrow_mut().0 += 1.0;
+///             this.borrow().0
+///         }),
+/// );
+/// assert_eq!(engine.eval("var c = Counter(1); c.next(); c.next();").unwrap(), rlox::Value::Number(3.0));
+/// ```
+pub struct NativeClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Method>,
+}
+
+impl NativeClass {
+    pub fn new(name: &str) -> NativeClass {
+        NativeClass {
+            name: name.to_string(),
+            superclass: None,
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Inherits from another class, which may be declared in Lox.
+    pub fn superclass(mut self, superclass: Rc<LoxClass>) -> NativeClass {
+        self.superclass = Some(superclass);
+        self
+    }
+
+    /// Adds a method.
+    ///
+    /// # Panics
+    ///
+    /// If `function` has no parameter before any `Vec` of the rest to take
+    /// the instance: the method would be called with one more argument than
+    /// it declares.
+    pub fn method<Args, F: NativeCallable<Args>>(mut self, name: &str, function: F) -> NativeClass {
+        if let Arity::Fixed(0) | Arity::AtLeast(0) = F::arity() {
+            panic!(
+                "method {} of {} must take the instance as its first parameter",
+                name, self.name
+            );
+        }
+
+        let owned_name = name.to_string();
+        let native = NativeFunction {
+            name: name.to_string(),
+            arity: F::arity(),
+            function: Box::new(move |interpreter, arguments| {
+                let callsite = Callsite {
+                    name: &owned_name,
+                    is_method: true,
+                };
+                function.call(interpreter, callsite, arguments)
+            }),
+        };
+        self.methods
+            .insert(Symbol::intern(name), Method::Native(Rc::new(native)));
+        self
+    }
+
+    pub fn build(self) -> LoxClass {
+        LoxClass {
+            name: self.name,
+            superclass: self.superclass,
+            methods: self.methods,
+        }
+    }
+}
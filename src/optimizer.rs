@@ -0,0 +1,217 @@
+//! Rewrites a resolved program into a cheaper one that behaves the same,
+//! before either backend runs it.
+//!
+//! Arithmetic, comparisons, concatenation, `!` and `and`/`or` on literals are
+//! computed once here instead of every time they're reached, `if` and `while`
+//! statements whose condition is a literal lose the branch that can't run,
+//! and statements after a `return` are dropped. Anything that would be a
+//! runtime error, like `"a" - 1`, is left alone so it still fails when and
+//! where it did.
+
+use crate::{
+    syntax::{
+        BinaryExpr, BlockStmt, Expr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr,
+        Stmt, UnaryExpr, WhileStmt,
+    },
+    token::TokenType,
+    visit::{fold, Fold},
+};
+
+/// Optimizes a program. It has to have been resolved first: the statements
+/// this removes still need checking.
+pub fn optimize(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    ConstantFolder.fold_statements(stmts)
+}
+
+pub struct ConstantFolder;
+
+/// A statement that does nothing, standing in for one that was removed
+/// where a statement is still needed, like the body of an `if`.
+fn nothing(line: usize) -> Stmt {
+    Stmt::Block(BlockStmt {
+        statements: Vec::new(),
+        line,
+    })
+}
+
+fn is_nothing(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Block(block) if block.statements.is_empty())
+}
+
+/// The same truthiness the interpreter uses.
+fn is_truthy(literal: &LiteralValue) -> bool {
+    !matches!(literal, LiteralValue::None | LiteralValue::Bool(false))
+}
+
+/// Literals are equal the way the values they evaluate to are.
+fn literals_equal(left: &LiteralValue, right: &LiteralValue) -> bool {
+    match (left, right) {
+        (LiteralValue::Float(l), LiteralValue::Float(r)) => l == r,
+        (LiteralValue::LoxString(l), LiteralValue::LoxString(r)) => l == r,
+        (LiteralValue::Bool(l), LiteralValue::Bool(r)) => l == r,
+        (LiteralValue::None, LiteralValue::None) => true,
+        _ => false,
+    }
+}
+
+/// The result of a binary operator on two literals, or `None` if it would
+/// be a runtime error.
+fn binary(operator: &TokenType, left: &LiteralValue, right: &LiteralValue) -> Option<LiteralValue> {
+    use LiteralValue::{Bool, Float, LoxString};
+
+    let literal = match (operator, left, right) {
+        (TokenType::EqualEqual, l, r) => Bool(literals_equal(l, r)),
+        (TokenType::BangEqual, l, r) => Bool(!literals_equal(l, r)),
+        (TokenType::Plus, LoxString(l), LoxString(r)) => LoxString([&**l, &**r].concat().into()),
+        (operator, Float(l), Float(r)) => match operator {
+            TokenType::Plus => Float(l + r),
+            TokenType::Minus => Float(l - r),
+            TokenType::Star => Float(l * r),
+            TokenType::Slash => Float(l / r),
+            TokenType::Greater => Bool(l > r),
+            TokenType::GreaterEqual => Bool(l >= r),
+            TokenType::Less => Bool(l < r),
+            TokenType::LessEqual => Bool(l <= r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(literal)
+}
+
+impl Fold for ConstantFolder {
+    fn fold_statements(&mut self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let mut folded = Vec::with_capacity(stmts.len());
+        for stmt in stmts {
+            let stmt = self.fold_statement(stmt);
+            if is_nothing(&stmt) {
+                continue;
+            }
+
+            let returns = matches!(stmt, Stmt::Return(_));
+            folded.push(stmt);
+            if returns {
+                break;
+            }
+        }
+        folded
+    }
+
+    fn fold_if_stmt(&mut self, stmt: IfStmt) -> Stmt {
+        let condition = self.fold_expression(stmt.condition);
+        if let Expr::Literal(literal) = &condition {
+            let branch = match is_truthy(&literal.value) {
+                true => Some(stmt.then_branch),
+                false => stmt.else_branch,
+            };
+            return branch
+                .map_or_else(|| nothing(stmt.line), |branch| self.fold_statement(*branch));
+        }
+
+        Stmt::If(IfStmt {
+            condition,
+            then_branch: Box::new(self.fold_statement(*stmt.then_branch)),
+            else_branch: stmt
+                .else_branch
+                .map(|branch| Box::new(self.fold_statement(*branch))),
+            line: stmt.line,
+        })
+    }
+
+    fn fold_while_stmt(&mut self, stmt: WhileStmt) -> Stmt {
+        let condition = self.fold_expression(stmt.condition);
+        if matches!(&condition, Expr::Literal(literal) if !is_truthy(&literal.value)) {
+            return nothing(stmt.line);
+        }
+
+        Stmt::While(WhileStmt {
+            condition,
+            body: Box::new(self.fold_statement(*stmt.body)),
+            line: stmt.line,
+        })
+    }
+
+    fn fold_binary(&mut self, expr: BinaryExpr) -> Expr {
+        let left = self.fold_expression(*expr.left);
+        let right = self.fold_expression(*expr.right);
+
+        if let (Expr::Literal(l), Expr::Literal(r)) = (&left, &right) {
+            if let Some(value) = binary(&expr.operator.token_type, &l.value, &r.value) {
+                return Expr::Literal(LiteralExpr {
+                    value,
+                    line: expr.operator.line,
+                });
+            }
+        }
+
+        Expr::Binary(BinaryExpr {
+            left: Box::new(left),
+            operator: expr.operator,
+            right: Box::new(right),
+        })
+    }
+
+    fn fold_grouping(&mut self, expr: Grouping) -> Expr {
+        match fold::walk_grouping(self, expr) {
+            Expr::Grouping(Grouping { expression, .. })
+                if matches!(*expression, Expr::Literal(_)) =>
+            {
+                *expression
+            }
+            expr => expr,
+        }
+    }
+
+    fn fold_unary(&mut self, expr: UnaryExpr) -> Expr {
+        let right = self.fold_expression(*expr.right);
+
+        let folded = match (&expr.operator.token_type, &right) {
+            (TokenType::Bang, Expr::Literal(literal)) => {
+                Some(LiteralValue::Bool(!is_truthy(&literal.value)))
+            }
+            (
+                TokenType::Minus,
+                Expr::Literal(LiteralExpr {
+                    value: LiteralValue::Float(number),
+                    ..
+                }),
+            ) => Some(LiteralValue::Float(-number)),
+            _ => None,
+        };
+
+        match folded {
+            Some(value) => Expr::Literal(LiteralExpr {
+                value,
+                line: expr.operator.line,
+            }),
+            None => Expr::Unary(UnaryExpr {
+                operator: expr.operator,
+                right: Box::new(right),
+            }),
+        }
+    }
+
+    /// `and` and `or` give back one of their operands, so a literal on the
+    /// left decides which.
+    fn fold_logical(&mut self, expr: LogicalExpr) -> Expr {
+        let left = self.fold_expression(*expr.left);
+        let right = self.fold_expression(*expr.right);
+
+        if let Expr::Literal(literal) = &left {
+            let short_circuits = match expr.operator.token_type {
+                TokenType::Or => is_truthy(&literal.value),
+                _ => !is_truthy(&literal.value),
+            };
+            return match short_circuits {
+                true => left,
+                false => right,
+            };
+        }
+
+        Expr::Logical(LogicalExpr {
+            left: Box::new(left),
+            operator: expr.operator,
+            right: Box::new(right),
+        })
+    }
+}
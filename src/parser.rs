@@ -2,13 +2,20 @@ use std::fmt;
 use std::iter::Peekable;
 
 use crate::{
-    syntax::{BinaryExpr, Expr, Grouping, LiteralValue, UnaryExpr},
+    syntax::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, Expr, ExpressionStmt, FunctionStmt,
+        GetExpr, Grouping, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt,
+        SetExpr, Stmt, SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+    },
     token::{Token, TokenType as TT},
 };
 
 type BoxIterToken = Box<dyn Iterator<Item = Token>>;
 type TokenPeekable = Peekable<BoxIterToken>;
 type ExprResult = Result<Expr, ParserError>;
+type StmtResult = Result<Stmt, ParserError>;
+
+const MAX_ARGUMENTS: usize = 255;
 
 #[derive(Debug)]
 pub enum ParserError {
@@ -16,6 +23,10 @@ pub enum ParserError {
     NonPrimaryToken(Token),
     EmptyPrimary(usize),
     EmptyExpression(usize),
+    ExpectedToken(TT, Token),
+    ExpectedIdentifier(Token),
+    InvalidAssignmentTarget(usize),
+    TooManyArguments(usize),
 }
 
 impl fmt::Display for ParserError {
@@ -45,6 +56,34 @@ impl fmt::Display for ParserError {
                     line
                 )?;
             }
+            ParserError::ExpectedToken(expected, token) => {
+                write!(
+                    f,
+                    "Parser Error: Expecting '{}' at line {}, found {:?}",
+                    expected, token.line, token.token_type
+                )?;
+            }
+            ParserError::ExpectedIdentifier(token) => {
+                write!(
+                    f,
+                    "Parser Error: Expecting an identifier at line {}, found {:?}",
+                    token.line, token.token_type
+                )?;
+            }
+            ParserError::InvalidAssignmentTarget(line) => {
+                write!(
+                    f,
+                    "Parser Error: Invalid assignment target at line {}",
+                    line
+                )?;
+            }
+            ParserError::TooManyArguments(line) => {
+                write!(
+                    f,
+                    "Parser Error: Can't have more than {} arguments at line {}",
+                    MAX_ARGUMENTS, line
+                )?;
+            }
         }
 
         Ok(())
@@ -58,6 +97,22 @@ impl ParserError {
             ParserError::NonPrimaryToken(Token { line, .. }) => line,
             ParserError::EmptyPrimary(line) => line,
             ParserError::EmptyExpression(line) => line,
+            ParserError::ExpectedToken(_, Token { line, .. }) => line,
+            ParserError::ExpectedIdentifier(Token { line, .. }) => line,
+            ParserError::InvalidAssignmentTarget(line) => line,
+            ParserError::TooManyArguments(line) => line,
+        }
+    }
+
+    /// Whether the parser ran out of tokens rather than finding a wrong one,
+    /// i.e. the input could still become valid if more of it followed.
+    pub fn is_at_eof(&self) -> bool {
+        match self {
+            ParserError::NonPrimaryToken(token)
+            | ParserError::ExpectedToken(_, token)
+            | ParserError::ExpectedIdentifier(token) => token.token_type == TT::Eof,
+            ParserError::EmptyPrimary(_) => true,
+            _ => false,
         }
     }
 }
@@ -76,8 +131,302 @@ impl Parser {
         }
     }
 
-    pub fn parse(&mut self) -> ExprResult {
-        self.expression()
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParserError>> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        let mut errors: Vec<ParserError> = Vec::new();
+
+        while !self.is_at_end() {
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(err) => {
+                    errors.push(err);
+                    self.synchronize();
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(statements)
+    }
+
+    /// Parses the tokens as a single expression with no trailing semicolon.
+    pub fn parse_expression(&mut self) -> ExprResult {
+        let expr = self.expression()?;
+
+        match self.is_at_end() {
+            true => Ok(expr),
+            false => Err(ParserError::ExpectedToken(TT::Eof, self.current_token())),
+        }
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        matches!(
+            self.tokens.peek(),
+            None | Some(Token {
+                token_type: TT::Eof,
+                ..
+            })
+        )
+    }
+
+    fn check(&mut self, token_type: &TT) -> bool {
+        matches!(self.tokens.peek(), Some(token) if &token.token_type == token_type)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.next()?;
+        // Update prev token line pointer
+        self.prev_token_line = token.line;
+        Some(token)
+    }
+
+    fn match_token(&mut self, token_type: &TT) -> Option<Token> {
+        if self.check(token_type) {
+            self.advance()
+        } else {
+            None
+        }
+    }
+
+    fn consume(&mut self, token_type: TT) -> Result<Token, ParserError> {
+        if self.check(&token_type) {
+            return Ok(self.advance().unwrap());
+        }
+
+        Err(ParserError::ExpectedToken(token_type, self.current_token()))
+    }
+
+    fn consume_identifier(&mut self) -> Result<Token, ParserError> {
+        match self.tokens.peek() {
+            Some(Token {
+                token_type: TT::Identifier(_),
+                ..
+            }) => Ok(self.advance().unwrap()),
+            _ => Err(ParserError::ExpectedIdentifier(self.current_token())),
+        }
+    }
+
+    fn current_token(&mut self) -> Token {
+        match self.tokens.peek() {
+            Some(token) => token.clone(),
+            None => Token::new(TT::Eof, self.prev_token_line),
+        }
+    }
+
+    fn declaration(&mut self) -> StmtResult {
+        if self.match_token(&TT::Class).is_some() {
+            self.class_declaration()
+        } else if self.match_token(&TT::Fun).is_some() {
+            Ok(Stmt::Function(self.function()?))
+        } else if self.match_token(&TT::Var).is_some() {
+            self.var_declaration()
+        } else {
+            self.statement()
+        }
+    }
+
+    fn class_declaration(&mut self) -> StmtResult {
+        let name = self.consume_identifier()?;
+
+        let superclass = match self.match_token(&TT::Less) {
+            Some(_) => Some(Variable {
+                name: self.consume_identifier()?,
+                slot: Default::default(),
+            }),
+            None => None,
+        };
+
+        self.consume(TT::LeftBrace)?;
+        let mut methods: Vec<FunctionStmt> = Vec::new();
+        while !self.check(&TT::RightBrace) && !self.is_at_end() {
+            methods.push(self.function()?);
+        }
+        self.consume(TT::RightBrace)?;
+
+        Ok(Stmt::Class(ClassStmt {
+            name,
+            superclass,
+            methods,
+        }))
+    }
+
+    fn function(&mut self) -> Result<FunctionStmt, ParserError> {
+        let name = self.consume_identifier()?;
+
+        self.consume(TT::LeftParen)?;
+        let mut params: Vec<Token> = Vec::new();
+        if !self.check(&TT::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    return Err(ParserError::TooManyArguments(self.prev_token_line));
+                }
+                params.push(self.consume_identifier()?);
+
+                if self.match_token(&TT::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+        self.consume(TT::RightParen)?;
+
+        self.consume(TT::LeftBrace)?;
+        let body = self.block()?;
+
+        Ok(FunctionStmt { name, params, body })
+    }
+
+    fn var_declaration(&mut self) -> StmtResult {
+        let name = self.consume_identifier()?;
+
+        let initializer = match self.match_token(&TT::Equal) {
+            Some(_) => Some(self.expression()?),
+            None => None,
+        };
+        self.consume(TT::Semicolon)?;
+
+        Ok(Stmt::Var(VarStmt { name, initializer }))
+    }
+
+    fn statement(&mut self) -> StmtResult {
+        if let Some(keyword) = self.match_token(&TT::For) {
+            self.for_statement(keyword.line)
+        } else if let Some(keyword) = self.match_token(&TT::If) {
+            self.if_statement(keyword.line)
+        } else if let Some(keyword) = self.match_token(&TT::Print) {
+            let expression = self.expression()?;
+            self.consume(TT::Semicolon)?;
+            Ok(Stmt::Print(PrintStmt {
+                expression,
+                line: keyword.line,
+            }))
+        } else if let Some(keyword) = self.match_token(&TT::Return) {
+            let value = match self.check(&TT::Semicolon) {
+                true => None,
+                false => Some(self.expression()?),
+            };
+            self.consume(TT::Semicolon)?;
+            Ok(Stmt::Return(ReturnStmt {
+                keyword,
+                value,
+                tail_call: Default::default(),
+            }))
+        } else if let Some(keyword) = self.match_token(&TT::While) {
+            self.consume(TT::LeftParen)?;
+            let condition = self.expression()?;
+            self.consume(TT::RightParen)?;
+            let body = Box::new(self.statement()?);
+            Ok(Stmt::While(WhileStmt {
+                condition,
+                body,
+                line: keyword.line,
+            }))
+        } else if let Some(brace) = self.match_token(&TT::LeftBrace) {
+            Ok(Stmt::Block(BlockStmt {
+                statements: self.block()?,
+                line: brace.line,
+            }))
+        } else {
+            self.expression_statement()
+        }
+    }
+
+    fn expression_statement(&mut self) -> StmtResult {
+        let line = self.current_token().line;
+        let expression = self.expression()?;
+        self.consume(TT::Semicolon)?;
+        Ok(Stmt::Expression(ExpressionStmt { expression, line }))
+    }
+
+    // A for loop is desugared into its initializer followed by a while loop
+    fn for_statement(&mut self, line: usize) -> StmtResult {
+        self.consume(TT::LeftParen)?;
+
+        let initializer = if self.match_token(&TT::Semicolon).is_some() {
+            None
+        } else if self.match_token(&TT::Var).is_some() {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = match self.check(&TT::Semicolon) {
+            true => Expr::Literal(LiteralExpr {
+                value: true.into(),
+                line,
+            }),
+            false => self.expression()?,
+        };
+        self.consume(TT::Semicolon)?;
+
+        let increment_line = self.current_token().line;
+        let increment = match self.check(&TT::RightParen) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(TT::RightParen)?;
+
+        let mut body = self.statement()?;
+
+        if let Some(expression) = increment {
+            body = Stmt::Block(BlockStmt {
+                statements: vec![
+                    body,
+                    Stmt::Expression(ExpressionStmt {
+                        expression,
+                        line: increment_line,
+                    }),
+                ],
+                line,
+            });
+        }
+
+        body = Stmt::While(WhileStmt {
+            condition,
+            body: Box::new(body),
+            line,
+        });
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(BlockStmt {
+                statements: vec![initializer, body],
+                line,
+            });
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self, line: usize) -> StmtResult {
+        self.consume(TT::LeftParen)?;
+        let condition = self.expression()?;
+        self.consume(TT::RightParen)?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = match self.match_token(&TT::Else) {
+            Some(_) => Some(Box::new(self.statement()?)),
+            None => None,
+        };
+
+        Ok(Stmt::If(IfStmt {
+            condition,
+            then_branch,
+            else_branch,
+            line,
+        }))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ParserError> {
+        let mut statements: Vec<Stmt> = Vec::new();
+
+        while !self.check(&TT::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        self.consume(TT::RightBrace)?;
+
+        Ok(statements)
     }
 
     fn binary_expr_generator(
@@ -107,8 +456,57 @@ impl Parser {
         Ok(expr)
     }
 
+    fn logical_expr_generator(
+        &mut self,
+        expr_fn: Box<dyn Fn(&mut Parser) -> ExprResult>,
+        expr_token: TT,
+    ) -> ExprResult {
+        let mut expr = expr_fn(self)?;
+
+        while let Some(operator) = self.match_token(&expr_token) {
+            let right = expr_fn(self)?;
+            expr = Expr::Logical(LogicalExpr {
+                left: Box::new(expr),
+                right: Box::new(right),
+                operator,
+            })
+        }
+
+        Ok(expr)
+    }
+
     fn expression(&mut self) -> ExprResult {
-        return self.equality();
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> ExprResult {
+        let expr = self.or()?;
+
+        if let Some(equals) = self.match_token(&TT::Equal) {
+            let value = Box::new(self.assignment()?);
+
+            return match expr {
+                Expr::Variable(Variable { name, slot }) => {
+                    Ok(Expr::Assign(AssignExpr { name, value, slot }))
+                }
+                Expr::Get(GetExpr { object, name }) => Ok(Expr::Set(SetExpr {
+                    object,
+                    name,
+                    value,
+                })),
+                _ => Err(ParserError::InvalidAssignmentTarget(equals.line)),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ExprResult {
+        self.logical_expr_generator(Box::new(Parser::and), TT::Or)
+    }
+
+    fn and(&mut self) -> ExprResult {
+        self.logical_expr_generator(Box::new(Parser::equality), TT::And)
     }
 
     fn equality(&mut self) -> ExprResult {
@@ -133,7 +531,7 @@ impl Parser {
 
     fn unary(&mut self) -> ExprResult {
         let unary_tokens = [TT::Bang, TT::Minus];
-        let intrem = match self.tokens.peek() {
+        match self.tokens.peek() {
             Some(token) if unary_tokens.contains(&token.token_type) => {
                 // Update prev token line pointer
                 self.prev_token_line = token.line;
@@ -146,10 +544,52 @@ impl Parser {
                 }))
             }
 
-            _ => self.primary(),
-        };
+            _ => self.call(),
+        }
+    }
+
+    fn call(&mut self) -> ExprResult {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_token(&TT::LeftParen).is_some() {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(&TT::Dot).is_some() {
+                let name = self.consume_identifier()?;
+                expr = Expr::Get(GetExpr {
+                    object: Box::new(expr),
+                    name,
+                });
+            } else {
+                break;
+            }
+        }
 
-        intrem
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ExprResult {
+        let mut arguments: Vec<Expr> = Vec::new();
+
+        if !self.check(&TT::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    return Err(ParserError::TooManyArguments(self.prev_token_line));
+                }
+                arguments.push(self.expression()?);
+
+                if self.match_token(&TT::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TT::RightParen)?;
+
+        Ok(Expr::Call(CallExpr {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
     }
 
     fn primary(&mut self) -> ExprResult {
@@ -184,6 +624,7 @@ impl Parser {
                     {
                         TT::RightParen => Ok(Expr::Grouping(Grouping {
                             expression: Box::new(expr),
+                            line: token.line,
                         })),
                         _token => Err(ParserError::UnterminatedParentheses(
                             self.prev_token_line,
@@ -197,14 +638,34 @@ impl Parser {
                 TT::True => self.consume_and_cast_literal(true.into()),
                 TT::Nil => self.consume_and_cast_literal(LiteralValue::None),
                 TT::Number(borrowed_float) => {
-                    let float = borrowed_float.clone();
+                    let float = *borrowed_float;
                     self.consume_and_cast_literal(float.into())
                 }
-                TT::LoxString(borrowed_str) => {
-                    let lox_string = borrowed_str.clone();
+                TT::LoxString(lox_string) => {
+                    let lox_string = lox_string.clone();
                     self.consume_and_cast_literal(lox_string.into())
                 }
 
+                // Handle names
+                TT::Identifier(_) => Ok(Expr::Variable(Variable {
+                    name: self.tokens.next().unwrap(),
+                    slot: Default::default(),
+                })),
+                TT::This => Ok(Expr::This(ThisExpr {
+                    keyword: self.tokens.next().unwrap(),
+                    slot: Default::default(),
+                })),
+                TT::Super => {
+                    let keyword = self.tokens.next().unwrap();
+                    self.consume(TT::Dot)?;
+                    let method = self.consume_identifier()?;
+                    Ok(Expr::Super(SuperExpr {
+                        keyword,
+                        method,
+                        slot: Default::default(),
+                    }))
+                }
+
                 _ => Err(ParserError::NonPrimaryToken(peek_token.clone())),
             }
         } else {
@@ -212,13 +673,17 @@ impl Parser {
         }
     }
 
-    fn consume_and_cast_literal(&mut self, literal_value: LiteralValue) -> ExprResult {
-        self.tokens.next();
-        Ok(Expr::Literal(literal_value))
+    fn consume_and_cast_literal(&mut self, value: LiteralValue) -> ExprResult {
+        let line = self.advance().unwrap().line;
+        Ok(Expr::Literal(LiteralExpr { value, line }))
     }
 
     fn synchronize(&mut self) {
-        self.tokens.next();
+        if let Some(token) = self.tokens.next() {
+            if token.token_type == TT::Semicolon {
+                return;
+            }
+        }
 
         while let Some(token) = self.tokens.peek() {
             if [
@@ -236,7 +701,9 @@ impl Parser {
                 return;
             }
 
-            self.tokens.next();
+            if self.tokens.next().unwrap().token_type == TT::Semicolon {
+                return;
+            }
         }
     }
 }
@@ -0,0 +1,264 @@
+//! The interactive session behind `rlox repl`, minus the line editor: it
+//! gathers lines until they make a complete input, runs it, echoes the value
+//! of a bare expression, and handles the `:` meta-commands.
+
+use std::fs;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use crate::{
+    ast_printer::ASTStringVisitor,
+    parser::{Parser, ParserError},
+    resolver::Resolver,
+    scanner::{Scanner, ScannerError},
+    syntax::{Expr, Stmt},
+    token::TokenType,
+    visit::Visitor,
+    Engine, Error,
+};
+
+/// What the REPL should do with the input typed so far.
+enum Input {
+    Statements(Vec<Stmt>),
+    Expression(Expr),
+    Incomplete,
+    Invalid,
+}
+
+/// A REPL session. Globals persist from one input to the next, until
+/// `:reset`.
+pub struct Repl {
+    engine: Engine,
+    /// Sets up each new engine, so `:reset` keeps where `print` writes to.
+    configure: Box<dyn Fn(&mut Engine)>,
+    input: String,
+    output: Box<dyn Write>,
+    errors: Box<dyn Write>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    /// A session that writes to stdout and reports errors on stderr.
+    pub fn new() -> Repl {
+        Repl {
+            engine: Engine::new(),
+            configure: Box::new(|_| {}),
+            input: String::new(),
+            output: Box::new(io::stdout()),
+            errors: Box::new(io::stderr()),
+        }
+    }
+
+    /// A session that writes everything it prints, `print` statements
+    /// included, to `output`, and reports errors to `errors`.
+    pub fn with_output(output: impl Write + Clone + 'static, errors: impl Write + 'static) -> Repl {
+        let engine_output = output.clone();
+        let configure = move |engine: &mut Engine| engine.set_output(engine_output.clone());
+        let mut engine = Engine::new();
+        configure(&mut engine);
+
+        Repl {
+            engine,
+            configure: Box::new(configure),
+            input: String::new(),
+            output: Box::new(output),
+            errors: Box::new(errors),
+        }
+    }
+
+    /// `"> "` while waiting for new input, `"... "` while the lines typed so
+    /// far need more to be complete.
+    pub fn prompt(&self) -> &'static str {
+        if self.input.is_empty() {
+            "> "
+        } else {
+            "... "
+        }
+    }
+
+    /// The globals defined so far, for completion.
+    pub fn global_names(&self) -> Vec<String> {
+        self.engine.global_names()
+    }
+
+    /// Forgets the lines typed since the last complete input, as Ctrl-C does.
+    pub fn interrupt(&mut self) {
+        self.input.clear();
+    }
+
+    /// Takes one line. A `:` at the start of a new input makes it a command;
+    /// anything else is added to the input so far, which runs as soon as it's
+    /// complete. An empty line runs it even if it isn't, to show what's wrong.
+    pub fn read_line(&mut self, line: &str) -> io::Result<()> {
+        if self.input.is_empty() && line.trim_start().starts_with(':') {
+            return self.run_command(line.trim());
+        }
+
+        let force = !self.input.is_empty() && line.trim().is_empty();
+        self.input.push_str(line);
+        self.input.push('\n');
+
+        let input = std::mem::take(&mut self.input);
+        match self.read_input(&input, force)? {
+            Input::Incomplete => {
+                self.input = input;
+                Ok(())
+            }
+            Input::Statements(stmts) => self.run_statements(&stmts),
+            Input::Expression(expr) => self.print_value(&expr),
+            Input::Invalid => Ok(()),
+        }
+    }
+
+    fn run_command(&mut self, line: &str) -> io::Result<()> {
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            ":tokens" => match Scanner::new(argument).scan_tokens() {
+                Ok(tokens) => {
+                    for token in tokens {
+                        writeln!(self.output, "{}", token)?;
+                    }
+                }
+                Err(errors) => self.error(Error::Scan(errors))?,
+            },
+            ":ast" => match self.read_input(argument, true)? {
+                Input::Statements(stmts) => {
+                    writeln!(self.output, "{}", ASTStringVisitor { statements: &stmts })?
+                }
+                Input::Expression(expr) => writeln!(
+                    self.output,
+                    "{}",
+                    ASTStringVisitor { statements: &[] }.visit_expression(&expr)
+                )?,
+                Input::Incomplete | Input::Invalid => {}
+            },
+            ":env" => {
+                for name in self.engine.global_names() {
+                    if let Some(value) = self.engine.get_global(&name) {
+                        writeln!(self.output, "{} = {}", name, value)?;
+                    }
+                }
+            }
+            ":load" if !argument.is_empty() => match fs::read_to_string(argument) {
+                Ok(source) => {
+                    if let Err(err) = self.engine.run(&source) {
+                        self.error(err)?;
+                    }
+                }
+                Err(err) => writeln!(self.errors, "Could not read {}: {}", argument, err)?,
+            },
+            ":reset" => {
+                self.engine = Engine::new();
+                (self.configure)(&mut self.engine);
+            }
+            ":time" => {
+                let start = Instant::now();
+                match self.read_input(argument, true)? {
+                    Input::Statements(stmts) => self.run_statements(&stmts)?,
+                    Input::Expression(expr) => self.print_value(&expr)?,
+                    Input::Incomplete | Input::Invalid => return Ok(()),
+                }
+                writeln!(self.output, "Elapsed: {:?}", start.elapsed())?;
+            }
+            ":help" => {
+                let help = [
+                    ":tokens <code>  Show the tokens the scanner produces for <code>",
+                    ":ast <code>     Show the parse tree of <code>",
+                    ":env            List global bindings and their values",
+                    ":load <file>    Run <file> in the current session",
+                    ":reset          Forget everything defined so far",
+                    ":time <code>    Run <code> and report how long it took",
+                    ":help           Show this message",
+                ];
+                for line in help {
+                    writeln!(self.output, "{}", line)?;
+                }
+            }
+            _ => writeln!(self.errors, "Unknown command {}, try :help", line)?,
+        }
+
+        Ok(())
+    }
+
+    fn read_input(&mut self, source: &str, force: bool) -> io::Result<Input> {
+        let tokens = match Scanner::new(source).scan_tokens() {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                let unterminated = errors
+                    .iter()
+                    .any(|err| matches!(err, ScannerError::UnterminatedString(_)));
+                if unterminated && !force {
+                    return Ok(Input::Incomplete);
+                }
+
+                self.error(Error::Scan(errors))?;
+                return Ok(Input::Invalid);
+            }
+        };
+
+        let depth = tokens
+            .iter()
+            .fold(0isize, |depth, token| match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBrace => depth + 1,
+                TokenType::RightParen | TokenType::RightBrace => depth - 1,
+                _ => depth,
+            });
+
+        match Parser::new(tokens.clone()).parse() {
+            Ok(stmts) => match Resolver::new().resolve(&stmts) {
+                Ok(()) => Ok(Input::Statements(stmts)),
+                Err(errors) => {
+                    self.error(Error::Resolve(errors))?;
+                    Ok(Input::Invalid)
+                }
+            },
+            Err(errors) => {
+                // A bare expression like `1 + 2` doesn't need its semicolon,
+                // and leaving it off is what asks for its value
+                if let Ok(expr) = Parser::new(tokens).parse_expression() {
+                    return match Resolver::new().resolve_expression(&expr) {
+                        Ok(()) => Ok(Input::Expression(expr)),
+                        Err(errors) => {
+                            self.error(Error::Resolve(errors))?;
+                            Ok(Input::Invalid)
+                        }
+                    };
+                }
+
+                if !force && (depth > 0 || errors.iter().all(ParserError::is_at_eof)) {
+                    return Ok(Input::Incomplete);
+                }
+
+                self.error(Error::Parse(errors))?;
+                Ok(Input::Invalid)
+            }
+        }
+    }
+
+    fn run_statements(&mut self, stmts: &[Stmt]) -> io::Result<()> {
+        match self.engine.execute(stmts) {
+            Ok(()) => Ok(()),
+            Err(err) => self.error(err),
+        }
+    }
+
+    fn print_value(&mut self, expr: &Expr) -> io::Result<()> {
+        match self.engine.evaluate(expr) {
+            Ok(value) => writeln!(self.output, "{}", value),
+            Err(err) => self.error(err),
+        }
+    }
+
+    fn error(&mut self, err: Error) -> io::Result<()> {
+        writeln!(self.errors, "{}", err)
+    }
+}
@@ -0,0 +1,361 @@
+//! Static checks that need to know how names are scoped, run between parsing
+//! and execution so a program with one of these mistakes never starts.
+//!
+//! The resolver also works out where each local variable lives, recording a
+//! `Slot` on every use so the interpreter can go straight to it.
+
+use std::cell::Cell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{
+    symbol::Symbol,
+    syntax::{
+        AssignExpr, BlockStmt, ClassStmt, Expr, FunctionStmt, ReturnStmt, Slot, Stmt, SuperExpr,
+        ThisExpr, VarStmt, Variable,
+    },
+    token::Token,
+    visit::{walk_mut, MutVisitor},
+};
+
+#[derive(Debug)]
+pub enum ResolverError {
+    ReadInOwnInitializer(String, usize),
+    AlreadyDeclared(String, usize),
+    TopLevelReturn(usize),
+    ReturnValueFromInitializer(usize),
+    ThisOutsideClass(usize),
+    SuperOutsideClass(usize),
+    SuperWithoutSuperclass(usize),
+    InheritsFromItself(String, usize),
+}
+
+impl fmt::Display for ResolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolverError::ReadInOwnInitializer(name, line) => {
+                write!(
+                    f,
+                    "Resolver Error: Can't read local variable '{}' in its own initializer at line {}",
+                    name, line
+                )?;
+            }
+            ResolverError::AlreadyDeclared(name, line) => {
+                write!(
+                    f,
+                    "Resolver Error: Already a variable named '{}' in this scope at line {}",
+                    name, line
+                )?;
+            }
+            ResolverError::TopLevelReturn(line) => {
+                write!(
+                    f,
+                    "Resolver Error: Can't return from top-level code at line {}",
+                    line
+                )?;
+            }
+            ResolverError::ReturnValueFromInitializer(line) => {
+                write!(
+                    f,
+                    "Resolver Error: Can't return a value from an initializer at line {}",
+                    line
+                )?;
+            }
+            ResolverError::ThisOutsideClass(line) => {
+                write!(
+                    f,
+                    "Resolver Error: Can't use 'this' outside of a class at line {}",
+                    line
+                )?;
+            }
+            ResolverError::SuperOutsideClass(line) => {
+                write!(
+                    f,
+                    "Resolver Error: Can't use 'super' outside of a class at line {}",
+                    line
+                )?;
+            }
+            ResolverError::SuperWithoutSuperclass(line) => {
+                write!(
+                    f,
+                    "Resolver Error: Can't use 'super' in a class with no superclass at line {}",
+                    line
+                )?;
+            }
+            ResolverError::InheritsFromItself(name, line) => {
+                write!(
+                    f,
+                    "Resolver Error: Class '{}' can't inherit from itself at line {}",
+                    name, line
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ResolverError {
+    pub fn line(&self) -> usize {
+        match *self {
+            ResolverError::ReadInOwnInitializer(_, line) => line,
+            ResolverError::AlreadyDeclared(_, line) => line,
+            ResolverError::TopLevelReturn(line) => line,
+            ResolverError::ReturnValueFromInitializer(line) => line,
+            ResolverError::ThisOutsideClass(line) => line,
+            ResolverError::SuperOutsideClass(line) => line,
+            ResolverError::SuperWithoutSuperclass(line) => line,
+            ResolverError::InheritsFromItself(_, line) => line,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+/// A local variable in one of the resolver's scopes.
+#[derive(Clone, Copy)]
+struct Local {
+    /// How many variables were declared in the scope before this one.
+    index: usize,
+    /// Whether its initializer has finished.
+    defined: bool,
+}
+
+/// Walks a program keeping a stack of block scopes, each mapping the locals
+/// declared in it to their slots. Globals aren't tracked, so they can be
+/// redeclared and referred to before they're defined.
+pub struct Resolver {
+    scopes: Vec<HashMap<Symbol, Local>>,
+    function: FunctionKind,
+    class: ClassKind,
+    errors: Vec<ResolverError>,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            function: FunctionKind::None,
+            class: ClassKind::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(mut self, statements: &[Stmt]) -> Result<(), Vec<ResolverError>> {
+        walk_mut::walk_statements(&mut self, statements);
+        self.finish()
+    }
+
+    pub fn resolve_expression(mut self, expr: &Expr) -> Result<(), Vec<ResolverError>> {
+        self.visit_expression(expr);
+        self.finish()
+    }
+
+    fn finish(self) -> Result<(), Vec<ResolverError>> {
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.errors),
+        }
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let index = scope.len();
+            match scope.entry(name.symbol()) {
+                Entry::Occupied(entry) => self.errors.push(ResolverError::AlreadyDeclared(
+                    entry.key().to_string(),
+                    name.line,
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert(Local {
+                        index,
+                        defined: false,
+                    });
+                }
+            }
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        let local = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.symbol()));
+        if let Some(local) = local {
+            local.defined = true;
+        }
+    }
+
+    /// A scope holding only `name`, for the `this` and `super` that methods
+    /// see around them.
+    fn begin_implicit_scope(&mut self, name: &str) {
+        let local = Local {
+            index: 0,
+            defined: true,
+        };
+        self.scopes
+            .push(HashMap::from([(Symbol::intern(name), local)]));
+    }
+
+    /// Records where the local `name` lives, innermost scope first. Names
+    /// not found in any scope are globals and keep an empty slot.
+    fn resolve_local(&self, name: Symbol, slot: &Cell<Option<Slot>>) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                scope.get(&name).map(|local| Slot {
+                    depth,
+                    index: local.index,
+                })
+            });
+        slot.set(found);
+    }
+
+    fn resolve_function(&mut self, stmt: &FunctionStmt, kind: FunctionKind) {
+        let enclosing = self.function;
+        self.function = kind;
+
+        self.scopes.push(HashMap::new());
+        for param in &stmt.params {
+            self.declare(param);
+            self.define(param);
+        }
+        walk_mut::walk_statements(self, &stmt.body);
+        self.scopes.pop();
+
+        self.function = enclosing;
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MutVisitor for Resolver {
+    type E = ();
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        self.scopes.push(HashMap::new());
+        walk_mut::walk_block_stmt(self, stmt);
+        self.scopes.pop();
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        self.declare(&stmt.name);
+        walk_mut::walk_var_stmt(self, stmt);
+        self.define(&stmt.name);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) {
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionKind::Function);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) {
+        match self.function {
+            FunctionKind::None => self
+                .errors
+                .push(ResolverError::TopLevelReturn(stmt.keyword.line)),
+            FunctionKind::Initializer if stmt.value.is_some() => self
+                .errors
+                .push(ResolverError::ReturnValueFromInitializer(stmt.keyword.line)),
+            _ => stmt
+                .tail_call
+                .set(matches!(stmt.value, Some(Expr::Call(_)))),
+        }
+        walk_mut::walk_return_stmt(self, stmt);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) {
+        let enclosing = self.class;
+        self.class = ClassKind::Class;
+
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.token_type == stmt.name.token_type {
+                self.errors.push(ResolverError::InheritsFromItself(
+                    stmt.name.token_type.to_string(),
+                    superclass.name.line,
+                ));
+            }
+            self.class = ClassKind::Subclass;
+            self.visit_variable(superclass);
+            self.begin_implicit_scope("super");
+        }
+
+        self.begin_implicit_scope("this");
+        for method in &stmt.methods {
+            let kind = match method.name.token_type.to_string().as_str() {
+                "init" => FunctionKind::Initializer,
+                _ => FunctionKind::Method,
+            };
+            self.resolve_function(method, kind);
+        }
+        self.scopes.pop();
+
+        if stmt.superclass.is_some() {
+            self.scopes.pop();
+        }
+        self.class = enclosing;
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) {
+        let key = expr.name.symbol();
+        let local = self.scopes.last().and_then(|scope| scope.get(&key));
+        if let Some(Local { defined: false, .. }) = local {
+            self.errors.push(ResolverError::ReadInOwnInitializer(
+                key.to_string(),
+                expr.name.line,
+            ));
+        }
+        self.resolve_local(key, &expr.slot);
+    }
+
+    fn visit_assign(&mut self, expr: &AssignExpr) {
+        walk_mut::walk_assign(self, expr);
+        self.resolve_local(expr.name.symbol(), &expr.slot);
+    }
+
+    fn visit_this(&mut self, expr: &ThisExpr) {
+        if self.class == ClassKind::None {
+            self.errors
+                .push(ResolverError::ThisOutsideClass(expr.keyword.line));
+        }
+        self.resolve_local(Symbol::intern("this"), &expr.slot);
+    }
+
+    fn visit_super(&mut self, expr: &SuperExpr) {
+        match self.class {
+            ClassKind::None => self
+                .errors
+                .push(ResolverError::SuperOutsideClass(expr.keyword.line)),
+            ClassKind::Class => self
+                .errors
+                .push(ResolverError::SuperWithoutSuperclass(expr.keyword.line)),
+            ClassKind::Subclass => {}
+        }
+        self.resolve_local(Symbol::intern("super"), &expr.slot);
+    }
+}
@@ -0,0 +1,131 @@
+//! Prints expressions in Reverse Polish notation, the order a stack machine
+//! would evaluate them in, e.g. `(1 + 2) * (4 - 3)` becomes `1 2 + 4 3 - *`.
+//!
+//! Unary minus is written `neg` and `!` is written `not` so they can't be
+//! confused with their binary forms. Groupings disappear, since the operand
+//! order already encodes them. Calls are written `callee args... call/<arity>`.
+//!
+//! Only expressions have an RPN form, so `rpn` takes an `Expr` rather than
+//! statements.
+
+use crate::{
+    ast_printer::{format_number, format_string},
+    syntax::{
+        AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, Grouping, LiteralExpr, LiteralValue,
+        LogicalExpr, SetExpr, SuperExpr, ThisExpr, UnaryExpr, Variable,
+    },
+    token::TokenType,
+    visit::Visitor,
+};
+
+/// Prints `expr` in Reverse Polish notation.
+pub fn rpn(expr: &Expr) -> String {
+    RPNVisitor.visit_expression(expr)
+}
+
+/// The visitor behind `rpn`, for callers that want to drive it themselves,
+/// e.g. from inside another visitor.
+pub struct RPNVisitor;
+
+impl RPNVisitor {
+    fn sequence(&self, items: impl IntoIterator<Item = String>) -> String {
+        items
+            .into_iter()
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+impl Visitor for RPNVisitor {
+    type E = String;
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> String {
+        self.sequence([
+            self.visit_expression(&expr.left),
+            self.visit_expression(&expr.right),
+            expr.operator.token_type.to_string(),
+        ])
+    }
+
+    fn visit_grouping(&self, expr: &Grouping) -> String {
+        self.visit_expression(&expr.expression)
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> String {
+        match &expr.value {
+            LiteralValue::Float(float) => format_number(*float),
+            LiteralValue::LoxString(string) => format_string(string),
+            LiteralValue::Bool(bool) => bool.to_string(),
+            LiteralValue::None => "nil".to_string(),
+        }
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> String {
+        let operator = match expr.operator.token_type {
+            TokenType::Minus => "neg".to_string(),
+            TokenType::Bang => "not".to_string(),
+            ref operator => operator.to_string(),
+        };
+        self.sequence([self.visit_expression(&expr.right), operator])
+    }
+
+    fn visit_variable(&self, expr: &Variable) -> String {
+        expr.name.token_type.to_string()
+    }
+
+    fn visit_assign(&self, expr: &AssignExpr) -> String {
+        self.sequence([
+            self.visit_expression(&expr.value),
+            format!("{} =", expr.name.token_type),
+        ])
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> String {
+        let operator = match expr.operator.token_type {
+            TokenType::And => "and",
+            _ => "or",
+        };
+        self.sequence([
+            self.visit_expression(&expr.left),
+            self.visit_expression(&expr.right),
+            operator.to_string(),
+        ])
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> String {
+        self.sequence(
+            [self.visit_expression(&expr.callee)]
+                .into_iter()
+                .chain(
+                    expr.arguments
+                        .iter()
+                        .map(|argument| self.visit_expression(argument)),
+                )
+                .chain([format!("call/{}", expr.arguments.len())]),
+        )
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> String {
+        self.sequence([
+            self.visit_expression(&expr.object),
+            format!(".{}", expr.name.token_type),
+        ])
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> String {
+        self.sequence([
+            self.visit_expression(&expr.object),
+            self.visit_expression(&expr.value),
+            format!(".{} =", expr.name.token_type),
+        ])
+    }
+
+    fn visit_this(&self, _expr: &ThisExpr) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> String {
+        format!("super.{}", expr.method.token_type)
+    }
+}
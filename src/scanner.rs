@@ -1,7 +1,8 @@
+use crate::symbol::Symbol;
 use crate::token::{Token, TokenType};
+use std::fmt;
 use std::iter;
 use std::str;
-use std::{collections, fmt};
 
 pub type ScannerResult<T> = Result<T, ScannerError>;
 
@@ -48,42 +49,56 @@ impl ScannerError {
     }
 }
 
+/// Every reserved word, in the order `keyword` checks them.
+pub const KEYWORDS: [&str; 16] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
+/// The token a reserved word scans to, or `None` for an identifier.
+pub fn keyword(word: &str) -> Option<TokenType> {
+    use crate::token::TokenType::*;
+    let token_type = match word {
+        "and" => And,
+        "class" => Class,
+        "else" => Else,
+        "false" => False,
+        "for" => For,
+        "fun" => Fun,
+        "if" => If,
+        "nil" => Nil,
+        "or" => Or,
+        "print" => Print,
+        "return" => Return,
+        "super" => Super,
+        "this" => This,
+        "true" => True,
+        "var" => Var,
+        "while" => While,
+        _ => return None,
+    };
+    Some(token_type)
+}
+
 pub struct Scanner<'a> {
     source: iter::Peekable<str::Chars<'a>>,
     line: usize,
-    keywords: collections::HashMap<String, TokenType>,
+    comments: Vec<usize>,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(source: &str) -> Scanner {
+    pub fn new(source: &str) -> Scanner<'_> {
         Scanner {
             source: source.chars().peekable(),
             line: 1,
-            keywords: Self::get_keywords(),
+            comments: Vec::new(),
         }
     }
 
-    pub fn get_keywords() -> collections::HashMap<String, TokenType> {
-        let mut keywords = collections::HashMap::new();
-
-        use crate::token::TokenType::*;
-        keywords.insert("or".to_string(), Or);
-        keywords.insert("and".to_string(), And);
-        keywords.insert("if".to_string(), If);
-        keywords.insert("else".to_string(), Else);
-        keywords.insert("var".to_string(), Var);
-        keywords.insert("for".to_string(), For);
-        keywords.insert("while".to_string(), While);
-        keywords.insert("fun".to_string(), Fun);
-        keywords.insert("class".to_string(), Class);
-        keywords.insert("super".to_string(), Super);
-        keywords.insert("this".to_string(), This);
-        keywords.insert("return".to_string(), Return);
-        keywords.insert("true".to_string(), True);
-        keywords.insert("false".to_string(), False);
-        keywords.insert("nil".to_string(), Nil);
-
-        keywords
+    /// The lines of the comments scanned so far. Comments don't become
+    /// tokens, so this is the only trace of them.
+    pub fn comment_lines(&self) -> &[usize] {
+        &self.comments
     }
 
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<ScannerError>> {
@@ -117,10 +132,7 @@ impl<'a> Scanner<'a> {
     }
 
     pub fn simple_token(&self, token_type: TokenType) -> Token {
-        Token {
-            token_type,
-            line: self.line,
-        }
+        Token::new(token_type, self.line)
     }
 
     pub fn scan_operator(
@@ -139,16 +151,18 @@ impl<'a> Scanner<'a> {
 
     pub fn skip_comments(&mut self, ch: char) -> bool {
         if ch == '/' && self.source.peek() == Some(&'/') {
+            self.comments.push(self.line);
             while let Some(&c) = self.source.peek() {
                 self.source.next();
                 if c == '\n' {
                     self.line += 1;
-                    return true;
+                    break;
                 }
             }
+            return true;
         }
 
-        return false;
+        false
     }
 
     pub fn skip_whitespace(&mut self) {
@@ -172,14 +186,14 @@ impl<'a> Scanner<'a> {
         while let Some(&c) = self.source.peek() {
             if c == '"' {
                 self.source.next();
-                return Ok(self.simple_token(TokenType::LoxString(string)));
+                return Ok(self.simple_token(TokenType::LoxString(string.into())));
             } else if c == '\n' {
                 self.line += 1;
             }
             string.push(self.source.next().unwrap());
         }
 
-        return Err(ScannerError::UnterminatedString(line));
+        Err(ScannerError::UnterminatedString(line))
     }
 
     pub fn consume_alphanumerals(&mut self, string: &mut String) {
@@ -210,9 +224,7 @@ impl<'a> Scanner<'a> {
 
         match string.parse() {
             Ok(float) => Ok(self.simple_token(TokenType::Number(float))),
-            Err(_) => {
-                return Err(ScannerError::UnparseableDigit(string, self.line));
-            }
+            Err(_) => Err(ScannerError::UnparseableDigit(string, self.line)),
         }
     }
 
@@ -221,16 +233,16 @@ impl<'a> Scanner<'a> {
         string.push(ch);
 
         while let Some(&c) = self.source.peek() {
-            if !c.is_alphabetic() && c != '_' && !c.is_digit(10) {
+            if !c.is_alphabetic() && c != '_' && !c.is_ascii_digit() {
                 break;
             }
             string.push(c);
             self.source.next();
         }
 
-        match self.keywords.get(&string) {
-            Some(keyword_type) => Ok(self.simple_token(keyword_type.clone())),
-            None => Ok(self.simple_token(TokenType::Identifier(string))),
+        match keyword(&string) {
+            Some(keyword_type) => Ok(self.simple_token(keyword_type)),
+            None => Ok(self.simple_token(TokenType::Identifier(Symbol::intern(&string)))),
         }
     }
 
@@ -257,8 +269,8 @@ impl<'a> Scanner<'a> {
 
             '"' => return self.parse_string(),
 
-            ch @ _ => {
-                if ch.is_digit(10) {
+            ch => {
+                if ch.is_ascii_digit() {
                     return self.parse_number(ch);
                 } else if ch.is_alphabetic() || ch == '_' {
                     return self.parse_identifier(ch);
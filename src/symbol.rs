@@ -0,0 +1,116 @@
+//! Interned strings for identifiers.
+//!
+//! A `Symbol` is a handle to a string stored once for the life of the
+//! program, so copying one is free and comparing or hashing two only looks
+//! at their addresses:
+//!
+//! ```
+//! use rlox::symbol::Symbol;
+//!
+//! let a = Symbol::intern("counter");
+//! let b = Symbol::intern(&String::from("counter"));
+//! assert_eq!(a, b);
+//! assert_eq!(a.as_str(), "counter");
+//! ```
+//!
+//! Interned strings are never freed, so only names are interned: a program
+//! can't make new ones as it runs. String literals and the strings a program
+//! builds are reference counted, and freed once nothing holds them.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+fn interner() -> &'static Mutex<HashSet<&'static str>> {
+    static INTERNER: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+    /// The symbol for `string`, the same one every time.
+    pub fn intern(string: &str) -> Symbol {
+        // Nothing can panic while holding the lock, but if something did the
+        // set would still be intact
+        let mut strings = interner().lock().unwrap_or_else(|err| err.into_inner());
+        match strings.get(string) {
+            Some(interned) => Symbol(interned),
+            None => {
+                let interned: &'static str = Box::leak(string.into());
+                strings.insert(interned);
+                Symbol(interned)
+            }
+        }
+    }
+
+    /// The symbol for `string` if it's been interned, without interning it,
+    /// for looking up a name a host passes in that may not exist.
+    pub fn find(string: &str) -> Option<Symbol> {
+        let strings = interner().lock().unwrap_or_else(|err| err.into_inner());
+        strings.get(string).map(|interned| Symbol(interned))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(self.0, state);
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Self {
+        Symbol::intern(string)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&string))
+    }
+}
@@ -1,46 +1,97 @@
-use crate::{ast_printer::ASTStringVisitor, token::Token};
+use crate::{ast_printer::ASTStringVisitor, token::Token, visit::Visitor};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt;
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
-pub enum UnaryOperator {
-    Bang,
-    Minus,
+/// Where the resolver found a local variable: `depth` scopes out from where
+/// it's used, at `index` among the variables declared in that scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
 }
 
-#[derive(Debug, Clone)]
-pub enum BinaryOperator {
-    Minus,
-    Plus,
-    Slash,
-    Star,
-    Equal,
-    NotEqual,
-    Less,
-    LessEqual,
-    Greater,
-    GreaterEqual,
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BinaryExpr {
     pub left: Box<Expr>,
     pub operator: Token,
     pub right: Box<Expr>,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Grouping {
     pub expression: Box<Expr>,
+    /// The line of the opening parenthesis.
+    #[serde(default)]
+    pub line: usize,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiteralExpr {
+    pub value: LiteralValue,
+    #[serde(default)]
+    pub line: usize,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnaryExpr {
     pub operator: Token,
     pub right: Box<Expr>,
 }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variable {
+    pub name: Token,
+    /// Filled in by the resolver, and left empty for globals.
+    #[serde(skip)]
+    pub slot: Cell<Option<Slot>>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignExpr {
+    pub name: Token,
+    pub value: Box<Expr>,
+    #[serde(skip)]
+    pub slot: Cell<Option<Slot>>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogicalExpr {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallExpr {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThisExpr {
+    pub keyword: Token,
+    #[serde(skip)]
+    pub slot: Cell<Option<Slot>>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuperExpr {
+    pub keyword: Token,
+    pub method: Token,
+    #[serde(skip)]
+    pub slot: Cell<Option<Slot>>,
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
 pub enum LiteralValue {
-    Float(f64),
-    LoxString(String),
+    Float(#[serde(with = "crate::ast_json::number")] f64),
+    LoxString(Rc<str>),
     Bool(bool),
     None,
 }
@@ -65,9 +116,15 @@ impl From<f64> for LiteralValue {
     }
 }
 
+impl From<Rc<str>> for LiteralValue {
+    fn from(value: Rc<str>) -> Self {
+        LiteralValue::LoxString(value)
+    }
+}
+
 impl From<String> for LiteralValue {
     fn from(value: String) -> Self {
-        LiteralValue::LoxString(value)
+        LiteralValue::LoxString(value.into())
     }
 }
 
@@ -77,21 +134,118 @@ impl From<bool> for LiteralValue {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Expr {
     Binary(BinaryExpr),
     Grouping(Grouping),
-    Literal(LiteralValue),
+    Literal(LiteralExpr),
     Unary(UnaryExpr),
+    Variable(Variable),
+    Assign(AssignExpr),
+    Logical(LogicalExpr),
+    Call(CallExpr),
+    Get(GetExpr),
+    Set(SetExpr),
+    This(ThisExpr),
+    Super(SuperExpr),
 }
 
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let expr_str = ASTStringVisitor {
-            expressions: &[self.clone()],
-        };
+        let expr_str = ASTStringVisitor { statements: &[] }.visit_expression(self);
         write!(f, "{}", expr_str)?;
 
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpressionStmt {
+    pub expression: Expr,
+    /// The line the expression starts on.
+    #[serde(default)]
+    pub line: usize,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintStmt {
+    pub expression: Expr,
+    /// The line of the `print` keyword.
+    #[serde(default)]
+    pub line: usize,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VarStmt {
+    pub name: Token,
+    pub initializer: Option<Expr>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockStmt {
+    pub statements: Vec<Stmt>,
+    /// The line of the opening brace, or of the `for` a block was desugared from.
+    #[serde(default)]
+    pub line: usize,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IfStmt {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+    /// The line of the `if` keyword.
+    #[serde(default)]
+    pub line: usize,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhileStmt {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    /// The line of the `while` or `for` keyword.
+    #[serde(default)]
+    pub line: usize,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionStmt {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReturnStmt {
+    pub keyword: Token,
+    pub value: Option<Expr>,
+    /// Set by the resolver when `value` is a call made as the function's
+    /// last act, so the call can take over the returning function's frame.
+    #[serde(skip)]
+    pub tail_call: Cell<bool>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassStmt {
+    pub name: Token,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<FunctionStmt>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Stmt {
+    Expression(ExpressionStmt),
+    Print(PrintStmt),
+    Var(VarStmt),
+    Block(BlockStmt),
+    If(IfStmt),
+    While(WhileStmt),
+    Function(FunctionStmt),
+    Return(ReturnStmt),
+    Class(ClassStmt),
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stmt_str = ASTStringVisitor {
+            statements: std::slice::from_ref(self),
+        };
+        write!(f, "{}", stmt_str)?;
+
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
+use crate::symbol::Symbol;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::rc::Rc;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value")]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
@@ -26,9 +30,9 @@ pub enum TokenType {
     LessEqual,
 
     // Literals.
-    Identifier(String),
-    LoxString(String),
-    Number(f64),
+    Identifier(Symbol),
+    LoxString(Rc<str>),
+    Number(#[serde(with = "crate::ast_json::number")] f64),
     Nil,
 
     // Keywords.
@@ -51,8 +55,9 @@ pub enum TokenType {
     Eof,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Token {
+    #[serde(flatten)]
     pub token_type: TokenType,
     pub line: usize,
 }
@@ -61,6 +66,15 @@ impl Token {
     pub fn new(token_type: TokenType, line: usize) -> Token {
         Token { token_type, line }
     }
+
+    /// The name an identifier stands for. Other tokens are named by how they
+    /// print, which is how `this` and `super` get looked up.
+    pub fn symbol(&self) -> Symbol {
+        match &self.token_type {
+            TokenType::Identifier(symbol) => *symbol,
+            other => Symbol::intern(&other.to_string()),
+        }
+    }
 }
 
 impl fmt::Display for Token {
@@ -93,7 +107,7 @@ impl fmt::Display for TokenType {
             Equal => "=",
             EqualEqual => "==",
             Greater => ">",
-            GreaterEqual => "=>",
+            GreaterEqual => ">=",
             Less => "<",
             LessEqual => "<=",
 
@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::callable::{LoxClass, LoxFunction, LoxInstance, NativeFunction};
+use crate::native::UserData;
+use crate::syntax::LiteralValue;
+use crate::visit::VisitResult;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    LoxString(Rc<str>),
+    Bool(bool),
+    Nil,
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    UserData(UserData),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::LoxString(_) => "string",
+            Value::Bool(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::UserData(_) => "userdata",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(l), Value::Number(r)) => l == r,
+            // Strings the interpreter makes are interned, so only ones from
+            // the host can be equal without being the same
+            (Value::LoxString(l), Value::LoxString(r)) => Rc::ptr_eq(l, r) || l == r,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Native(l), Value::Native(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::UserData(l), Value::UserData(r)) => l.ptr_eq(r),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", number)?,
+            Value::LoxString(string) => write!(f, "{}", string)?,
+            Value::Bool(bool) => write!(f, "{}", bool)?,
+            Value::Nil => write!(f, "nil")?,
+            Value::Function(function) => write!(f, "<fn {}>", function.name())?,
+            Value::Native(native) => write!(f, "<native fn {}>", native.name)?,
+            Value::Class(class) => write!(f, "{}", class.name)?,
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name)?,
+            Value::UserData(data) => write!(f, "<userdata {}>", data.type_name())?,
+        }
+
+        Ok(())
+    }
+}
+
+impl VisitResult for Value {
+    fn output() -> Self {
+        Value::Nil
+    }
+}
+
+impl From<LiteralValue> for Value {
+    fn from(value: LiteralValue) -> Self {
+        match value {
+            LiteralValue::Float(float) => Value::Number(float),
+            LiteralValue::LoxString(string) => Value::LoxString(string),
+            LiteralValue::Bool(bool) => Value::Bool(bool),
+            LiteralValue::None => Value::Nil,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Value::Number(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Value::Bool(bool)
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Value::LoxString(string.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::LoxString(string.into())
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        option.map_or(Value::Nil, Into::into)
+    }
+}
+
+/// The tree-walker's string table. Every string a program makes goes through
+/// it, so equal strings share one allocation and compare by address. Unlike
+/// `Symbol`s they can be freed: once the table has doubled, it forgets the
+/// strings nothing else holds any more.
+pub(crate) struct Strings {
+    table: HashSet<Rc<str>>,
+    /// The string each literal evaluates to, so evaluating one doesn't have
+    /// to hash its contents. Literals are keyed by the address of their copy
+    /// in the syntax tree, which each entry holds on to so the address can't
+    /// be reused while it's here.
+    literals: HashMap<*const u8, (Rc<str>, Rc<str>)>,
+    sweep_at: usize,
+}
+
+const FIRST_SWEEP: usize = 1024;
+
+impl Strings {
+    pub(crate) fn new() -> Strings {
+        Strings {
+            table: HashSet::new(),
+            literals: HashMap::new(),
+            sweep_at: FIRST_SWEEP,
+        }
+    }
+
+    pub(crate) fn intern(&mut self, string: &str) -> Value {
+        if let Some(interned) = self.table.get(string) {
+            return Value::LoxString(interned.clone());
+        }
+
+        self.sweep_if_due();
+        let interned: Rc<str> = string.into();
+        self.table.insert(interned.clone());
+        Value::LoxString(interned)
+    }
+
+    /// Once there are twice as many strings and literals as the last sweep
+    /// left, forgets those nothing else holds.
+    fn sweep_if_due(&mut self) {
+        if self.table.len() + self.literals.len() >= self.sweep_at {
+            // Literals whose syntax tree is gone go first, as they hold on
+            // to strings in the table
+            self.literals
+                .retain(|_, (literal, _)| Rc::strong_count(literal) > 1);
+            self.table.retain(|interned| Rc::strong_count(interned) > 1);
+            self.sweep_at = FIRST_SWEEP.max((self.table.len() + self.literals.len()) * 2);
+        }
+    }
+
+    pub(crate) fn literal(&mut self, literal: &Rc<str>) -> Value {
+        let address = Rc::as_ptr(literal) as *const u8;
+        if let Some((_, interned)) = self.literals.get(&address) {
+            return Value::LoxString(interned.clone());
+        }
+
+        self.sweep_if_due();
+        let value = self.intern(literal);
+        if let Value::LoxString(interned) = &value {
+            self.literals
+                .insert(address, (literal.clone(), interned.clone()));
+        }
+        value
+    }
+}
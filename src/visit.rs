@@ -1,13 +1,612 @@
-use crate::syntax::Expr;
+//! Traversal infrastructure for the AST.
+//!
+//! `Visitor` and `MutVisitor` have one method per node. Every method has a
+//! default that calls the matching `walk_*` function, which recurses into the
+//! node's children, so a pass only needs to override the nodes it cares about.
+//! `Fold` does the same for passes that consume the tree and build a new one.
 
-pub trait MutVisitor {
-    type E;
+use crate::syntax::{
+    AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, Expr, ExpressionStmt, FunctionStmt,
+    GetExpr, Grouping, IfStmt, LiteralExpr, LogicalExpr, PrintStmt, ReturnStmt, SetExpr, Stmt,
+    SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+};
 
-    fn visit_expression(&mut self, expr: &Expr) -> Self::E;
+/// Values a visitor can return from a node it didn't override.
+///
+/// `output` is what a walked node evaluates to, and `is_break` lets a value
+/// such as an `Err` stop the walk early and bubble up unchanged.
+pub trait VisitResult {
+    fn output() -> Self;
+
+    fn is_break(&self) -> bool {
+        false
+    }
+}
+
+impl VisitResult for () {
+    fn output() -> Self {}
+}
+
+impl VisitResult for String {
+    fn output() -> Self {
+        String::new()
+    }
+}
+
+impl VisitResult for usize {
+    fn output() -> Self {
+        0
+    }
+}
+
+impl<T> VisitResult for Option<T> {
+    fn output() -> Self {
+        None
+    }
+}
+
+impl<T: VisitResult, E> VisitResult for Result<T, E> {
+    fn output() -> Self {
+        Ok(T::output())
+    }
+
+    fn is_break(&self) -> bool {
+        self.is_err()
+    }
+}
+
+macro_rules! try_visit {
+    ($e:expr) => {
+        let result = $e;
+        if result.is_break() {
+            return result;
+        }
+    };
+}
+
+macro_rules! make_visitor {
+    ($(#[$attr:meta])* $visitor:ident, $walk:ident, $($mutability:ident)?) => {
+        $(#[$attr])*
+        pub trait $visitor {
+            type E: VisitResult;
+
+            fn visit_statement(&$($mutability)? self, stmt: &Stmt) -> Self::E {
+                $walk::walk_statement(self, stmt)
+            }
+
+            fn visit_expression_stmt(&$($mutability)? self, stmt: &ExpressionStmt) -> Self::E {
+                $walk::walk_expression_stmt(self, stmt)
+            }
+
+            fn visit_print_stmt(&$($mutability)? self, stmt: &PrintStmt) -> Self::E {
+                $walk::walk_print_stmt(self, stmt)
+            }
+
+            fn visit_var_stmt(&$($mutability)? self, stmt: &VarStmt) -> Self::E {
+                $walk::walk_var_stmt(self, stmt)
+            }
+
+            fn visit_block_stmt(&$($mutability)? self, stmt: &BlockStmt) -> Self::E {
+                $walk::walk_block_stmt(self, stmt)
+            }
+
+            fn visit_if_stmt(&$($mutability)? self, stmt: &IfStmt) -> Self::E {
+                $walk::walk_if_stmt(self, stmt)
+            }
+
+            fn visit_while_stmt(&$($mutability)? self, stmt: &WhileStmt) -> Self::E {
+                $walk::walk_while_stmt(self, stmt)
+            }
+
+            fn visit_function_stmt(&$($mutability)? self, stmt: &FunctionStmt) -> Self::E {
+                $walk::walk_function_stmt(self, stmt)
+            }
+
+            fn visit_return_stmt(&$($mutability)? self, stmt: &ReturnStmt) -> Self::E {
+                $walk::walk_return_stmt(self, stmt)
+            }
+
+            fn visit_class_stmt(&$($mutability)? self, stmt: &ClassStmt) -> Self::E {
+                $walk::walk_class_stmt(self, stmt)
+            }
+
+            fn visit_expression(&$($mutability)? self, expr: &Expr) -> Self::E {
+                $walk::walk_expression(self, expr)
+            }
+
+            fn visit_binary(&$($mutability)? self, expr: &BinaryExpr) -> Self::E {
+                $walk::walk_binary(self, expr)
+            }
+
+            fn visit_grouping(&$($mutability)? self, expr: &Grouping) -> Self::E {
+                $walk::walk_grouping(self, expr)
+            }
+
+            fn visit_literal(&$($mutability)? self, _expr: &LiteralExpr) -> Self::E {
+                Self::E::output()
+            }
+
+            fn visit_unary(&$($mutability)? self, expr: &UnaryExpr) -> Self::E {
+                $walk::walk_unary(self, expr)
+            }
+
+            fn visit_variable(&$($mutability)? self, _expr: &Variable) -> Self::E {
+                Self::E::output()
+            }
+
+            fn visit_assign(&$($mutability)? self, expr: &AssignExpr) -> Self::E {
+                $walk::walk_assign(self, expr)
+            }
+
+            fn visit_logical(&$($mutability)? self, expr: &LogicalExpr) -> Self::E {
+                $walk::walk_logical(self, expr)
+            }
+
+            fn visit_call(&$($mutability)? self, expr: &CallExpr) -> Self::E {
+                $walk::walk_call(self, expr)
+            }
+
+            fn visit_get(&$($mutability)? self, expr: &GetExpr) -> Self::E {
+                $walk::walk_get(self, expr)
+            }
+
+            fn visit_set(&$($mutability)? self, expr: &SetExpr) -> Self::E {
+                $walk::walk_set(self, expr)
+            }
+
+            fn visit_this(&$($mutability)? self, _expr: &ThisExpr) -> Self::E {
+                Self::E::output()
+            }
+
+            fn visit_super(&$($mutability)? self, _expr: &SuperExpr) -> Self::E {
+                Self::E::output()
+            }
+        }
+
+        pub mod $walk {
+            use super::*;
+
+            pub fn walk_statement<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &Stmt) -> V::E {
+                match stmt {
+                    Stmt::Expression(stmt) => visitor.visit_expression_stmt(stmt),
+                    Stmt::Print(stmt) => visitor.visit_print_stmt(stmt),
+                    Stmt::Var(stmt) => visitor.visit_var_stmt(stmt),
+                    Stmt::Block(stmt) => visitor.visit_block_stmt(stmt),
+                    Stmt::If(stmt) => visitor.visit_if_stmt(stmt),
+                    Stmt::While(stmt) => visitor.visit_while_stmt(stmt),
+                    Stmt::Function(stmt) => visitor.visit_function_stmt(stmt),
+                    Stmt::Return(stmt) => visitor.visit_return_stmt(stmt),
+                    Stmt::Class(stmt) => visitor.visit_class_stmt(stmt),
+                }
+            }
+
+            pub fn walk_statements<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmts: &[Stmt]) -> V::E {
+                for stmt in stmts {
+                    try_visit!(visitor.visit_statement(stmt));
+                }
+                V::E::output()
+            }
+
+            pub fn walk_expression_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &ExpressionStmt) -> V::E {
+                try_visit!(visitor.visit_expression(&stmt.expression));
+                V::E::output()
+            }
+
+            pub fn walk_print_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &PrintStmt) -> V::E {
+                try_visit!(visitor.visit_expression(&stmt.expression));
+                V::E::output()
+            }
+
+            pub fn walk_var_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &VarStmt) -> V::E {
+                if let Some(initializer) = &stmt.initializer {
+                    try_visit!(visitor.visit_expression(initializer));
+                }
+                V::E::output()
+            }
+
+            pub fn walk_block_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &BlockStmt) -> V::E {
+                walk_statements(visitor, &stmt.statements)
+            }
+
+            pub fn walk_if_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &IfStmt) -> V::E {
+                try_visit!(visitor.visit_expression(&stmt.condition));
+                try_visit!(visitor.visit_statement(&stmt.then_branch));
+                if let Some(else_branch) = &stmt.else_branch {
+                    try_visit!(visitor.visit_statement(else_branch));
+                }
+                V::E::output()
+            }
+
+            pub fn walk_while_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &WhileStmt) -> V::E {
+                try_visit!(visitor.visit_expression(&stmt.condition));
+                try_visit!(visitor.visit_statement(&stmt.body));
+                V::E::output()
+            }
+
+            pub fn walk_function_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &FunctionStmt) -> V::E {
+                walk_statements(visitor, &stmt.body)
+            }
+
+            pub fn walk_return_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &ReturnStmt) -> V::E {
+                if let Some(value) = &stmt.value {
+                    try_visit!(visitor.visit_expression(value));
+                }
+                V::E::output()
+            }
+
+            pub fn walk_class_stmt<V: $visitor + ?Sized>(visitor: &$($mutability)? V, stmt: &ClassStmt) -> V::E {
+                if let Some(superclass) = &stmt.superclass {
+                    try_visit!(visitor.visit_variable(superclass));
+                }
+                for method in &stmt.methods {
+                    try_visit!(visitor.visit_function_stmt(method));
+                }
+                V::E::output()
+            }
+
+            pub fn walk_expression<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &Expr) -> V::E {
+                match expr {
+                    Expr::Binary(expr) => visitor.visit_binary(expr),
+                    Expr::Grouping(expr) => visitor.visit_grouping(expr),
+                    Expr::Literal(expr) => visitor.visit_literal(expr),
+                    Expr::Unary(expr) => visitor.visit_unary(expr),
+                    Expr::Variable(expr) => visitor.visit_variable(expr),
+                    Expr::Assign(expr) => visitor.visit_assign(expr),
+                    Expr::Logical(expr) => visitor.visit_logical(expr),
+                    Expr::Call(expr) => visitor.visit_call(expr),
+                    Expr::Get(expr) => visitor.visit_get(expr),
+                    Expr::Set(expr) => visitor.visit_set(expr),
+                    Expr::This(expr) => visitor.visit_this(expr),
+                    Expr::Super(expr) => visitor.visit_super(expr),
+                }
+            }
+
+            pub fn walk_binary<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &BinaryExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.left));
+                try_visit!(visitor.visit_expression(&expr.right));
+                V::E::output()
+            }
+
+            pub fn walk_grouping<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &Grouping) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.expression));
+                V::E::output()
+            }
+
+            pub fn walk_unary<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &UnaryExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.right));
+                V::E::output()
+            }
+
+            pub fn walk_assign<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &AssignExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.value));
+                V::E::output()
+            }
+
+            pub fn walk_logical<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &LogicalExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.left));
+                try_visit!(visitor.visit_expression(&expr.right));
+                V::E::output()
+            }
+
+            pub fn walk_call<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &CallExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.callee));
+                for argument in &expr.arguments {
+                    try_visit!(visitor.visit_expression(argument));
+                }
+                V::E::output()
+            }
+
+            pub fn walk_get<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &GetExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.object));
+                V::E::output()
+            }
+
+            pub fn walk_set<V: $visitor + ?Sized>(visitor: &$($mutability)? V, expr: &SetExpr) -> V::E {
+                try_visit!(visitor.visit_expression(&expr.object));
+                try_visit!(visitor.visit_expression(&expr.value));
+                V::E::output()
+            }
+        }
+    };
 }
 
-pub trait Visitor {
-    type E;
+make_visitor!(
+    /// Read-only traversal, for passes that don't need to update their own state.
+    Visitor,
+    walk,
+);
+
+make_visitor!(
+    /// Traversal for passes that accumulate state as they go, like the interpreter.
+    MutVisitor,
+    walk_mut,
+    mut
+);
+
+/// Consumes an AST and produces a rewritten one.
+///
+/// Node methods return the node type of their parent position (`Expr` or
+/// `Stmt`) rather than their own, so a rewrite can replace a node with one of
+/// a different kind, e.g. a `BinaryExpr` with a `Literal`.
+pub trait Fold {
+    fn fold_statements(&mut self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        fold::walk_statements(self, stmts)
+    }
+
+    fn fold_statement(&mut self, stmt: Stmt) -> Stmt {
+        fold::walk_statement(self, stmt)
+    }
+
+    fn fold_expression_stmt(&mut self, stmt: ExpressionStmt) -> Stmt {
+        fold::walk_expression_stmt(self, stmt)
+    }
+
+    fn fold_print_stmt(&mut self, stmt: PrintStmt) -> Stmt {
+        fold::walk_print_stmt(self, stmt)
+    }
+
+    fn fold_var_stmt(&mut self, stmt: VarStmt) -> Stmt {
+        fold::walk_var_stmt(self, stmt)
+    }
+
+    fn fold_block_stmt(&mut self, stmt: BlockStmt) -> Stmt {
+        fold::walk_block_stmt(self, stmt)
+    }
+
+    fn fold_if_stmt(&mut self, stmt: IfStmt) -> Stmt {
+        fold::walk_if_stmt(self, stmt)
+    }
+
+    fn fold_while_stmt(&mut self, stmt: WhileStmt) -> Stmt {
+        fold::walk_while_stmt(self, stmt)
+    }
+
+    fn fold_function_stmt(&mut self, stmt: FunctionStmt) -> FunctionStmt {
+        fold::walk_function_stmt(self, stmt)
+    }
+
+    fn fold_return_stmt(&mut self, stmt: ReturnStmt) -> Stmt {
+        fold::walk_return_stmt(self, stmt)
+    }
+
+    fn fold_class_stmt(&mut self, stmt: ClassStmt) -> Stmt {
+        fold::walk_class_stmt(self, stmt)
+    }
+
+    fn fold_expression(&mut self, expr: Expr) -> Expr {
+        fold::walk_expression(self, expr)
+    }
+
+    fn fold_binary(&mut self, expr: BinaryExpr) -> Expr {
+        fold::walk_binary(self, expr)
+    }
+
+    fn fold_grouping(&mut self, expr: Grouping) -> Expr {
+        fold::walk_grouping(self, expr)
+    }
+
+    fn fold_literal(&mut self, expr: LiteralExpr) -> Expr {
+        Expr::Literal(expr)
+    }
+
+    fn fold_unary(&mut self, expr: UnaryExpr) -> Expr {
+        fold::walk_unary(self, expr)
+    }
+
+    fn fold_variable(&mut self, expr: Variable) -> Expr {
+        Expr::Variable(expr)
+    }
+
+    fn fold_assign(&mut self, expr: AssignExpr) -> Expr {
+        fold::walk_assign(self, expr)
+    }
+
+    fn fold_logical(&mut self, expr: LogicalExpr) -> Expr {
+        fold::walk_logical(self, expr)
+    }
+
+    fn fold_call(&mut self, expr: CallExpr) -> Expr {
+        fold::walk_call(self, expr)
+    }
+
+    fn fold_get(&mut self, expr: GetExpr) -> Expr {
+        fold::walk_get(self, expr)
+    }
+
+    fn fold_set(&mut self, expr: SetExpr) -> Expr {
+        fold::walk_set(self, expr)
+    }
+
+    fn fold_this(&mut self, expr: ThisExpr) -> Expr {
+        Expr::This(expr)
+    }
+
+    fn fold_super(&mut self, expr: SuperExpr) -> Expr {
+        Expr::Super(expr)
+    }
+}
+
+pub mod fold {
+    use super::*;
+
+    pub fn walk_statements<F: Fold + ?Sized>(folder: &mut F, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        stmts
+            .into_iter()
+            .map(|stmt| folder.fold_statement(stmt))
+            .collect()
+    }
+
+    pub fn walk_statement<F: Fold + ?Sized>(folder: &mut F, stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Expression(stmt) => folder.fold_expression_stmt(stmt),
+            Stmt::Print(stmt) => folder.fold_print_stmt(stmt),
+            Stmt::Var(stmt) => folder.fold_var_stmt(stmt),
+            Stmt::Block(stmt) => folder.fold_block_stmt(stmt),
+            Stmt::If(stmt) => folder.fold_if_stmt(stmt),
+            Stmt::While(stmt) => folder.fold_while_stmt(stmt),
+            Stmt::Function(stmt) => Stmt::Function(folder.fold_function_stmt(stmt)),
+            Stmt::Return(stmt) => folder.fold_return_stmt(stmt),
+            Stmt::Class(stmt) => folder.fold_class_stmt(stmt),
+        }
+    }
+
+    pub fn walk_expression_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: ExpressionStmt) -> Stmt {
+        Stmt::Expression(ExpressionStmt {
+            expression: folder.fold_expression(stmt.expression),
+            line: stmt.line,
+        })
+    }
+
+    pub fn walk_print_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: PrintStmt) -> Stmt {
+        Stmt::Print(PrintStmt {
+            expression: folder.fold_expression(stmt.expression),
+            line: stmt.line,
+        })
+    }
+
+    pub fn walk_var_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: VarStmt) -> Stmt {
+        Stmt::Var(VarStmt {
+            name: stmt.name,
+            initializer: stmt.initializer.map(|expr| folder.fold_expression(expr)),
+        })
+    }
+
+    pub fn walk_block_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: BlockStmt) -> Stmt {
+        Stmt::Block(BlockStmt {
+            statements: folder.fold_statements(stmt.statements),
+            line: stmt.line,
+        })
+    }
+
+    pub fn walk_if_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: IfStmt) -> Stmt {
+        Stmt::If(IfStmt {
+            condition: folder.fold_expression(stmt.condition),
+            then_branch: Box::new(folder.fold_statement(*stmt.then_branch)),
+            else_branch: stmt
+                .else_branch
+                .map(|branch| Box::new(folder.fold_statement(*branch))),
+            line: stmt.line,
+        })
+    }
+
+    pub fn walk_while_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: WhileStmt) -> Stmt {
+        Stmt::While(WhileStmt {
+            condition: folder.fold_expression(stmt.condition),
+            body: Box::new(folder.fold_statement(*stmt.body)),
+            line: stmt.line,
+        })
+    }
+
+    pub fn walk_function_stmt<F: Fold + ?Sized>(
+        folder: &mut F,
+        stmt: FunctionStmt,
+    ) -> FunctionStmt {
+        FunctionStmt {
+            name: stmt.name,
+            params: stmt.params,
+            body: folder.fold_statements(stmt.body),
+        }
+    }
+
+    pub fn walk_return_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: ReturnStmt) -> Stmt {
+        Stmt::Return(ReturnStmt {
+            keyword: stmt.keyword,
+            value: stmt.value.map(|expr| folder.fold_expression(expr)),
+            tail_call: stmt.tail_call,
+        })
+    }
+
+    pub fn walk_class_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: ClassStmt) -> Stmt {
+        Stmt::Class(ClassStmt {
+            name: stmt.name,
+            superclass: stmt.superclass,
+            methods: stmt
+                .methods
+                .into_iter()
+                .map(|method| folder.fold_function_stmt(method))
+                .collect(),
+        })
+    }
+
+    pub fn walk_expression<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+        match expr {
+            Expr::Binary(expr) => folder.fold_binary(expr),
+            Expr::Grouping(expr) => folder.fold_grouping(expr),
+            Expr::Literal(expr) => folder.fold_literal(expr),
+            Expr::Unary(expr) => folder.fold_unary(expr),
+            Expr::Variable(expr) => folder.fold_variable(expr),
+            Expr::Assign(expr) => folder.fold_assign(expr),
+            Expr::Logical(expr) => folder.fold_logical(expr),
+            Expr::Call(expr) => folder.fold_call(expr),
+            Expr::Get(expr) => folder.fold_get(expr),
+            Expr::Set(expr) => folder.fold_set(expr),
+            Expr::This(expr) => folder.fold_this(expr),
+            Expr::Super(expr) => folder.fold_super(expr),
+        }
+    }
+
+    pub fn walk_binary<F: Fold + ?Sized>(folder: &mut F, expr: BinaryExpr) -> Expr {
+        Expr::Binary(BinaryExpr {
+            left: Box::new(folder.fold_expression(*expr.left)),
+            operator: expr.operator,
+            right: Box::new(folder.fold_expression(*expr.right)),
+        })
+    }
+
+    pub fn walk_grouping<F: Fold + ?Sized>(folder: &mut F, expr: Grouping) -> Expr {
+        Expr::Grouping(Grouping {
+            expression: Box::new(folder.fold_expression(*expr.expression)),
+            line: expr.line,
+        })
+    }
+
+    pub fn walk_unary<F: Fold + ?Sized>(folder: &mut F, expr: UnaryExpr) -> Expr {
+        Expr::Unary(UnaryExpr {
+            operator: expr.operator,
+            right: Box::new(folder.fold_expression(*expr.right)),
+        })
+    }
+
+    pub fn walk_assign<F: Fold + ?Sized>(folder: &mut F, expr: AssignExpr) -> Expr {
+        Expr::Assign(AssignExpr {
+            name: expr.name,
+            value: Box::new(folder.fold_expression(*expr.value)),
+            slot: expr.slot,
+        })
+    }
+
+    pub fn walk_logical<F: Fold + ?Sized>(folder: &mut F, expr: LogicalExpr) -> Expr {
+        Expr::Logical(LogicalExpr {
+            left: Box::new(folder.fold_expression(*expr.left)),
+            operator: expr.operator,
+            right: Box::new(folder.fold_expression(*expr.right)),
+        })
+    }
+
+    pub fn walk_call<F: Fold + ?Sized>(folder: &mut F, expr: CallExpr) -> Expr {
+        Expr::Call(CallExpr {
+            callee: Box::new(folder.fold_expression(*expr.callee)),
+            paren: expr.paren,
+            arguments: expr
+                .arguments
+                .into_iter()
+                .map(|argument| folder.fold_expression(argument))
+                .collect(),
+        })
+    }
+
+    pub fn walk_get<F: Fold + ?Sized>(folder: &mut F, expr: GetExpr) -> Expr {
+        Expr::Get(GetExpr {
+            object: Box::new(folder.fold_expression(*expr.object)),
+            name: expr.name,
+        })
+    }
 
-    fn visit_expression(&self, expr: &Expr) -> Self::E;
+    pub fn walk_set<F: Fold + ?Sized>(folder: &mut F, expr: SetExpr) -> Expr {
+        Expr::Set(SetExpr {
+            object: Box::new(folder.fold_expression(*expr.object)),
+            name: expr.name,
+            value: Box::new(folder.fold_expression(*expr.value)),
+        })
+    }
 }
@@ -0,0 +1,579 @@
+//! Compiled scripts saved to disk, so running one again can skip scanning,
+//! parsing and compiling.
+//!
+//! A `.loxc` file starts with a header, followed by the script's function
+//! with everything nested in it:
+//!
+//! ```text
+//! magic     "LOXC"
+//! version   u16
+//! source    u64   hash of the source the file was compiled from
+//! length    u32   bytes after the header
+//! checksum  u64   hash of those bytes
+//! ```
+//!
+//! Integers are little-endian. Loading checks the header and checksum, then
+//! every instruction, so a damaged file is reported as an error rather than
+//! handed to the VM.
+
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::chunk::{Chunk, Constant, Function, OpCode};
+use crate::symbol::Symbol;
+
+pub const MAGIC: [u8; 4] = *b"LOXC";
+
+/// Bumped whenever the layout or the instruction set changes, so files from
+/// an older `rlox` are recompiled instead of misread.
+pub const VERSION: u16 = 4;
+
+const HEADER_LEN: usize = 4 + 2 + 8 + 4 + 8;
+
+/// How deep functions can be nested in a file, which bounds the recursion
+/// when loading one.
+const MAX_NESTING: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheError {
+    NotBytecode,
+    UnsupportedVersion(u16),
+    /// The file was compiled from a different version of the source.
+    Stale,
+    Truncated,
+    ChecksumMismatch,
+    Invalid(String),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::NotBytecode => write!(f, "Cache Error: Not a compiled Lox file"),
+            CacheError::UnsupportedVersion(version) => write!(
+                f,
+                "Cache Error: Unsupported bytecode version {}, expected {}",
+                version, VERSION
+            ),
+            CacheError::Stale => write!(f, "Cache Error: Compiled from a different source"),
+            CacheError::Truncated => write!(f, "Cache Error: File is truncated"),
+            CacheError::ChecksumMismatch => {
+                write!(f, "Cache Error: Checksum mismatch, the file is corrupted")
+            }
+            CacheError::Invalid(reason) => write!(f, "Cache Error: Invalid bytecode: {}", reason),
+        }
+    }
+}
+
+impl error::Error for CacheError {}
+
+/// Where the compiled form of the script at `path` is kept: next to it, with
+/// a `.loxc` extension.
+pub fn cache_path(path: &Path) -> PathBuf {
+    path.with_extension("loxc")
+}
+
+/// 64-bit FNV-1a. Unlike `std`'s hashers it's the same on every platform
+/// and release, which a hash written to disk needs.
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Serializes a compiled script, recording the source it came from.
+pub fn encode(function: &Function, source: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_function(function, &mut body);
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&hash(source.as_bytes()).to_le_bytes());
+    bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&hash(&body).to_le_bytes());
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+/// Loads a compiled script. With `source`, the file also has to have been
+/// compiled from exactly that source.
+pub fn decode(bytes: &[u8], source: Option<&str>) -> Result<Rc<Function>, CacheError> {
+    let mut reader = Reader { bytes, offset: 0 };
+
+    if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+        return Err(CacheError::NotBytecode);
+    }
+    reader.offset = MAGIC.len();
+
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(CacheError::UnsupportedVersion(version));
+    }
+    let source_hash = reader.u64()?;
+    let length = reader.u32()?;
+    let checksum = reader.u64()?;
+
+    if let Some(source) = source {
+        if hash(source.as_bytes()) != source_hash {
+            return Err(CacheError::Stale);
+        }
+    }
+    match (bytes.len() - HEADER_LEN).cmp(&length) {
+        Ordering::Less => return Err(CacheError::Truncated),
+        Ordering::Greater => return Err(invalid("trailing bytes after the script")),
+        Ordering::Equal => {}
+    }
+    if hash(&bytes[HEADER_LEN..]) != checksum {
+        return Err(CacheError::ChecksumMismatch);
+    }
+
+    let function = reader.function(0)?;
+    if reader.offset != bytes.len() {
+        return Err(invalid("trailing bytes after the script"));
+    }
+    verify(&function)?;
+
+    Ok(Rc::new(function))
+}
+
+fn invalid(reason: impl Into<String>) -> CacheError {
+    CacheError::Invalid(reason.into())
+}
+
+const NUMBER: u8 = 0;
+const STRING: u8 = 1;
+const FUNCTION: u8 = 2;
+const NAME: u8 = 3;
+
+fn write_u32(value: usize, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_str(string: &str, out: &mut Vec<u8>) {
+    write_u32(string.len(), out);
+    out.extend_from_slice(string.as_bytes());
+}
+
+fn write_function(function: &Function, out: &mut Vec<u8>) {
+    write_str(&function.name, out);
+    write_u32(function.arity, out);
+    write_u32(function.upvalue_count, out);
+    write_u32(function.inline_caches, out);
+
+    let chunk = &function.chunk;
+    write_u32(chunk.code.len(), out);
+    out.extend_from_slice(&chunk.code);
+
+    // Lines as runs, since most instructions share the line of the one
+    // before them
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for line in &chunk.lines {
+        match runs.last_mut() {
+            Some((last, count)) if last == line => *count += 1,
+            _ => runs.push((*line, 1)),
+        }
+    }
+    write_u32(runs.len(), out);
+    for (line, count) in runs {
+        write_u32(line, out);
+        write_u32(count, out);
+    }
+
+    write_u32(chunk.constants.len(), out);
+    for constant in &chunk.constants {
+        match constant {
+            Constant::Number(number) => {
+                out.push(NUMBER);
+                out.extend_from_slice(&number.to_bits().to_le_bytes());
+            }
+            Constant::String(string) => {
+                out.push(STRING);
+                write_str(string, out);
+            }
+            Constant::Name(name) => {
+                out.push(NAME);
+                write_str(name, out);
+            }
+            Constant::Function(function) => {
+                out.push(FUNCTION);
+                write_function(function, out);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], CacheError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(CacheError::Truncated)?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, CacheError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, CacheError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<usize, CacheError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64, CacheError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, CacheError> {
+        let len = self.u32()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn function(&mut self, depth: usize) -> Result<Function, CacheError> {
+        if depth > MAX_NESTING {
+            return Err(invalid("functions nested too deeply"));
+        }
+
+        let name = self.string()?;
+        let arity = self.u32()?;
+        let upvalue_count = self.u32()?;
+        let inline_caches = self.u32()?;
+
+        let len = self.u32()?;
+        let code = self.take(len)?.to_vec();
+
+        let mut lines = Vec::with_capacity(code.len());
+        let runs = self.u32()?;
+        for _ in 0..runs {
+            let line = self.u32()?;
+            let count = self.u32()?;
+            if count > code.len() - lines.len() {
+                return Err(invalid("more lines than instructions"));
+            }
+            lines.extend(std::iter::repeat_n(line, count));
+        }
+        if lines.len() != code.len() {
+            return Err(invalid("fewer lines than instructions"));
+        }
+
+        let count = self.u32()?;
+        // Every constant takes at least a byte, which keeps a bogus count
+        // from reserving more than the file could hold
+        let mut constants = Vec::with_capacity(count.min(self.bytes.len() - self.offset));
+        for _ in 0..count {
+            constants.push(match self.u8()? {
+                NUMBER => Constant::Number(f64::from_bits(self.u64()?)),
+                STRING => Constant::String(self.string()?.into()),
+                NAME => Constant::Name(Symbol::intern(&self.string()?)),
+                FUNCTION => Constant::Function(Rc::new(self.function(depth + 1)?)),
+                tag => return Err(invalid(format!("unknown constant tag {}", tag))),
+            });
+        }
+
+        Ok(Function {
+            name,
+            arity,
+            upvalue_count,
+            inline_caches,
+            chunk: Chunk {
+                code,
+                constants,
+                lines,
+            },
+        })
+    }
+}
+
+/// Checks that every instruction is one the VM knows, that its operands are
+/// all there and refer to constants of the right kind, that jumps land on
+/// instructions, and that the code can't run off its end. Then
+/// `verify_stack` checks what the instructions do to the stack.
+fn verify(function: &Function) -> Result<(), CacheError> {
+    let chunk = &function.chunk;
+    let name = &function.name;
+    if function.arity > u8::MAX as usize {
+        return Err(invalid(format!("{} has too many parameters", name)));
+    }
+    if function.upvalue_count > 256 {
+        return Err(invalid(format!("{} has too many upvalues", name)));
+    }
+    if function.inline_caches > u16::MAX as usize + 1 {
+        return Err(invalid(format!("{} has too many inline caches", name)));
+    }
+
+    let operand = |offset: usize, len: usize| {
+        if offset + len < chunk.code.len() {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "missing operand at {} in {}",
+                offset, name
+            )))
+        }
+    };
+    let constant = |offset: usize| -> Result<&Constant, CacheError> {
+        operand(offset, 2)?;
+        chunk
+            .constants
+            .get(chunk.read_u16(offset + 1) as usize)
+            .ok_or_else(|| invalid(format!("missing constant at {} in {}", offset, name)))
+    };
+    let name_at = |offset: usize| match constant(offset)? {
+        Constant::Name(_) => Ok(()),
+        _ => Err(invalid(format!(
+            "expected a name at {} in {}",
+            offset, name
+        ))),
+    };
+    // `at` is where the cache index is, the instruction at `offset`
+    let inline_cache = |offset: usize, at: usize| {
+        operand(at - 1, 2)?;
+        match (chunk.read_u16(at) as usize) < function.inline_caches {
+            true => Ok(()),
+            false => Err(invalid(format!(
+                "missing inline cache at {} in {}",
+                offset, name
+            ))),
+        }
+    };
+
+    // Where the instruction at each offset ends, and 0 inside instructions
+    let mut ends = vec![0; chunk.code.len()];
+    let mut jumps = Vec::new();
+    let mut last = None;
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let start = offset;
+        let op = OpCode::try_from(chunk.code[offset]).map_err(|byte| {
+            invalid(format!("unknown opcode {} at {} in {}", byte, offset, name))
+        })?;
+        last = Some(op);
+
+        offset = match op {
+            OpCode::Constant => match constant(offset)? {
+                Constant::Function(_) => {
+                    return Err(invalid(format!(
+                        "function used as a value at {} in {}",
+                        offset, name
+                    )))
+                }
+                _ => offset + 3,
+            },
+            OpCode::GetProperty | OpCode::SetProperty => {
+                name_at(offset)?;
+                inline_cache(offset, offset + 3)?;
+                offset + 5
+            }
+            OpCode::Invoke => {
+                name_at(offset)?;
+                inline_cache(offset, offset + 4)?;
+                offset + 6
+            }
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                name_at(offset)?;
+                offset + 3
+            }
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::Call | OpCode::TailCall => {
+                operand(offset, 1)?;
+                offset + 2
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                operand(offset, 1)?;
+                if chunk.code[offset + 1] as usize >= function.upvalue_count {
+                    return Err(invalid(format!(
+                        "missing upvalue at {} in {}",
+                        offset, name
+                    )));
+                }
+                offset + 2
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                operand(offset, 2)?;
+                let jump = chunk.read_u16(offset + 1) as usize;
+                let target = match op {
+                    OpCode::Loop => (offset + 3).checked_sub(jump),
+                    _ => Some(offset + 3 + jump),
+                };
+                jumps.push((offset, target));
+                offset + 3
+            }
+            OpCode::Closure => {
+                let nested = match constant(offset)? {
+                    Constant::Function(nested) => nested,
+                    _ => {
+                        return Err(invalid(format!(
+                            "expected a function at {} in {}",
+                            offset, name
+                        )))
+                    }
+                };
+                operand(offset + 2, nested.upvalue_count * 2)?;
+                for i in 0..nested.upvalue_count {
+                    let is_local = chunk.code[offset + 3 + i * 2];
+                    let index = chunk.code[offset + 4 + i * 2] as usize;
+                    if is_local > 1 || (is_local == 0 && index >= function.upvalue_count) {
+                        return Err(invalid(format!("bad capture at {} in {}", offset, name)));
+                    }
+                }
+                offset + 3 + nested.upvalue_count * 2
+            }
+            _ => offset + 1,
+        };
+        ends[start] = offset;
+    }
+
+    if last != Some(OpCode::Return) {
+        return Err(invalid(format!("{} doesn't end with a return", name)));
+    }
+    for (offset, target) in jumps {
+        // Jumping to the very end would run off it
+        if !target.is_some_and(|target| target < ends.len() && ends[target] != 0) {
+            return Err(invalid(format!(
+                "jump at {} in {} lands outside the code",
+                offset, name
+            )));
+        }
+    }
+
+    verify_stack(function, &ends)?;
+
+    for constant in &chunk.constants {
+        if let Constant::Function(nested) = constant {
+            verify(nested)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// How many values the instruction at `offset` takes off the stack, and how
+/// many it leaves in their place.
+fn stack_effect(chunk: &Chunk, offset: usize, op: OpCode) -> (usize, usize) {
+    match op {
+        OpCode::Constant
+        | OpCode::Nil
+        | OpCode::True
+        | OpCode::False
+        | OpCode::GetLocal
+        | OpCode::GetGlobal
+        | OpCode::GetUpvalue
+        | OpCode::Closure
+        | OpCode::Class => (0, 1),
+        OpCode::Pop
+        | OpCode::DefineGlobal
+        | OpCode::Print
+        | OpCode::CloseUpvalue
+        | OpCode::Return => (1, 0),
+        OpCode::SetLocal
+        | OpCode::SetGlobal
+        | OpCode::SetUpvalue
+        | OpCode::GetProperty
+        | OpCode::Not
+        | OpCode::Negate
+        | OpCode::JumpIfFalse => (1, 1),
+        OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Equal
+        | OpCode::NotEqual
+        | OpCode::Greater
+        | OpCode::GreaterEqual
+        | OpCode::Less
+        | OpCode::LessEqual
+        | OpCode::Add
+        | OpCode::Subtract
+        | OpCode::Multiply
+        | OpCode::Divide
+        | OpCode::Inherit
+        | OpCode::Method => (2, 1),
+        OpCode::Jump | OpCode::Loop => (0, 0),
+        // The callee and its arguments, replaced by what it returns
+        OpCode::Call | OpCode::TailCall => (chunk.code[offset + 1] as usize + 1, 1),
+        OpCode::Invoke => (chunk.code[offset + 3] as usize + 1, 1),
+    }
+}
+
+/// Follows every path through the code, tracking how many values the
+/// function has on the stack, counting the callee and arguments it starts
+/// with. Checks that no instruction takes more values than there are, that
+/// local slots and captured locals are among them, and that paths meeting
+/// at an instruction agree on how many there are.
+fn verify_stack(function: &Function, ends: &[usize]) -> Result<(), CacheError> {
+    let chunk = &function.chunk;
+    let name = &function.name;
+    let mut depths: Vec<Option<usize>> = vec![None; chunk.code.len()];
+    let mut pending = vec![(0, function.arity + 1)];
+
+    while let Some((offset, depth)) = pending.pop() {
+        match depths[offset] {
+            Some(seen) if seen == depth => continue,
+            Some(_) => {
+                return Err(invalid(format!(
+                    "paths disagree on the stack at {} in {}",
+                    offset, name
+                )))
+            }
+            None => depths[offset] = Some(depth),
+        }
+
+        // Only ever called on an instruction `verify` has decoded
+        let op = OpCode::try_from(chunk.code[offset]).unwrap();
+        let (takes, leaves) = stack_effect(chunk, offset, op);
+        if takes > depth {
+            return Err(invalid(format!(
+                "stack underflow at {} in {}",
+                offset, name
+            )));
+        }
+
+        let local = |slot: u8| match (slot as usize) < depth {
+            true => Ok(()),
+            false => Err(invalid(format!("missing local at {} in {}", offset, name))),
+        };
+        match op {
+            OpCode::GetLocal | OpCode::SetLocal => local(chunk.code[offset + 1])?,
+            OpCode::Closure => {
+                for capture in chunk.code[offset + 3..ends[offset]].chunks(2) {
+                    if capture[0] == 1 {
+                        local(capture[1])?;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let depth = depth - takes + leaves;
+        let jump = || chunk.read_u16(offset + 1) as usize;
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((ends[offset] + jump(), depth)),
+            OpCode::Loop => pending.push((ends[offset] - jump(), depth)),
+            OpCode::JumpIfFalse => {
+                pending.push((ends[offset] + jump(), depth));
+                pending.push((ends[offset], depth));
+            }
+            _ => pending.push((ends[offset], depth)),
+        }
+    }
+
+    Ok(())
+}
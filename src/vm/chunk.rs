@@ -0,0 +1,208 @@
+//! Compiled bytecode: instructions, the constants they refer to, and the
+//! source line of every byte.
+
+use std::rc::Rc;
+
+use crate::symbol::Symbol;
+
+/// One instruction. Operands follow the opcode byte: constant indexes, jump
+/// offsets and inline cache indexes take two bytes, big-endian, and
+/// everything else one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    /// `constant: u16` — pushes a constant.
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// `slot: u8`
+    GetLocal,
+    /// `slot: u8`
+    SetLocal,
+    /// `name: u16`
+    GetGlobal,
+    /// `name: u16`
+    DefineGlobal,
+    /// `name: u16`
+    SetGlobal,
+    /// `index: u8`
+    GetUpvalue,
+    /// `index: u8`
+    SetUpvalue,
+    /// `name: u16, cache: u16`
+    GetProperty,
+    /// `name: u16, cache: u16`
+    SetProperty,
+    /// `name: u16` — pops the superclass and binds its method to `this`.
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    /// `offset: u16` — jumps forward.
+    Jump,
+    /// `offset: u16` — jumps forward if the top of the stack is falsey,
+    /// leaving it there.
+    JumpIfFalse,
+    /// `offset: u16` — jumps backward.
+    Loop,
+    /// `arguments: u8`
+    Call,
+    /// `function: u16`, then an `is_local: u8, index: u8` pair for each of
+    /// the function's upvalues.
+    Closure,
+    CloseUpvalue,
+    Return,
+    /// `name: u16`
+    Class,
+    /// Copies the superclass's methods into the class on top of it.
+    Inherit,
+    /// `name: u16` — adds the closure on top of the stack to the class
+    /// under it.
+    Method,
+    /// `name: u16, arguments: u8, cache: u16` — calls the method `name` of
+    /// the receiver under the arguments, without binding it first.
+    Invoke,
+    /// `arguments: u8` — like `Call`, but a Lox callee replaces the
+    /// current frame instead of going on top of it.
+    TailCall,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 40] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+        OpCode::Invoke,
+        OpCode::TailCall,
+    ];
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OpCode::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+/// A value known at compile time.
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Number(f64),
+    String(Rc<str>),
+    /// The name of a variable, property or method. Only these are interned,
+    /// since a program can't make new ones while it runs.
+    Name(Symbol),
+    Function(Rc<Function>),
+}
+
+/// A compiled function, or the top-level script.
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    /// How many inline caches its property instructions use. Each has its
+    /// own, numbered by its `cache` operand.
+    pub inline_caches: usize,
+    pub chunk: Chunk,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    /// The source line of each byte in `code`.
+    pub lines: Vec<usize>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: usize) {
+        self.write(op as u8, line);
+    }
+
+    pub fn write_u16(&mut self, value: u16, line: usize) {
+        let [high, low] = value.to_be_bytes();
+        self.write(high, line);
+        self.write(low, line);
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// Adds a constant, reusing an equal number or string already in the
+    /// pool, and returns its index.
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        let existing = self
+            .constants
+            .iter()
+            .position(|other| match (other, &constant) {
+                (Constant::Number(l), Constant::Number(r)) => l.to_bits() == r.to_bits(),
+                (Constant::String(l), Constant::String(r)) => l == r,
+                (Constant::Name(l), Constant::Name(r)) => l == r,
+                _ => false,
+            });
+
+        existing.unwrap_or_else(|| {
+            self.constants.push(constant);
+            self.constants.len() - 1
+        })
+    }
+}
@@ -0,0 +1,706 @@
+//! Compiles a resolved syntax tree to bytecode.
+//!
+//! Locals live in stack slots worked out here, with variables captured by
+//! closures reached through upvalues, so the VM never looks a local up by
+//! name. Only globals are.
+
+use std::fmt;
+use std::rc::Rc;
+
+use super::chunk::{Chunk, Constant, Function, OpCode};
+use crate::symbol::Symbol;
+use crate::syntax::{
+    AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, Expr, ExpressionStmt, FunctionStmt,
+    GetExpr, IfStmt, LiteralExpr, LiteralValue, LogicalExpr, PrintStmt, ReturnStmt, SetExpr, Stmt,
+    SuperExpr, ThisExpr, UnaryExpr, VarStmt, Variable, WhileStmt,
+};
+use crate::token::{Token, TokenType};
+use crate::visit::{walk_mut, MutVisitor};
+
+/// Limits of the bytecode format that a program ran into.
+#[derive(Debug)]
+pub enum CompileError {
+    TooManyConstants(usize),
+    TooManyLocals(usize),
+    TooManyUpvalues(usize),
+    JumpTooLarge(usize),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::TooManyConstants(line) => {
+                write!(
+                    f,
+                    "Compile Error: Too many constants in one function at line {}",
+                    line
+                )?;
+            }
+            CompileError::TooManyLocals(line) => {
+                write!(
+                    f,
+                    "Compile Error: Too many local variables in function at line {}",
+                    line
+                )?;
+            }
+            CompileError::TooManyUpvalues(line) => {
+                write!(
+                    f,
+                    "Compile Error: Too many closure variables in function at line {}",
+                    line
+                )?;
+            }
+            CompileError::JumpTooLarge(line) => {
+                write!(
+                    f,
+                    "Compile Error: Too much code to jump over at line {}",
+                    line
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CompileError {
+    pub fn line(&self) -> usize {
+        match *self {
+            CompileError::TooManyConstants(line) => line,
+            CompileError::TooManyLocals(line) => line,
+            CompileError::TooManyUpvalues(line) => line,
+            CompileError::JumpTooLarge(line) => line,
+        }
+    }
+}
+
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+const MAX_UPVALUES: usize = u8::MAX as usize + 1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: Symbol,
+    depth: usize,
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Upvalue {
+    index: u8,
+    is_local: bool,
+}
+
+/// The function being compiled, one per level of nesting.
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> FunctionState {
+        // Slot 0 holds the function being called, or `this` in a method
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+
+        FunctionState {
+            function: Function {
+                name: name.to_string(),
+                arity: 0,
+                upvalue_count: 0,
+                inline_caches: 0,
+                chunk: Chunk::new(),
+            },
+            kind,
+            locals: vec![Local {
+                name: Symbol::intern(receiver),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        }
+    }
+
+    fn resolve_local(&self, name: Symbol) -> Option<u8> {
+        self.locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+}
+
+/// Where a variable lives, with the operand to reach it.
+enum Target {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    /// The line of the last token seen, for nodes that don't have one.
+    line: usize,
+    errors: Vec<CompileError>,
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            states: vec![FunctionState::new("script", FunctionKind::Script)],
+            line: 1,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Compiles a program into the function that runs it.
+    pub fn compile(mut self, statements: &[Stmt]) -> Result<Rc<Function>, Vec<CompileError>> {
+        walk_mut::walk_statements(&mut self, statements);
+        self.emit_return();
+
+        match self.errors.is_empty() {
+            true => Ok(Rc::new(self.states.pop().unwrap().function)),
+            false => Err(self.errors),
+        }
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state().function.chunk
+    }
+
+    fn emit(&mut self, byte: u8) {
+        let line = self.line;
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit(op as u8);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        let line = self.line;
+        self.chunk().write_u16(value, line);
+    }
+
+    fn emit_op_u16(&mut self, op: OpCode, operand: u16) {
+        self.emit_op(op);
+        self.emit_u16(operand);
+    }
+
+    fn emit_return(&mut self) {
+        match self.state().kind {
+            FunctionKind::Initializer => {
+                self.emit_op(OpCode::GetLocal);
+                self.emit(0);
+            }
+            _ => self.emit_op(OpCode::Nil),
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn make_constant(&mut self, constant: Constant) -> u16 {
+        let index = self.chunk().add_constant(constant);
+        match u16::try_from(index) {
+            Ok(index) => index,
+            Err(_) => {
+                self.errors.push(CompileError::TooManyConstants(self.line));
+                0
+            }
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> u16 {
+        self.make_constant(Constant::Name(name.symbol()))
+    }
+
+    /// Gives a property instruction an inline cache of its own. If a
+    /// function runs out, the last one is shared. Entries remember the name
+    /// they were filled for, so sharing is slower but still right.
+    fn inline_cache(&mut self) -> u16 {
+        let function = &mut self.state().function;
+        let index = function.inline_caches.min(u16::MAX as usize);
+        function.inline_caches = index + 1;
+        index as u16
+    }
+
+    /// Emits a jump with a placeholder offset, returning where the offset
+    /// goes so `patch_jump` can fill it in.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op_u16(op, u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        let distance = self.chunk().code.len() - offset - 2;
+        let distance = match u16::try_from(distance) {
+            Ok(distance) => distance,
+            Err(_) => {
+                self.errors.push(CompileError::JumpTooLarge(self.line));
+                0
+            }
+        };
+
+        let [high, low] = distance.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+    }
+
+    fn emit_loop(&mut self, start: usize) {
+        self.emit_op(OpCode::Loop);
+        let distance = self.chunk().code.len() - start + 2;
+        match u16::try_from(distance) {
+            Ok(distance) => self.emit_u16(distance),
+            Err(_) => {
+                self.errors.push(CompileError::JumpTooLarge(self.line));
+                self.emit_u16(0);
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.state();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+
+        while let Some(local) = self.state().locals.pop_if(|local| local.depth > depth) {
+            match local.is_captured {
+                true => self.emit_op(OpCode::CloseUpvalue),
+                false => self.emit_op(OpCode::Pop),
+            }
+        }
+    }
+
+    fn add_local(&mut self, name: Symbol) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.errors.push(CompileError::TooManyLocals(self.line));
+            return;
+        }
+
+        let depth = self.state().scope_depth;
+        self.state().locals.push(Local {
+            name,
+            depth,
+            is_captured: false,
+        });
+    }
+
+    /// Makes room for a local. Globals don't need declaring.
+    fn declare_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.add_local(name.symbol());
+        }
+    }
+
+    /// Binds the value on top of the stack to a variable just declared. A
+    /// local is already in its slot.
+    fn define_variable(&mut self, name: &Token) {
+        if self.state().scope_depth == 0 {
+            let constant = self.identifier_constant(name);
+            self.emit_op_u16(OpCode::DefineGlobal, constant);
+        }
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: Symbol) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+
+        let enclosing = &mut self.states[state - 1];
+        if let Some(slot) = enclosing.resolve_local(name) {
+            enclosing.locals[slot as usize].is_captured = true;
+            return self.add_upvalue(state, slot, true);
+        }
+
+        let index = self.resolve_upvalue(state - 1, name)?;
+        self.add_upvalue(state, index, false)
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool) -> Option<u8> {
+        let upvalue = Upvalue { index, is_local };
+        let upvalues = &mut self.states[state].upvalues;
+        if let Some(existing) = upvalues.iter().position(|other| *other == upvalue) {
+            return Some(existing as u8);
+        }
+
+        if upvalues.len() == MAX_UPVALUES {
+            self.errors.push(CompileError::TooManyUpvalues(self.line));
+            return Some(0);
+        }
+        upvalues.push(upvalue);
+        Some((upvalues.len() - 1) as u8)
+    }
+
+    fn resolve(&mut self, name: &Token) -> Target {
+        let key = name.symbol();
+        if let Some(slot) = self.state().resolve_local(key) {
+            return Target::Local(slot);
+        }
+        if let Some(index) = self.resolve_upvalue(self.states.len() - 1, key) {
+            return Target::Upvalue(index);
+        }
+        Target::Global(self.identifier_constant(name))
+    }
+
+    fn get_variable(&mut self, name: &Token) {
+        self.line = name.line;
+        match self.resolve(name) {
+            Target::Local(slot) => {
+                self.emit_op(OpCode::GetLocal);
+                self.emit(slot);
+            }
+            Target::Upvalue(index) => {
+                self.emit_op(OpCode::GetUpvalue);
+                self.emit(index);
+            }
+            Target::Global(constant) => self.emit_op_u16(OpCode::GetGlobal, constant),
+        }
+    }
+
+    fn set_variable(&mut self, name: &Token) {
+        self.line = name.line;
+        match self.resolve(name) {
+            Target::Local(slot) => {
+                self.emit_op(OpCode::SetLocal);
+                self.emit(slot);
+            }
+            Target::Upvalue(index) => {
+                self.emit_op(OpCode::SetUpvalue);
+                self.emit(index);
+            }
+            Target::Global(constant) => self.emit_op_u16(OpCode::SetGlobal, constant),
+        }
+    }
+
+    /// Compiles a function body and emits the closure that creates it.
+    fn function(&mut self, stmt: &FunctionStmt, kind: FunctionKind) {
+        self.line = stmt.name.line;
+        self.states
+            .push(FunctionState::new(&stmt.name.token_type.to_string(), kind));
+        self.begin_scope();
+
+        for param in &stmt.params {
+            self.state().function.arity += 1;
+            self.declare_variable(param);
+        }
+        walk_mut::walk_statements(self, &stmt.body);
+        self.emit_return();
+
+        let state = self.states.pop().unwrap();
+        let mut function = state.function;
+        function.upvalue_count = state.upvalues.len();
+
+        self.line = stmt.name.line;
+        let constant = self.make_constant(Constant::Function(Rc::new(function)));
+        self.emit_op_u16(OpCode::Closure, constant);
+        for upvalue in state.upvalues {
+            self.emit(upvalue.is_local as u8);
+            self.emit(upvalue.index);
+        }
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MutVisitor for Compiler {
+    type E = ();
+
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) {
+        self.visit_expression(&stmt.expression);
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        self.visit_expression(&stmt.expression);
+        self.emit_op(OpCode::Print);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        self.line = stmt.name.line;
+        match &stmt.initializer {
+            Some(initializer) => self.visit_expression(initializer),
+            None => self.emit_op(OpCode::Nil),
+        }
+        self.line = stmt.name.line;
+        self.declare_variable(&stmt.name);
+        self.define_variable(&stmt.name);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        self.begin_scope();
+        walk_mut::walk_block_stmt(self, stmt);
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) {
+        self.visit_expression(&stmt.condition);
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.visit_statement(&stmt.then_branch);
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.visit_statement(else_branch);
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) {
+        let start = self.chunk().code.len();
+        self.visit_expression(&stmt.condition);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.visit_statement(&stmt.body);
+        self.emit_loop(start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) {
+        // Declared first, so the function can call itself
+        self.declare_variable(&stmt.name);
+        self.function(stmt, FunctionKind::Function);
+        self.line = stmt.name.line;
+        self.define_variable(&stmt.name);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) {
+        self.line = stmt.keyword.line;
+        match &stmt.value {
+            // The method isn't invoked: `TailCall` takes over the frame
+            // with whatever the callee turns out to be
+            Some(Expr::Call(call)) if stmt.tail_call.get() => {
+                self.visit_expression(&call.callee);
+                for argument in &call.arguments {
+                    self.visit_expression(argument);
+                }
+
+                self.line = call.paren.line;
+                self.emit_op(OpCode::TailCall);
+                self.emit(call.arguments.len() as u8);
+                // Reached when the callee was a native or a class, which
+                // doesn't take over the frame but returns to it
+                self.emit_op(OpCode::Return);
+            }
+            Some(value) => {
+                self.visit_expression(value);
+                self.emit_op(OpCode::Return);
+            }
+            None => self.emit_return(),
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) {
+        self.line = stmt.name.line;
+        let name = self.identifier_constant(&stmt.name);
+        self.declare_variable(&stmt.name);
+        self.emit_op_u16(OpCode::Class, name);
+        self.define_variable(&stmt.name);
+
+        // The superclass lives in a local named `super` around the methods
+        if let Some(superclass) = &stmt.superclass {
+            self.visit_variable(superclass);
+            self.begin_scope();
+            self.add_local(Symbol::intern("super"));
+
+            self.get_variable(&stmt.name);
+            self.line = superclass.name.line;
+            self.emit_op(OpCode::Inherit);
+        }
+
+        self.get_variable(&stmt.name);
+        for method in &stmt.methods {
+            let kind = match method.name.token_type.to_string().as_str() {
+                "init" => FunctionKind::Initializer,
+                _ => FunctionKind::Method,
+            };
+            self.function(method, kind);
+            let name = self.identifier_constant(&method.name);
+            self.emit_op_u16(OpCode::Method, name);
+        }
+        self.emit_op(OpCode::Pop);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_binary(&mut self, expr: &BinaryExpr) {
+        self.visit_expression(&expr.left);
+        self.visit_expression(&expr.right);
+
+        self.line = expr.operator.line;
+        let op = match expr.operator.token_type {
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            ref operator => unreachable!("{} isn't a binary operator", operator),
+        };
+        self.emit_op(op);
+    }
+
+    fn visit_literal(&mut self, expr: &LiteralExpr) {
+        match &expr.value {
+            LiteralValue::Float(number) => {
+                let constant = self.make_constant(Constant::Number(*number));
+                self.emit_op_u16(OpCode::Constant, constant);
+            }
+            LiteralValue::LoxString(string) => {
+                let constant = self.make_constant(Constant::String(string.clone()));
+                self.emit_op_u16(OpCode::Constant, constant);
+            }
+            LiteralValue::Bool(true) => self.emit_op(OpCode::True),
+            LiteralValue::Bool(false) => self.emit_op(OpCode::False),
+            LiteralValue::None => self.emit_op(OpCode::Nil),
+        }
+    }
+
+    fn visit_unary(&mut self, expr: &UnaryExpr) {
+        self.visit_expression(&expr.right);
+
+        self.line = expr.operator.line;
+        match expr.operator.token_type {
+            TokenType::Minus => self.emit_op(OpCode::Negate),
+            _ => self.emit_op(OpCode::Not),
+        }
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) {
+        self.get_variable(&expr.name);
+    }
+
+    fn visit_assign(&mut self, expr: &AssignExpr) {
+        self.visit_expression(&expr.value);
+        self.set_variable(&expr.name);
+    }
+
+    fn visit_logical(&mut self, expr: &LogicalExpr) {
+        self.visit_expression(&expr.left);
+
+        self.line = expr.operator.line;
+        let end_jump = match expr.operator.token_type {
+            TokenType::Or => {
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump);
+                end_jump
+            }
+            _ => self.emit_jump(OpCode::JumpIfFalse),
+        };
+
+        self.emit_op(OpCode::Pop);
+        self.visit_expression(&expr.right);
+        self.patch_jump(end_jump);
+    }
+
+    fn visit_call(&mut self, expr: &CallExpr) {
+        // `object.method(...)` is one instruction, unless the name and the
+        // call are on different lines: errors finding the method point at
+        // the first and errors calling it at the second
+        let method = match expr.callee.as_ref() {
+            Expr::Get(get) if get.name.line == expr.paren.line => Some(get),
+            _ => None,
+        };
+
+        match method {
+            Some(get) => self.visit_expression(&get.object),
+            None => self.visit_expression(&expr.callee),
+        }
+        for argument in &expr.arguments {
+            self.visit_expression(argument);
+        }
+
+        self.line = expr.paren.line;
+        match method {
+            Some(get) => {
+                let name = self.identifier_constant(&get.name);
+                let cache = self.inline_cache();
+                self.emit_op_u16(OpCode::Invoke, name);
+                self.emit(expr.arguments.len() as u8);
+                self.emit_u16(cache);
+            }
+            None => {
+                self.emit_op(OpCode::Call);
+                // The parser allows at most 255 arguments
+                self.emit(expr.arguments.len() as u8);
+            }
+        }
+    }
+
+    fn visit_get(&mut self, expr: &GetExpr) {
+        self.visit_expression(&expr.object);
+
+        self.line = expr.name.line;
+        let name = self.identifier_constant(&expr.name);
+        let cache = self.inline_cache();
+        self.emit_op_u16(OpCode::GetProperty, name);
+        self.emit_u16(cache);
+    }
+
+    fn visit_set(&mut self, expr: &SetExpr) {
+        self.visit_expression(&expr.object);
+        self.visit_expression(&expr.value);
+
+        self.line = expr.name.line;
+        let name = self.identifier_constant(&expr.name);
+        let cache = self.inline_cache();
+        self.emit_op_u16(OpCode::SetProperty, name);
+        self.emit_u16(cache);
+    }
+
+    fn visit_this(&mut self, expr: &ThisExpr) {
+        let name = Token::new(
+            TokenType::Identifier(Symbol::intern("this")),
+            expr.keyword.line,
+        );
+        self.get_variable(&name);
+    }
+
+    fn visit_super(&mut self, expr: &SuperExpr) {
+        let line = expr.keyword.line;
+        self.get_variable(&Token::new(
+            TokenType::Identifier(Symbol::intern("this")),
+            line,
+        ));
+        self.get_variable(&Token::new(
+            TokenType::Identifier(Symbol::intern("super")),
+            line,
+        ));
+
+        self.line = expr.method.line;
+        let name = self.identifier_constant(&expr.method);
+        self.emit_op_u16(OpCode::GetSuper, name);
+    }
+}
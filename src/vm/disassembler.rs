@@ -0,0 +1,168 @@
+//! Human-readable listings of compiled bytecode.
+//!
+//! Each instruction is shown on one line with its offset, source line and
+//! operands, with constants printed by value:
+//!
+//! ```text
+//! == script ==
+//! 0000    1 Constant            0 '1'
+//! 0003    | Print
+//! ```
+//!
+//! A `|` in the line column means the same line as the instruction before.
+
+use std::fmt::Write;
+
+use super::chunk::{Chunk, Constant, Function, OpCode};
+
+/// Lists a function's instructions, followed by those of every function
+/// nested in it.
+pub fn disassemble(function: &Function) -> String {
+    let mut out = String::new();
+    disassemble_function(function, &mut out);
+    out
+}
+
+fn disassemble_function(function: &Function, out: &mut String) {
+    let _ = writeln!(out, "== {} ==", function.name);
+    let chunk = &function.chunk;
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, out);
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(function) = constant {
+            out.push('\n');
+            disassemble_function(function, out);
+        }
+    }
+}
+
+/// Writes the instruction at `offset` as one line, or more for a closure's
+/// upvalues, and returns the offset of the next one.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let _ = write!(out, "{:04} ", offset);
+    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        out.push_str("   | ");
+    } else {
+        let _ = write!(out, "{:4} ", chunk.lines[offset]);
+    }
+
+    let op = match OpCode::try_from(chunk.code[offset]) {
+        Ok(op) => op,
+        Err(byte) => {
+            let _ = writeln!(out, "Unknown opcode {}", byte);
+            return offset + 1;
+        }
+    };
+    let name = format!("{:?}", op);
+
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index = chunk.read_u16(offset + 1);
+            let _ = writeln!(
+                out,
+                "{:<16} {:4} '{}'",
+                name,
+                index,
+                constant(chunk, index as usize)
+            );
+            offset + 3
+        }
+        OpCode::GetProperty | OpCode::SetProperty => {
+            let index = chunk.read_u16(offset + 1);
+            let _ = writeln!(
+                out,
+                "{:<16} {:4} '{}' cache {}",
+                name,
+                index,
+                constant(chunk, index as usize),
+                chunk.read_u16(offset + 3)
+            );
+            offset + 5
+        }
+        OpCode::Invoke => {
+            let index = chunk.read_u16(offset + 1);
+            let _ = writeln!(
+                out,
+                "{:<16} {:4} '{}' ({} args) cache {}",
+                name,
+                index,
+                constant(chunk, index as usize),
+                chunk.code[offset + 3],
+                chunk.read_u16(offset + 4)
+            );
+            offset + 6
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call
+        | OpCode::TailCall => {
+            let _ = writeln!(out, "{:<16} {:4}", name, chunk.code[offset + 1]);
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let target = match op {
+                OpCode::Loop => (offset + 3).saturating_sub(jump),
+                _ => offset + 3 + jump,
+            };
+            let _ = writeln!(out, "{:<16} {:4} -> {}", name, offset, target);
+            offset + 3
+        }
+        OpCode::Closure => {
+            let index = chunk.read_u16(offset + 1);
+            let _ = writeln!(
+                out,
+                "{:<16} {:4} {}",
+                name,
+                index,
+                constant(chunk, index as usize)
+            );
+
+            let upvalue_count = match &chunk.constants[index as usize] {
+                Constant::Function(function) => function.upvalue_count,
+                _ => 0,
+            };
+            let mut offset = offset + 3;
+            for _ in 0..upvalue_count {
+                let kind = match chunk.code[offset] {
+                    1 => "local",
+                    _ => "upvalue",
+                };
+                let _ = writeln!(
+                    out,
+                    "{:04}    |                     {} {}",
+                    offset,
+                    kind,
+                    chunk.code[offset + 1]
+                );
+                offset += 2;
+            }
+            offset
+        }
+        _ => {
+            let _ = writeln!(out, "{}", name);
+            offset + 1
+        }
+    }
+}
+
+fn constant(chunk: &Chunk, index: usize) -> String {
+    match &chunk.constants[index] {
+        Constant::Number(number) => number.to_string(),
+        Constant::String(string) => string.to_string(),
+        Constant::Name(name) => name.to_string(),
+        Constant::Function(function) => format!("<fn {}>", function.name),
+    }
+}
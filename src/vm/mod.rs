@@ -0,0 +1,920 @@
+//! A second backend that compiles programs to bytecode and runs them on a
+//! stack machine, for scripts that run long enough for the tree-walker to be
+//! too slow. It takes the same resolved syntax tree as `Interpreter` and
+//! reports the same runtime errors.
+//!
+//! ```
+//! let mut vm = rlox::vm::Vm::new();
+//! vm.run("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10);")
+//!     .unwrap();
+//! ```
+
+pub mod cache;
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod object;
+pub mod value;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::engine::{self, Error};
+use crate::interpreter::{self, RuntimeError, TraceFrame, TracedError};
+use crate::native::Arity;
+use crate::symbol::Symbol;
+use crate::token::TokenType;
+use chunk::{Constant, Function, OpCode};
+use compiler::Compiler;
+use object::{
+    BoundMethod, Class, Closure, Heap, InlineCache, Instance, Native, Obj, ObjRef, ShapeId, Upvalue,
+};
+use value::Value;
+
+/// How deep calls can nest before the VM gives up with a stack overflow.
+const FRAMES_MAX: usize = interpreter::MAX_CALL_DEPTH;
+
+struct CallFrame {
+    closure: ObjRef,
+    /// The closure's function object, which holds its constants.
+    function: ObjRef,
+    code: Rc<Function>,
+    ip: usize,
+    /// Where the frame's stack window starts: the callee, then arguments
+    /// and locals.
+    slots: usize,
+    /// The functions this frame ran before tail calls replaced them, each
+    /// with the line of its tail call, for stack traces.
+    tail_callers: Vec<(Rc<Function>, usize)>,
+}
+
+impl CallFrame {
+    /// The line of the instruction being executed.
+    fn line(&self) -> usize {
+        self.code.chunk.lines[self.ip.saturating_sub(1)]
+    }
+}
+
+/// What a property lookup on an instance found.
+enum Property {
+    Field(Value),
+    Method(ObjRef),
+}
+
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Symbol, Value>,
+    /// The name looked up to find a class's initializer.
+    init_string: Symbol,
+    /// Upvalues still pointing into the stack, ordered by slot.
+    open_upvalues: Vec<ObjRef>,
+    output: Box<dyn Write>,
+    /// Whether to print the stack and each instruction on stderr as it runs.
+    trace: bool,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let mut vm = Vm {
+            heap: Heap::new(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            init_string: Symbol::intern("init"),
+            open_upvalues: Vec::new(),
+            output: Box::new(io::stdout()),
+            trace: false,
+        };
+        vm.define_native("clock", Arity::Fixed(0), |_, _| {
+            Ok(Value::number(interpreter::clock()))
+        });
+        vm
+    }
+
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Collects garbage before every allocation instead of when the heap
+    /// has grown enough, to find objects the collector misses.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.heap.set_stress(stress);
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// Frees every object the program can no longer reach: everything but
+    /// what's on the stack, in globals and in open upvalues, and what those
+    /// refer to.
+    pub fn collect_garbage(&mut self) {
+        let frames = self
+            .frames
+            .iter()
+            .flat_map(|frame| [frame.closure, frame.function]);
+        let roots = self
+            .stack
+            .iter()
+            .copied()
+            .chain(self.globals.values().copied())
+            .chain(self.open_upvalues.iter().copied().map(Value::obj))
+            .chain(frames.map(Value::obj));
+        self.heap.collect(roots);
+    }
+
+    /// Interns a string, collecting first if the heap has grown enough.
+    fn alloc_string(&mut self, string: &str) -> Value {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc_string(string)
+    }
+
+    /// Allocates an object, collecting first if the heap has grown enough.
+    /// Whatever `obj` refers to has to be reachable from the roots.
+    fn alloc(&mut self, obj: Obj) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(obj)
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Heap, &[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let native = self.heap.alloc(Obj::Native(Native {
+            name: name.to_string(),
+            arity,
+            function: Rc::new(Box::new(function)),
+        }));
+        self.globals
+            .insert(Symbol::intern(name), Value::obj(native));
+    }
+
+    /// Scans, parses, resolves, compiles and runs a program.
+    pub fn run(&mut self, source: &str) -> Result<(), Error> {
+        let function = compile(source)?;
+        Ok(self.interpret(function)?)
+    }
+
+    /// Runs a compiled script. Globals it defines stay around for the next.
+    pub fn interpret(&mut self, script: Rc<Function>) -> Result<(), TracedError> {
+        // Nothing roots the script's objects until it's on the stack, so
+        // these allocations mustn't collect
+        let function = self.heap.load_function(script);
+        let closure = self.heap.alloc(Obj::Closure(Closure {
+            function,
+            upvalues: Vec::new(),
+        }));
+        self.stack.push(Value::obj(closure));
+        self.call_closure(closure, 0)
+            .and_then(|_| self.execute())
+            .map_err(|err| self.traced(err))
+    }
+
+    /// Records the calls the error escaped and unwinds the stack.
+    fn traced(&mut self, error: RuntimeError) -> TracedError {
+        let mut trace = Vec::new();
+        for i in (1..self.frames.len()).rev() {
+            let frame = &self.frames[i];
+            // Each function was called from where the one it replaced made
+            // the tail call
+            let called_from = |index: usize| match index {
+                0 => Some(self.frames[i - 1].line()),
+                index => Some(frame.tail_callers[index - 1].1),
+            };
+            trace.push(TraceFrame {
+                function: frame.code.name.clone(),
+                called_from: called_from(frame.tail_callers.len()),
+                native: false,
+            });
+            for (index, (code, _)) in frame.tail_callers.iter().enumerate().rev() {
+                trace.push(TraceFrame {
+                    function: code.name.clone(),
+                    called_from: called_from(index),
+                    native: false,
+                });
+            }
+        }
+
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+        TracedError { error, trace }
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn current(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn line(&self) -> usize {
+        self.current().line()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame();
+        let byte = frame.code.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame();
+        let value = frame.code.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.heap.function(self.current().function).constants[index]
+    }
+
+    /// Reads a name operand. The function comes along because the name is
+    /// borrowed from its constants.
+    fn read_name(&mut self) -> (Rc<Function>, usize) {
+        let index = self.read_u16() as usize;
+        (self.current().code.clone(), index)
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the stack shouldn't underflow")
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn numbers(&mut self, operator: TokenType) -> Result<(f64, f64), RuntimeError> {
+        match (self.peek(1).as_number(), self.peek(0).as_number()) {
+            (Some(left), Some(right)) => {
+                self.stack.truncate(self.stack.len() - 2);
+                Ok((left, right))
+            }
+            _ => Err(RuntimeError::OperandsMustBeNumbers(operator, self.line())),
+        }
+    }
+
+    fn trace_instruction(&self) {
+        let mut line = String::from("          ");
+        for value in &self.stack {
+            line.push_str(&format!("[ {} ]", self.heap.display(*value)));
+        }
+        line.push('\n');
+
+        let frame = self.current();
+        disassembler::disassemble_instruction(&frame.code.chunk, frame.ip, &mut line);
+        eprint!("{}", line);
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            if self.trace {
+                self.trace_instruction();
+            }
+
+            let op = match OpCode::try_from(self.read_byte()) {
+                Ok(op) => op,
+                Err(byte) => unreachable!("the compiler never emits opcode {}", byte),
+            };
+
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::NIL),
+                OpCode::True => self.push(Value::bool(true)),
+                OpCode::False => self.push(Value::bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.current().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot]);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.current().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let (function, index) = self.read_name();
+                    let name = constant_name(&function, index);
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(*value),
+                        None => {
+                            return Err(RuntimeError::UndefinedVariable(
+                                name.to_string(),
+                                self.line(),
+                            ))
+                        }
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let (function, index) = self.read_name();
+                    let value = self.pop();
+                    self.globals.insert(constant_name(&function, index), value);
+                }
+                OpCode::SetGlobal => {
+                    let (function, index) = self.read_name();
+                    let name = constant_name(&function, index);
+                    let value = self.peek(0);
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => {
+                            return Err(RuntimeError::UndefinedVariable(
+                                name.to_string(),
+                                self.line(),
+                            ))
+                        }
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.heap.closure(self.current().closure).upvalues[index];
+                    let value = match self.heap.get(upvalue) {
+                        Obj::Upvalue(Upvalue::Open(slot)) => self.stack[*slot],
+                        Obj::Upvalue(Upvalue::Closed(value)) => *value,
+                        _ => unreachable!("expected an upvalue"),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.heap.closure(self.current().closure).upvalues[index];
+                    let value = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Obj::Upvalue(Upvalue::Open(slot)) => {
+                            let slot = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Obj::Upvalue(upvalue) => *upvalue = Upvalue::Closed(value),
+                        _ => unreachable!("expected an upvalue"),
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_u16() as usize;
+                    let cache = self.read_u16() as usize;
+                    let instance = match self.as_instance(self.peek(0)) {
+                        Some(instance) => instance,
+                        None => return Err(RuntimeError::OnlyInstancesHaveProperties(self.line())),
+                    };
+
+                    match self.find_property(instance, name, cache)? {
+                        Property::Field(value) => {
+                            self.pop();
+                            self.push(value);
+                        }
+                        Property::Method(method) => self.bind_method(method),
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_u16() as usize;
+                    let cache = self.read_u16() as usize;
+                    let value = self.peek(0);
+                    let instance = match self.as_instance(self.peek(1)) {
+                        Some(instance) => instance,
+                        None => return Err(RuntimeError::OnlyInstancesHaveFields(self.line())),
+                    };
+
+                    self.set_field(instance, name, cache, value);
+                    self.stack.truncate(self.stack.len() - 2);
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let (function, index) = self.read_name();
+                    let name = constant_name(&function, index);
+                    let superclass = match self.pop().as_obj() {
+                        Some(obj) => obj,
+                        None => unreachable!("super is always a class"),
+                    };
+                    match self.heap.class(superclass).methods.get(&name) {
+                        Some(&method) => self.bind_method(method),
+                        None => {
+                            return Err(RuntimeError::UndefinedProperty(
+                                name.to_string(),
+                                self.line(),
+                            ))
+                        }
+                    }
+                }
+                OpCode::Equal => {
+                    let (right, left) = (self.pop(), self.pop());
+                    self.push(Value::bool(self.heap.values_equal(left, right)));
+                }
+                OpCode::NotEqual => {
+                    let (right, left) = (self.pop(), self.pop());
+                    self.push(Value::bool(!self.heap.values_equal(left, right)));
+                }
+                OpCode::Greater => {
+                    let (left, right) = self.numbers(TokenType::Greater)?;
+                    self.push(Value::bool(left > right));
+                }
+                OpCode::GreaterEqual => {
+                    let (left, right) = self.numbers(TokenType::GreaterEqual)?;
+                    self.push(Value::bool(left >= right));
+                }
+                OpCode::Less => {
+                    let (left, right) = self.numbers(TokenType::Less)?;
+                    self.push(Value::bool(left < right));
+                }
+                OpCode::LessEqual => {
+                    let (left, right) = self.numbers(TokenType::LessEqual)?;
+                    self.push(Value::bool(left <= right));
+                }
+                OpCode::Add => {
+                    let (left, right) = (self.peek(1), self.peek(0));
+                    let value = match (left.as_number(), right.as_number()) {
+                        (Some(left), Some(right)) => Value::number(left + right),
+                        _ => match (self.heap.as_str(left), self.heap.as_str(right)) {
+                            (Some(left), Some(right)) => {
+                                let string = [left, right].concat();
+                                self.alloc_string(&string)
+                            }
+                            _ => {
+                                return Err(RuntimeError::OperandsMustBeNumbersOrStrings(
+                                    self.line(),
+                                ))
+                            }
+                        },
+                    };
+                    self.stack.truncate(self.stack.len() - 2);
+                    self.push(value);
+                }
+                OpCode::Subtract => {
+                    let (left, right) = self.numbers(TokenType::Minus)?;
+                    self.push(Value::number(left - right));
+                }
+                OpCode::Multiply => {
+                    let (left, right) = self.numbers(TokenType::Star)?;
+                    self.push(Value::number(left * right));
+                }
+                OpCode::Divide => {
+                    let (left, right) = self.numbers(TokenType::Slash)?;
+                    self.push(Value::number(left / right));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::bool(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.peek(0).as_number() {
+                    Some(number) => {
+                        self.pop();
+                        self.push(Value::number(-number));
+                    }
+                    None => {
+                        return Err(RuntimeError::OperandMustBeNumber(
+                            TokenType::Minus,
+                            self.line(),
+                        ))
+                    }
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    // Like a closed pipe, a failing output shouldn't stop the program
+                    let _ = writeln!(self.output, "{}", self.heap.display(value));
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frame().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame().ip -= offset;
+                }
+                OpCode::Call => {
+                    let count = self.read_byte() as usize;
+                    self.call_value(self.peek(count), count)?;
+                }
+                OpCode::TailCall => {
+                    let count = self.read_byte() as usize;
+                    self.tail_call(self.peek(count), count)?;
+                }
+                OpCode::Closure => {
+                    let function = match self.read_constant().as_obj() {
+                        Some(function) => function,
+                        None => unreachable!("expected a function constant"),
+                    };
+                    let upvalue_count = self.heap.function(function).function.upvalue_count;
+
+                    let mut upvalues = Vec::with_capacity(upvalue_count);
+                    for _ in 0..upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        upvalues.push(match is_local {
+                            true => self.capture_upvalue(self.current().slots + index),
+                            false => self.heap.closure(self.current().closure).upvalues[index],
+                        });
+                    }
+
+                    let closure = self.alloc(Obj::Closure(Closure { function, upvalues }));
+                    self.push(Value::obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let (function, index) = self.read_name();
+                    let class = self.alloc(Obj::Class(Class {
+                        name: constant_name(&function, index).to_string(),
+                        methods: HashMap::new(),
+                    }));
+                    self.push(Value::obj(class));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1).as_obj().map(|obj| self.heap.get(obj)) {
+                        Some(Obj::Class(superclass)) => superclass.methods.clone(),
+                        _ => return Err(RuntimeError::SuperclassMustBeClass(self.line())),
+                    };
+                    if let Some(class) = self.pop().as_obj() {
+                        self.heap.class_mut(class).methods = superclass;
+                    }
+                }
+                OpCode::Method => {
+                    let (function, index) = self.read_name();
+                    let (method, class) = match (self.peek(0).as_obj(), self.peek(1).as_obj()) {
+                        (Some(method), Some(class)) => (method, class),
+                        _ => unreachable!("expected a class and a method"),
+                    };
+                    self.heap
+                        .class_mut(class)
+                        .methods
+                        .insert(constant_name(&function, index), method);
+                    self.pop();
+                }
+                OpCode::Invoke => {
+                    let name = self.read_u16() as usize;
+                    let count = self.read_byte() as usize;
+                    let cache = self.read_u16() as usize;
+                    let instance = match self.as_instance(self.peek(count)) {
+                        Some(instance) => instance,
+                        None => return Err(RuntimeError::OnlyInstancesHaveProperties(self.line())),
+                    };
+
+                    // The receiver is already where the method expects
+                    // `this`, so there's no bound method to make
+                    match self.find_property(instance, name, cache)? {
+                        Property::Method(method) => self.call_closure(method, count)?,
+                        Property::Field(value) => {
+                            let base = self.stack.len() - count - 1;
+                            self.stack[base] = value;
+                            self.call_value(value, count)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `callee`, which sits on the stack under its `count` arguments.
+    fn call_value(&mut self, callee: Value, count: usize) -> Result<(), RuntimeError> {
+        let callee = match callee.as_obj() {
+            Some(obj) => obj,
+            None => return Err(RuntimeError::NotCallable(self.line())),
+        };
+        let base = self.stack.len() - count - 1;
+
+        match self.heap.get(callee) {
+            Obj::Closure(_) => self.call_closure(callee, count),
+            Obj::Native(native) => {
+                let function = native.function.clone();
+                match native.arity {
+                    arity if arity.accepts(count) => {}
+                    Arity::Fixed(arity) => {
+                        return Err(RuntimeError::ArityMismatch(arity, count, self.line()))
+                    }
+                    Arity::AtLeast(minimum) => {
+                        return Err(RuntimeError::TooFewArguments(minimum, count, self.line()))
+                    }
+                }
+
+                let result = function(&mut self.heap, &self.stack[base + 1..])
+                    .map_err(|message| RuntimeError::Native(message, self.line()))?;
+                self.stack.truncate(base);
+                self.push(result);
+                Ok(())
+            }
+            Obj::Class(class) => {
+                let initializer = class.methods.get(&self.init_string).copied();
+                let instance = self.alloc(Obj::Instance(Instance {
+                    class: callee,
+                    shape: ShapeId::EMPTY,
+                    fields: Vec::new(),
+                }));
+                self.stack[base] = Value::obj(instance);
+
+                match initializer {
+                    Some(initializer) => self.call_closure(initializer, count),
+                    None if count != 0 => Err(RuntimeError::ArityMismatch(0, count, self.line())),
+                    None => Ok(()),
+                }
+            }
+            Obj::BoundMethod(bound) => {
+                let method = bound.method;
+                self.stack[base] = bound.receiver;
+                self.call_closure(method, count)
+            }
+            _ => Err(RuntimeError::NotCallable(self.line())),
+        }
+    }
+
+    /// Calls `callee` in place of the current function: a closure or bound
+    /// method is moved down with its arguments to where the current frame
+    /// starts, and the frame is reused for it. Anything else is called as
+    /// usual.
+    fn tail_call(&mut self, callee: Value, count: usize) -> Result<(), RuntimeError> {
+        let base = self.stack.len() - count - 1;
+        let closure = match callee.as_obj().map(|obj| (obj, self.heap.get(obj))) {
+            Some((obj, Obj::Closure(_))) => obj,
+            Some((_, Obj::BoundMethod(bound))) => {
+                let method = bound.method;
+                self.stack[base] = bound.receiver;
+                method
+            }
+            _ => return self.call_value(callee, count),
+        };
+
+        let function = self.heap.closure(closure).function;
+        let code = self.heap.function(function).function.clone();
+        if code.arity != count {
+            return Err(RuntimeError::ArityMismatch(code.arity, count, self.line()));
+        }
+
+        let slots = self.current().slots;
+        self.close_upvalues(slots);
+        self.stack.drain(slots..base);
+
+        let line = self.line();
+        let frame = self.frame();
+        let mut tail_callers = std::mem::take(&mut frame.tail_callers);
+        interpreter::record_tail_call(&mut tail_callers, frame.code.clone(), line, |a, b| {
+            a.name == b.name
+        });
+        *frame = CallFrame {
+            closure,
+            function,
+            code,
+            ip: 0,
+            slots,
+            tail_callers,
+        };
+        Ok(())
+    }
+
+    fn call_closure(&mut self, closure: ObjRef, count: usize) -> Result<(), RuntimeError> {
+        let function = self.heap.closure(closure).function;
+        let code = self.heap.function(function).function.clone();
+
+        if code.arity != count {
+            return Err(RuntimeError::ArityMismatch(code.arity, count, self.line()));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(RuntimeError::StackOverflow(self.line()));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            code,
+            ip: 0,
+            slots: self.stack.len() - count - 1,
+            tail_callers: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Replaces the receiver on top of the stack with `method` bound to it.
+    /// The receiver stays on the stack until then, so a collection can't
+    /// free it.
+    fn bind_method(&mut self, method: ObjRef) {
+        let receiver = self.peek(0);
+        let bound = self.alloc(Obj::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::obj(bound));
+    }
+
+    fn as_instance(&self, value: Value) -> Option<ObjRef> {
+        let obj = value.as_obj()?;
+        matches!(self.heap.get(obj), Obj::Instance(_)).then_some(obj)
+    }
+
+    /// Finds the property named by constant `name` on `instance`, a field
+    /// before a method, through the current function's inline cache
+    /// `cache`.
+    fn find_property(
+        &mut self,
+        instance: ObjRef,
+        name: usize,
+        cache: usize,
+    ) -> Result<Property, RuntimeError> {
+        let function = self.current().function;
+        let Instance { class, shape, .. } = *self.heap.instance(instance);
+
+        match self.heap.function(function).caches[cache] {
+            InlineCache::Field {
+                name: cached_name,
+                shape: cached,
+                index,
+            } if cached_name == name && cached == shape => {
+                return Ok(Property::Field(self.heap.instance(instance).fields[index]))
+            }
+            InlineCache::Method {
+                name: cached_name,
+                shape: cached,
+                class: cached_class,
+                method,
+            } if cached_name == name && cached == shape && cached_class == class => {
+                return Ok(Property::Method(method))
+            }
+            _ => {}
+        }
+
+        let symbol = constant_name(&self.current().code, name);
+        let (property, entry) = match self.heap.shape(shape).field(symbol) {
+            Some(index) => (
+                Property::Field(self.heap.instance(instance).fields[index]),
+                InlineCache::Field { name, shape, index },
+            ),
+            None => match self.heap.class(class).methods.get(&symbol) {
+                Some(&method) => (
+                    Property::Method(method),
+                    InlineCache::Method {
+                        name,
+                        shape,
+                        class,
+                        method,
+                    },
+                ),
+                None => {
+                    return Err(RuntimeError::UndefinedProperty(
+                        symbol.to_string(),
+                        self.line(),
+                    ))
+                }
+            },
+        };
+        self.heap.function_mut(function).caches[cache] = entry;
+        Ok(property)
+    }
+
+    /// Sets the field named by constant `name` on `instance`, adding it if
+    /// it's new, through the current function's inline cache `cache`.
+    fn set_field(&mut self, instance: ObjRef, name: usize, cache: usize, value: Value) {
+        let function = self.current().function;
+        let shape = self.heap.instance(instance).shape;
+
+        match self.heap.function(function).caches[cache] {
+            InlineCache::Field {
+                name: cached_name,
+                shape: cached,
+                index,
+            } if cached_name == name && cached == shape => {
+                self.heap.instance_mut(instance).fields[index] = value;
+                return;
+            }
+            InlineCache::Transition {
+                name: cached_name,
+                from,
+                to,
+            } if cached_name == name && from == shape => {
+                let instance = self.heap.instance_mut(instance);
+                instance.fields.push(value);
+                instance.shape = to;
+                return;
+            }
+            _ => {}
+        }
+
+        let symbol = constant_name(&self.current().code, name);
+        let entry = match self.heap.shape(shape).field(symbol) {
+            Some(index) => {
+                self.heap.instance_mut(instance).fields[index] = value;
+                InlineCache::Field { name, shape, index }
+            }
+            None => {
+                let to = self.heap.add_field(shape, symbol);
+                let instance = self.heap.instance_mut(instance);
+                instance.fields.push(value);
+                instance.shape = to;
+                InlineCache::Transition {
+                    name,
+                    from: shape,
+                    to,
+                }
+            }
+        };
+        self.heap.function_mut(function).caches[cache] = entry;
+    }
+
+    /// Finds or creates the upvalue for a stack slot, so closures that
+    /// capture the same variable share it.
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let position = self.open_upvalues.partition_point(|&upvalue| {
+            matches!(self.heap.get(upvalue), Obj::Upvalue(Upvalue::Open(open)) if *open < slot)
+        });
+
+        if let Some(&upvalue) = self.open_upvalues.get(position) {
+            if matches!(self.heap.get(upvalue), Obj::Upvalue(Upvalue::Open(open)) if *open == slot)
+            {
+                return upvalue;
+            }
+        }
+
+        let upvalue = self.alloc(Obj::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, upvalue);
+        upvalue
+    }
+
+    /// Moves the variables in slots from `last` up off the stack and into
+    /// their upvalues.
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(&upvalue) = self.open_upvalues.last() {
+            let slot = match self.heap.get(upvalue) {
+                Obj::Upvalue(Upvalue::Open(slot)) if *slot >= last => *slot,
+                _ => break,
+            };
+            *self.heap.get_mut(upvalue) = Obj::Upvalue(Upvalue::Closed(self.stack[slot]));
+            self.open_upvalues.pop();
+        }
+    }
+}
+
+/// Scans, parses, resolves and compiles a program to bytecode.
+pub fn compile(source: &str) -> Result<Rc<Function>, Error> {
+    let stmts = engine::compile(source)?;
+    Compiler::new().compile(&stmts).map_err(Error::Compile)
+}
+
+/// Compiles the script read from `path`, or loads it from the `.loxc` file
+/// next to it when that was compiled from the same source. A stale or
+/// damaged cache is rebuilt; scripts without one aren't given one, that's
+/// what `rlox compile` is for.
+pub fn compile_cached(path: &Path, source: &str) -> Result<Rc<Function>, Error> {
+    let cache = cache::cache_path(path);
+    let bytes = match fs::read(&cache) {
+        Ok(bytes) => bytes,
+        Err(_) => return compile(source),
+    };
+    if let Ok(function) = cache::decode(&bytes, Some(source)) {
+        return Ok(function);
+    }
+
+    let function = compile(source)?;
+    // Running the script matters more than caching it, say in a read-only
+    // directory
+    let _ = fs::write(&cache, cache::encode(&function, source));
+    Ok(function)
+}
+
+fn constant_name(function: &Function, index: usize) -> Symbol {
+    match &function.chunk.constants[index] {
+        Constant::Name(name) => *name,
+        _ => unreachable!("expected a name constant"),
+    }
+}
@@ -0,0 +1,522 @@
+//! The heap the bytecode VM's objects live on.
+//!
+//! Objects are referred to by index into the heap rather than by pointer, so
+//! values are small and `Copy`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::chunk::{Constant, Function};
+use super::value::{Unpacked, Value};
+use crate::native::Arity;
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(pub(super) u32);
+
+/// A native's body. It gets the heap to read strings from its arguments and
+/// allocate its result. An `Err` is raised as a runtime error at the call
+/// site. Natives mustn't hold on to objects between calls: the collector
+/// can't see them there.
+pub type NativeFn = Box<dyn Fn(&mut Heap, &[Value]) -> Result<Value, String>>;
+
+pub struct Native {
+    pub name: String,
+    pub arity: Arity,
+    pub function: Rc<NativeFn>,
+}
+
+/// A compiled function along with its constants turned into values.
+pub struct FunctionObj {
+    pub function: Rc<Function>,
+    pub constants: Vec<Value>,
+    /// What each property instruction found last time, by cache index.
+    pub caches: Vec<InlineCache>,
+}
+
+/// What a property instruction remembers about the last instance it saw, so
+/// the next one with the same shape skips the lookup. Any other shape falls
+/// back to looking the name up and caches what it finds instead.
+///
+/// Each entry keeps the constant index of the `name` it was filled for, so
+/// instructions sharing a cache can't be answered for each other's names.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum InlineCache {
+    #[default]
+    Empty,
+    /// The property is the field at `index`.
+    Field {
+        name: usize,
+        shape: ShapeId,
+        index: usize,
+    },
+    /// The property is `class`'s `method`: instances of other classes can
+    /// have the same shape.
+    Method {
+        name: usize,
+        shape: ShapeId,
+        class: ObjRef,
+        method: ObjRef,
+    },
+    /// Setting the property adds a field, turning shape `from` into `to`.
+    Transition {
+        name: usize,
+        from: ShapeId,
+        to: ShapeId,
+    },
+}
+
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+/// A variable captured by a closure. It stays on the stack while the
+/// function that declared it is running, and moves here when it returns.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<Symbol, ObjRef>,
+}
+
+/// Where an instance keeps each of its fields. Instances given the same
+/// fields in the same order share a shape, so their fields are at the same
+/// indexes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeId(u32);
+
+impl ShapeId {
+    /// The shape of an instance without fields.
+    pub const EMPTY: ShapeId = ShapeId(0);
+}
+
+#[derive(Default)]
+pub struct Shape {
+    fields: HashMap<Symbol, usize>,
+    /// The shapes made by adding one more field to this one.
+    transitions: HashMap<Symbol, ShapeId>,
+}
+
+impl Shape {
+    pub fn field(&self, name: Symbol) -> Option<usize> {
+        self.fields.get(&name).copied()
+    }
+}
+
+pub struct Instance {
+    pub class: ObjRef,
+    pub shape: ShapeId,
+    /// Field values, at the indexes `shape` gives them.
+    pub fields: Vec<Value>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+pub enum Obj {
+    /// Always made by `Heap::alloc_string`, which keeps one object per
+    /// distinct string.
+    String(Rc<str>),
+    Function(FunctionObj),
+    Native(Native),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+impl Obj {
+    /// Roughly how much memory the object takes, counting what it owns.
+    fn size(&self) -> usize {
+        let entry = size_of::<Symbol>() + size_of::<ObjRef>();
+        size_of::<Obj>()
+            + match self {
+                Obj::String(string) => string.len(),
+                Obj::Function(function) => function.constants.len() * size_of::<Value>(),
+                Obj::Native(native) => native.name.len(),
+                Obj::Closure(closure) => closure.upvalues.len() * size_of::<ObjRef>(),
+                Obj::Upvalue(_) | Obj::BoundMethod(_) => 0,
+                Obj::Class(class) => class.name.len() + class.methods.len() * entry,
+                Obj::Instance(instance) => instance.fields.len() * size_of::<Value>(),
+            }
+    }
+}
+
+/// How much the heap may grow past what survived a collection before the
+/// next one.
+const HEAP_GROW_FACTOR: usize = 2;
+
+/// The least the heap is allowed to grow to before a collection, so small
+/// programs never pay for one.
+const MIN_NEXT_GC: usize = 1024 * 1024;
+
+/// Where every object lives. Objects are freed by `collect` once nothing
+/// reaches them; their slots are reused by later allocations.
+///
+/// The heap doesn't know the VM's roots, so it never collects on its own:
+/// the VM checks `should_collect` before allocating.
+pub struct Heap {
+    objects: Vec<Option<Obj>>,
+    marks: Vec<bool>,
+    /// Every string object by its contents.
+    strings: HashMap<Rc<str>, ObjRef>,
+    /// Every instance shape so far. Shapes are never freed: there's one
+    /// for each distinct way a program adds fields, not one per instance.
+    shapes: Vec<Shape>,
+    free: Vec<u32>,
+    /// Marked objects whose references haven't been followed yet.
+    gray: Vec<ObjRef>,
+    /// An estimate of the memory taken by live objects and the garbage
+    /// allocated since the last collection.
+    bytes_allocated: usize,
+    next_gc: usize,
+    stress: bool,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap {
+            objects: Vec::new(),
+            marks: Vec::new(),
+            strings: HashMap::new(),
+            shapes: vec![Shape::default()],
+            free: Vec::new(),
+            gray: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: MIN_NEXT_GC,
+            stress: false,
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap::default()
+    }
+
+    pub fn alloc(&mut self, obj: Obj) -> ObjRef {
+        self.bytes_allocated += obj.size();
+
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = Some(obj);
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(Some(obj));
+                self.marks.push(false);
+                ObjRef((self.objects.len() - 1) as u32)
+            }
+        }
+    }
+
+    /// The string object for `string`, allocating it only if there isn't
+    /// one already. Equal strings are the same object, so they compare by
+    /// reference.
+    pub fn alloc_string(&mut self, string: &str) -> Value {
+        if let Some(&obj) = self.strings.get(string) {
+            return Value::obj(obj);
+        }
+
+        let string: Rc<str> = string.into();
+        let obj = self.alloc(Obj::String(string.clone()));
+        self.strings.insert(string, obj);
+        Value::obj(obj)
+    }
+
+    /// Makes `should_collect` always true, so every allocation the VM makes
+    /// collects first. Slow, but it finds objects that aren't rooted.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.bytes_allocated > self.next_gc
+    }
+
+    /// How many objects are alive, or at least not yet collected.
+    pub fn object_count(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    pub fn bytes_allocated(&self) -> usize {
+        self.bytes_allocated
+    }
+
+    /// Frees every object that can't be reached from `roots`, then sets the
+    /// next collection for when the heap has grown by `HEAP_GROW_FACTOR`.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = Value>) {
+        for root in roots {
+            self.mark_value(root);
+        }
+        while let Some(obj) = self.gray.pop() {
+            self.blacken(obj);
+        }
+        self.sweep();
+
+        self.next_gc = (self.bytes_allocated * HEAP_GROW_FACTOR).max(MIN_NEXT_GC);
+    }
+
+    fn mark_value(&mut self, value: Value) {
+        if let Some(obj) = value.as_obj() {
+            self.mark(obj);
+        }
+    }
+
+    fn mark(&mut self, obj: ObjRef) {
+        let marked = &mut self.marks[obj.0 as usize];
+        if !*marked {
+            *marked = true;
+            self.gray.push(obj);
+        }
+    }
+
+    /// Marks everything `obj` refers to.
+    fn blacken(&mut self, obj: ObjRef) {
+        let mut values = Vec::new();
+        let mut objs = Vec::new();
+        match self.get(obj) {
+            Obj::String(_) | Obj::Native(_) | Obj::Upvalue(Upvalue::Open(_)) => {}
+            Obj::Function(function) => {
+                values.extend_from_slice(&function.constants);
+                // A collected class's slot could be reused by another one,
+                // which would then match the cache
+                for cache in &function.caches {
+                    if let InlineCache::Method { class, method, .. } = cache {
+                        objs.push(*class);
+                        objs.push(*method);
+                    }
+                }
+            }
+            Obj::Closure(closure) => {
+                objs.push(closure.function);
+                objs.extend_from_slice(&closure.upvalues);
+            }
+            Obj::Upvalue(Upvalue::Closed(value)) => values.push(*value),
+            Obj::Class(class) => objs.extend(class.methods.values()),
+            Obj::Instance(instance) => {
+                objs.push(instance.class);
+                values.extend_from_slice(&instance.fields);
+            }
+            Obj::BoundMethod(bound) => {
+                values.push(bound.receiver);
+                objs.push(bound.method);
+            }
+        }
+
+        for value in values {
+            self.mark_value(value);
+        }
+        for obj in objs {
+            self.mark(obj);
+        }
+    }
+
+    fn sweep(&mut self) {
+        // The table doesn't keep strings alive, it only has to forget them
+        let marks = &self.marks;
+        self.strings.retain(|_, obj| marks[obj.0 as usize]);
+
+        self.bytes_allocated = 0;
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            let Some(obj) = slot else { continue };
+            if self.marks[index] {
+                self.marks[index] = false;
+                self.bytes_allocated += obj.size();
+            } else {
+                *slot = None;
+                self.free.push(index as u32);
+            }
+        }
+    }
+
+    pub fn get(&self, obj: ObjRef) -> &Obj {
+        self.objects[obj.0 as usize]
+            .as_ref()
+            .expect("a live object was collected")
+    }
+
+    pub fn get_mut(&mut self, obj: ObjRef) -> &mut Obj {
+        self.objects[obj.0 as usize]
+            .as_mut()
+            .expect("a live object was collected")
+    }
+
+    /// Turns a compiled function into an object, along with the functions
+    /// nested in it.
+    pub fn load_function(&mut self, function: Rc<Function>) -> ObjRef {
+        let constants = function
+            .chunk
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Constant::Number(number) => Value::number(*number),
+                Constant::String(string) => self.alloc_string(string),
+                Constant::Name(name) => self.alloc_string(name),
+                Constant::Function(function) => Value::obj(self.load_function(function.clone())),
+            })
+            .collect();
+
+        let caches = vec![InlineCache::Empty; function.inline_caches];
+        self.alloc(Obj::Function(FunctionObj {
+            function,
+            constants,
+            caches,
+        }))
+    }
+
+    pub fn shape(&self, shape: ShapeId) -> &Shape {
+        &self.shapes[shape.0 as usize]
+    }
+
+    /// The shape of an instance of shape `from` once it gets the field
+    /// `name`, which goes after its others.
+    pub fn add_field(&mut self, from: ShapeId, name: Symbol) -> ShapeId {
+        if let Some(&to) = self.shape(from).transitions.get(&name) {
+            return to;
+        }
+
+        let mut fields = self.shape(from).fields.clone();
+        fields.insert(name, fields.len());
+        let to = ShapeId(self.shapes.len() as u32);
+        self.shapes.push(Shape {
+            fields,
+            transitions: HashMap::new(),
+        });
+        self.shapes[from.0 as usize].transitions.insert(name, to);
+        to
+    }
+
+    pub fn as_str(&self, value: Value) -> Option<&str> {
+        match self.get(value.as_obj()?) {
+            Obj::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn function(&self, obj: ObjRef) -> &FunctionObj {
+        match self.get(obj) {
+            Obj::Function(function) => function,
+            _ => unreachable!("expected a function"),
+        }
+    }
+
+    pub fn closure(&self, obj: ObjRef) -> &Closure {
+        match self.get(obj) {
+            Obj::Closure(closure) => closure,
+            _ => unreachable!("expected a closure"),
+        }
+    }
+
+    pub fn class(&self, obj: ObjRef) -> &Class {
+        match self.get(obj) {
+            Obj::Class(class) => class,
+            _ => unreachable!("expected a class"),
+        }
+    }
+
+    pub fn class_mut(&mut self, obj: ObjRef) -> &mut Class {
+        match self.get_mut(obj) {
+            Obj::Class(class) => class,
+            _ => unreachable!("expected a class"),
+        }
+    }
+
+    pub fn instance(&self, obj: ObjRef) -> &Instance {
+        match self.get(obj) {
+            Obj::Instance(instance) => instance,
+            _ => unreachable!("expected an instance"),
+        }
+    }
+
+    pub fn instance_mut(&mut self, obj: ObjRef) -> &mut Instance {
+        match self.get_mut(obj) {
+            Obj::Instance(instance) => instance,
+            _ => unreachable!("expected an instance"),
+        }
+    }
+
+    pub fn function_mut(&mut self, obj: ObjRef) -> &mut FunctionObj {
+        match self.get_mut(obj) {
+            Obj::Function(function) => function,
+            _ => unreachable!("expected a function"),
+        }
+    }
+
+    /// Strings are equal if they have the same characters, everything else
+    /// only to itself. Strings are interned, so that's the same thing.
+    pub fn values_equal(&self, left: Value, right: Value) -> bool {
+        match (left.unpack(), right.unpack()) {
+            (Unpacked::Nil, Unpacked::Nil) => true,
+            (Unpacked::Bool(l), Unpacked::Bool(r)) => l == r,
+            (Unpacked::Number(l), Unpacked::Number(r)) => l == r,
+            (Unpacked::Obj(l), Unpacked::Obj(r)) => l == r,
+            _ => false,
+        }
+    }
+
+    /// The same names the tree-walker uses.
+    pub fn type_name(&self, value: Value) -> &'static str {
+        match value.unpack() {
+            Unpacked::Nil => "nil",
+            Unpacked::Bool(_) => "boolean",
+            Unpacked::Number(_) => "number",
+            Unpacked::Obj(obj) => match self.get(obj) {
+                Obj::String(_) => "string",
+                Obj::Class(_) => "class",
+                Obj::Instance(_) => "instance",
+                Obj::Upvalue(_) => "upvalue",
+                Obj::Function(_) | Obj::Native(_) | Obj::Closure(_) | Obj::BoundMethod(_) => {
+                    "function"
+                }
+            },
+        }
+    }
+
+    fn function_name(&self, obj: ObjRef) -> &str {
+        let closure = self.closure(obj);
+        &self.function(closure.function).function.name
+    }
+
+    pub fn display(&self, value: Value) -> Display<'_> {
+        Display { heap: self, value }
+    }
+}
+
+/// Formats a value the way `print` shows it.
+pub struct Display<'a> {
+    heap: &'a Heap,
+    value: Value,
+}
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let obj = match self.value.unpack() {
+            Unpacked::Nil => return write!(f, "nil"),
+            Unpacked::Bool(bool) => return write!(f, "{}", bool),
+            Unpacked::Number(number) => return write!(f, "{}", number),
+            Unpacked::Obj(obj) => obj,
+        };
+
+        match self.heap.get(obj) {
+            Obj::String(string) => write!(f, "{}", string),
+            Obj::Function(function) => write!(f, "<fn {}>", function.function.name),
+            Obj::Native(native) => write!(f, "<native fn {}>", native.name),
+            Obj::Closure(_) => write!(f, "<fn {}>", self.heap.function_name(obj)),
+            Obj::Upvalue(_) => write!(f, "upvalue"),
+            Obj::Class(class) => write!(f, "{}", class.name),
+            Obj::Instance(instance) => {
+                write!(f, "{} instance", self.heap.class(instance.class).name)
+            }
+            Obj::BoundMethod(bound) => write!(f, "<fn {}>", self.heap.function_name(bound.method)),
+        }
+    }
+}
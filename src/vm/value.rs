@@ -0,0 +1,158 @@
+//! The VM's runtime values, in one of two representations picked at build
+//! time. By default a `Value` is an enum, a tag next to its payload. With
+//! the `nan-boxing` feature it's packed into the 64 bits of an `f64`:
+//!
+//! - numbers are stored as themselves;
+//! - `nil`, `true` and `false` are quiet NaNs with a tag in the low bits;
+//! - objects are quiet NaNs with the sign bit set and the object's heap
+//!   index in the low bits.
+//!
+//! Both have the same interface, so code outside this module can't tell
+//! them apart. Build or test with `--features nan-boxing` to compare them.
+
+use std::fmt;
+
+use super::object::ObjRef;
+
+/// What a `Value` holds, taken out so it can be matched on.
+#[derive(Debug, Clone, Copy)]
+pub enum Unpacked {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+pub use tagged::Value;
+
+#[cfg(feature = "nan-boxing")]
+pub use nan_boxed::Value;
+
+#[cfg(not(feature = "nan-boxing"))]
+mod tagged {
+    use super::{ObjRef, Unpacked};
+
+    #[derive(Clone, Copy)]
+    pub struct Value(Unpacked);
+
+    impl Value {
+        pub const NIL: Value = Value(Unpacked::Nil);
+
+        pub fn bool(bool: bool) -> Value {
+            Value(Unpacked::Bool(bool))
+        }
+
+        pub fn number(number: f64) -> Value {
+            Value(Unpacked::Number(number))
+        }
+
+        pub fn obj(obj: ObjRef) -> Value {
+            Value(Unpacked::Obj(obj))
+        }
+
+        pub fn unpack(self) -> Unpacked {
+            self.0
+        }
+
+        pub fn as_number(self) -> Option<f64> {
+            match self.0 {
+                Unpacked::Number(number) => Some(number),
+                _ => None,
+            }
+        }
+
+        pub fn as_obj(self) -> Option<ObjRef> {
+            match self.0 {
+                Unpacked::Obj(obj) => Some(obj),
+                _ => None,
+            }
+        }
+
+        pub fn is_truthy(self) -> bool {
+            !matches!(self.0, Unpacked::Nil | Unpacked::Bool(false))
+        }
+    }
+}
+
+#[cfg(feature = "nan-boxing")]
+mod nan_boxed {
+    use super::{ObjRef, Unpacked};
+
+    /// The exponent bits, the quiet bit, and one more so that no NaN the
+    /// hardware produces looks like a boxed value.
+    const QNAN: u64 = 0x7ffc_0000_0000_0000;
+    const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+
+    const TAG_NIL: u64 = 1;
+    const TAG_FALSE: u64 = 2;
+    const TAG_TRUE: u64 = 3;
+
+    #[derive(Clone, Copy)]
+    pub struct Value(u64);
+
+    impl Value {
+        pub const NIL: Value = Value(QNAN | TAG_NIL);
+        const FALSE: Value = Value(QNAN | TAG_FALSE);
+        const TRUE: Value = Value(QNAN | TAG_TRUE);
+
+        pub fn bool(bool: bool) -> Value {
+            match bool {
+                true => Value::TRUE,
+                false => Value::FALSE,
+            }
+        }
+
+        pub fn number(number: f64) -> Value {
+            // A NaN carrying a payload could pass for a boxed value, so
+            // every NaN is stored as the one that can't
+            match number.is_nan() {
+                true => Value(f64::NAN.to_bits()),
+                false => Value(number.to_bits()),
+            }
+        }
+
+        pub fn obj(obj: ObjRef) -> Value {
+            Value(SIGN_BIT | QNAN | obj.0 as u64)
+        }
+
+        pub fn unpack(self) -> Unpacked {
+            if let Some(number) = self.as_number() {
+                return Unpacked::Number(number);
+            }
+            if let Some(obj) = self.as_obj() {
+                return Unpacked::Obj(obj);
+            }
+            match self.0 & !QNAN {
+                TAG_NIL => Unpacked::Nil,
+                TAG_FALSE => Unpacked::Bool(false),
+                TAG_TRUE => Unpacked::Bool(true),
+                bits => unreachable!("{:#x} isn't a value", bits),
+            }
+        }
+
+        pub fn as_number(self) -> Option<f64> {
+            match self.0 & QNAN == QNAN {
+                true => None,
+                false => Some(f64::from_bits(self.0)),
+            }
+        }
+
+        pub fn as_obj(self) -> Option<ObjRef> {
+            match self.0 & (SIGN_BIT | QNAN) == SIGN_BIT | QNAN {
+                true => Some(ObjRef(self.0 as u32)),
+                false => None,
+            }
+        }
+
+        pub fn is_truthy(self) -> bool {
+            self.0 != Value::NIL.0 && self.0 != Value::FALSE.0
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.unpack().fmt(f)
+    }
+}
@@ -0,0 +1,115 @@
+use rlox::ast_dot::ASTDotVisitor;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+
+fn dot(source: &str) -> String {
+    let tokens = Scanner::new(source).scan_tokens().unwrap();
+    ASTDotVisitor::new().render(&Parser::new(tokens).parse().unwrap())
+}
+
+fn labels(dot: &str) -> Vec<&str> {
+    dot.lines()
+        .filter_map(|line| {
+            line.split_once(" [label=\"")
+                .filter(|_| !line.contains("->"))
+        })
+        .map(|(_, label)| label.trim_end_matches("\"];"))
+        .collect()
+}
+
+#[test]
+fn renders_nodes_and_labelled_edges() {
+    assert_eq!(
+        dot("var a = (1);\nif (a)\n  print a;\nwhile (false) {\n  a;\n}\n"),
+        r#"digraph AST {
+  node [shape=box, fontname="monospace"];
+  n0 [label="Program"];
+  n1 [label="Var a\nline 1"];
+  n2 [label="Grouping\nline 1"];
+  n3 [label="Literal 1\nline 1"];
+  n4 [label="If\nline 2"];
+  n5 [label="Variable a\nline 2"];
+  n6 [label="Print\nline 3"];
+  n7 [label="Variable a\nline 3"];
+  n8 [label="While\nline 4"];
+  n9 [label="Literal false\nline 4"];
+  n10 [label="Block\nline 4"];
+  n11 [label="Expression\nline 5"];
+  n12 [label="Variable a\nline 5"];
+  n2 -> n3 [label="expression"];
+  n1 -> n2 [label="initializer"];
+  n0 -> n1 [label="stmt 0"];
+  n4 -> n5 [label="condition"];
+  n6 -> n7 [label="expression"];
+  n4 -> n6 [label="then"];
+  n0 -> n4 [label="stmt 1"];
+  n8 -> n9 [label="condition"];
+  n11 -> n12 [label="expression"];
+  n10 -> n11 [label="stmt 0"];
+  n8 -> n10 [label="body"];
+  n0 -> n8 [label="stmt 2"];
+}
+"#
+    );
+}
+
+#[test]
+fn every_node_but_the_root_has_a_line() {
+    let source = "class A < B {\n  m(x) { return super.m(this.y = -x); }\n}\n\
+                  fun f() { print \"s\" + nil or true and !f(); }\n\
+                  {\n  var c;\n  c = (1 / 2);\n}\n";
+    let dot = dot(source);
+    let labels = labels(&dot);
+
+    assert_eq!(labels[0], "Program");
+    for label in &labels[1..] {
+        assert!(label.contains("\\nline "), "{}", label);
+    }
+}
+
+#[test]
+fn desugared_for_loops_take_the_line_of_the_for() {
+    let dot = dot("var i;\nfor (i = 0;\n     i < 3;\n     i = i + 1)\n  print i;\n");
+
+    assert_eq!(
+        labels(&dot),
+        [
+            "Program",
+            "Var i\\nline 1",
+            "Block\\nline 2",
+            "Expression\\nline 2",
+            "Assign i\\nline 2",
+            "Literal 0\\nline 2",
+            "While\\nline 2",
+            "Binary <\\nline 3",
+            "Variable i\\nline 3",
+            "Literal 3\\nline 3",
+            "Block\\nline 2",
+            "Print\\nline 5",
+            "Variable i\\nline 5",
+            "Expression\\nline 4",
+            "Assign i\\nline 4",
+            "Binary +\\nline 4",
+            "Variable i\\nline 4",
+            "Literal 1\\nline 4",
+        ]
+    );
+}
+
+#[test]
+fn infinite_for_loops_get_a_true_condition_on_the_line_of_the_for() {
+    let dot = dot("\nfor (;;) print 1;\n");
+
+    assert!(dot.contains("[label=\"Literal true\\nline 2\"]"), "{}", dot);
+}
+
+#[test]
+fn labels_are_escaped() {
+    let dot = dot("print \"a\\b\nc\";");
+
+    assert!(
+        dot.contains(r#"[label="Literal \"a\\\\b\\nc\"\nline "#),
+        "{}",
+        dot
+    );
+}
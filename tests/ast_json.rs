@@ -0,0 +1,235 @@
+use std::fs;
+
+use serde_json::{json, Value};
+
+use rlox::ast_json::{statements_from_json, statements_to_json, tokens_from_json, tokens_to_json};
+use rlox::ast_printer::ASTStringVisitor;
+use rlox::ast_reader::ASTReader;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+use rlox::syntax::{Expr, LiteralExpr, LiteralValue, Stmt};
+use rlox::token::{Token, TokenType};
+
+fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens().unwrap();
+    Parser::new(tokens).parse().unwrap()
+}
+
+fn print(statements: &[Stmt]) -> String {
+    ASTStringVisitor { statements }.to_string()
+}
+
+fn value(json: &str) -> Value {
+    serde_json::from_str(json).unwrap()
+}
+
+fn printed_literal(statement: &Stmt) -> f64 {
+    match statement {
+        Stmt::Print(print) => match &print.expression {
+            Expr::Literal(LiteralExpr {
+                value: LiteralValue::Float(number),
+                ..
+            }) => *number,
+            other => panic!("not a number literal: {:?}", other),
+        },
+        other => panic!("not a print statement: {:?}", other),
+    }
+}
+
+#[test]
+fn tokens_are_written_with_their_kind_value_and_line() {
+    let tokens = Scanner::new("print x +\n1.5;").scan_tokens().unwrap();
+
+    assert_eq!(
+        value(&tokens_to_json(&tokens)),
+        json!({
+            "version": 1,
+            "tokens": [
+                {"kind": "Print", "line": 1},
+                {"kind": "Identifier", "value": "x", "line": 1},
+                {"kind": "Plus", "line": 1},
+                {"kind": "Number", "value": 1.5, "line": 2},
+                {"kind": "Semicolon", "line": 2},
+                {"kind": "Eof", "line": 2},
+            ]
+        })
+    );
+}
+
+#[test]
+fn tokens_are_read_back() {
+    let tokens = tokens_from_json(
+        r#"{"version": 1, "tokens": [
+            {"kind": "Var", "line": 1},
+            {"kind": "Identifier", "value": "s", "line": 1},
+            {"kind": "Equal", "line": 1},
+            {"kind": "LoxString", "value": "hi", "line": 2},
+            {"kind": "Semicolon", "line": 2},
+            {"kind": "Eof", "line": 2}
+        ]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        print(&Parser::new(tokens).parse().unwrap()),
+        "(var s \"hi\")"
+    );
+}
+
+#[test]
+fn statements_are_written_as_tagged_nodes() {
+    let statements = parse("if (a) print -1; else b = nil;");
+
+    assert_eq!(
+        value(&statements_to_json(&statements)),
+        json!({
+            "version": 1,
+            "statements": [{
+                "type": "If",
+                "condition": {"type": "Variable", "name": {"kind": "Identifier", "value": "a", "line": 1}},
+                "then_branch": {
+                    "type": "Print",
+                    "expression": {
+                        "type": "Unary",
+                        "operator": {"kind": "Minus", "line": 1},
+                        "right": {"type": "Literal", "value": 1.0, "line": 1},
+                    },
+                    "line": 1,
+                },
+                "else_branch": {
+                    "type": "Expression",
+                    "expression": {
+                        "type": "Assign",
+                        "name": {"kind": "Identifier", "value": "b", "line": 1},
+                        "value": {"type": "Literal", "value": null, "line": 1},
+                    },
+                    "line": 1,
+                },
+                "line": 1,
+            }]
+        })
+    );
+}
+
+#[test]
+fn statements_are_read_back_for_every_test_program() {
+    let mut paths: Vec<_> = fs::read_dir("tests/programs")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let statements = parse(&fs::read_to_string(&path).unwrap());
+        let read = statements_from_json(&statements_to_json(&statements))
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+
+        assert_eq!(print(&read), print(&statements), "{}", path.display());
+    }
+}
+
+#[test]
+fn other_schema_versions_are_rejected() {
+    assert_eq!(
+        statements_from_json(r#"{"version": 2, "statements": []}"#)
+            .unwrap_err()
+            .to_string(),
+        "JSON Error: Unsupported schema version 2, expecting 1"
+    );
+    assert_eq!(
+        tokens_from_json(r#"{"version": 0, "tokens": []}"#)
+            .unwrap_err()
+            .to_string(),
+        "JSON Error: Unsupported schema version 0, expecting 1"
+    );
+}
+
+#[test]
+fn missing_lines_are_read_as_zero() {
+    let statements = statements_from_json(
+        r#"{"version": 1, "statements": [
+            {"type": "Print", "expression": {"type": "Literal", "value": "old"}}
+        ]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        value(&statements_to_json(&statements))["statements"][0],
+        json!({"type": "Print", "expression": {"type": "Literal", "value": "old", "line": 0}, "line": 0})
+    );
+}
+
+#[test]
+fn malformed_documents_are_rejected() {
+    for json in [
+        "",
+        r#"{"statements": []}"#,
+        r#"{"version": 1, "tokens": [{"kind": "Bogus", "line": 1}]}"#,
+    ] {
+        let err = tokens_from_json(json)
+            .map(|_| ())
+            .and(statements_from_json(json).map(|_| ()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("JSON Error: "), "{}", err);
+    }
+}
+
+#[test]
+fn non_finite_literals_survive_a_round_trip() {
+    let statements = ASTReader::new("(print #nan)\n(print #inf)\n(print #-inf)\n(print 2)")
+        .read_statements()
+        .unwrap();
+    let json = statements_to_json(&statements);
+
+    assert_eq!(
+        value(&json)["statements"],
+        json!([
+            {"type": "Print", "expression": {"type": "Literal", "value": {"number": "NaN"}, "line": 1}, "line": 1},
+            {"type": "Print", "expression": {"type": "Literal", "value": {"number": "Infinity"}, "line": 2}, "line": 2},
+            {"type": "Print", "expression": {"type": "Literal", "value": {"number": "-Infinity"}, "line": 3}, "line": 3},
+            {"type": "Print", "expression": {"type": "Literal", "value": 2.0, "line": 4}, "line": 4},
+        ])
+    );
+
+    let read = statements_from_json(&json).unwrap();
+    assert!(printed_literal(&read[0]).is_nan());
+    assert_eq!(printed_literal(&read[1]), f64::INFINITY);
+    assert_eq!(printed_literal(&read[2]), f64::NEG_INFINITY);
+    assert_eq!(printed_literal(&read[3]), 2.0);
+}
+
+#[test]
+fn non_finite_number_tokens_survive_a_round_trip() {
+    let tokens = vec![
+        Token::new(TokenType::Number(f64::INFINITY), 1),
+        Token::new(TokenType::Number(f64::NAN), 1),
+    ];
+    let json = tokens_to_json(&tokens);
+
+    assert_eq!(
+        value(&json)["tokens"],
+        json!([
+            {"kind": "Number", "value": {"number": "Infinity"}, "line": 1},
+            {"kind": "Number", "value": {"number": "NaN"}, "line": 1},
+        ])
+    );
+
+    let read = tokens_from_json(&json).unwrap();
+    assert_eq!(read[0].token_type, TokenType::Number(f64::INFINITY));
+    assert!(matches!(read[1].token_type, TokenType::Number(n) if n.is_nan()));
+}
+
+#[test]
+fn unknown_non_finite_numbers_are_rejected() {
+    let err = statements_from_json(
+        r#"{"version": 1, "statements": [
+            {"type": "Print", "expression": {"type": "Literal", "value": {"number": "huge"}}}
+        ]}"#,
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().starts_with("JSON Error: "), "{}", err);
+}
@@ -0,0 +1,143 @@
+use std::fs;
+
+use rlox::ast_printer::ASTStringVisitor;
+use rlox::ast_reader::ASTReader;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+use rlox::syntax::Stmt;
+
+fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens().unwrap();
+    Parser::new(tokens).parse().unwrap()
+}
+
+fn print(statements: &[Stmt]) -> String {
+    ASTStringVisitor { statements }.to_string()
+}
+
+fn read_error(source: &str) -> String {
+    ASTReader::new(source)
+        .read_statements()
+        .map(|statements| print(&statements))
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn printer_writes_one_form_per_statement() {
+    let source = "var a = 1;
+fun f(x) { return x * (2 + -a); }
+if (a < 2) print \"hi\\n\"; else { print f(3); }
+class B < A { init() { this.x = super.m(); } }
+while (!true) a = nil;
+";
+
+    assert_eq!(
+        print(&parse(source)),
+        "(var a 1)
+(fun f (x) (return (* x (group (+ 2 (- a))))))
+(if (< a 2) (print \"hi\\\\n\") (block (print (call f 3))))
+(class B (< A) (fun init () (expr (set this x (call (super m))))))
+(while (! true) (expr (assign a nil)))"
+    );
+}
+
+#[test]
+fn printer_spells_out_non_finite_numbers() {
+    let statements = ASTReader::new("(print #inf) (print #-inf) (print #nan) (print 1.5)")
+        .read_statements()
+        .unwrap();
+
+    assert_eq!(
+        print(&statements),
+        "(print #inf)\n(print #-inf)\n(print #nan)\n(print 1.5)"
+    );
+}
+
+#[test]
+fn reader_reads_back_what_the_printer_wrote() {
+    let mut paths: Vec<_> = fs::read_dir("tests/programs")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let printed = print(&parse(&fs::read_to_string(&path).unwrap()));
+        let read = ASTReader::new(&printed)
+            .read_statements()
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+
+        assert_eq!(print(&read), printed, "{}", path.display());
+    }
+}
+
+#[test]
+fn reader_reads_a_single_expression() {
+    let expr = ASTReader::new("(call (get a b) \"c\" 2)")
+        .read_expression()
+        .unwrap();
+    let tokens = Scanner::new("a.b(\"c\", 2)").scan_tokens().unwrap();
+
+    assert_eq!(
+        format!("{:?}", expr),
+        format!("{:?}", Parser::new(tokens).parse_expression().unwrap())
+    );
+}
+
+#[test]
+fn reader_rejects_unbalanced_parentheses() {
+    assert_eq!(
+        read_error("(print\n(+ 1 2)"),
+        "Reader Error: Unexpected end of input at line 2"
+    );
+    assert_eq!(
+        read_error("(print 1))"),
+        "Reader Error: Unbalanced ')' at line 1"
+    );
+}
+
+#[test]
+fn reader_rejects_bad_strings_and_numbers() {
+    assert_eq!(
+        read_error("(print \"abc)"),
+        "Reader Error: Unterminated string at line 1"
+    );
+    assert_eq!(
+        read_error("(print \"a\\qb\")"),
+        "Reader Error: Invalid escape sequence \\q at line 1"
+    );
+    assert_eq!(
+        read_error("(print #huge)"),
+        "Reader Error: Invalid number #huge at line 1"
+    );
+}
+
+#[test]
+fn reader_rejects_unknown_and_malformed_forms() {
+    assert_eq!(
+        read_error("(loop 1)"),
+        "Reader Error: Unknown form (loop ...) at line 1"
+    );
+    assert_eq!(
+        read_error("\n(print 1 2)"),
+        "Reader Error: Wrong number or kind of arguments to (print ...) at line 2"
+    );
+    assert_eq!(
+        read_error("(var 1 2)"),
+        "Reader Error: Expecting a name at line 1"
+    );
+    assert_eq!(
+        read_error("(fun f (a class))"),
+        "Reader Error: Expecting a name at line 1"
+    );
+    assert_eq!(
+        ASTReader::new("1 2")
+            .read_expression()
+            .unwrap_err()
+            .to_string(),
+        "Reader Error: Wrong number or kind of arguments to (expression ...) at line 1"
+    );
+}
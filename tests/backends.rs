@@ -0,0 +1,153 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn run(flag: &str, script: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(flag)
+        .arg(script)
+        .output()
+        .expect("rlox should start")
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut scripts: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty());
+    scripts
+}
+
+fn assert_same(expected: &Output, actual: &Output, script: &Path) {
+    let name = script.display();
+    assert_eq!(
+        String::from_utf8_lossy(&expected.stdout),
+        String::from_utf8_lossy(&actual.stdout),
+        "stdout of {}",
+        name
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&expected.stderr),
+        String::from_utf8_lossy(&actual.stderr),
+        "stderr of {}",
+        name
+    );
+    assert_eq!(
+        expected.status.code(),
+        actual.status.code(),
+        "exit code of {}",
+        name
+    );
+}
+
+/// Every program in tests/programs has to behave the same on both backends:
+/// the same output, the same errors and the same exit code.
+#[test]
+fn backends_agree_on_every_program() {
+    for script in programs() {
+        let tree = run("--backend=tree", &script);
+        let vm = run("--backend=vm", &script);
+        assert_same(&tree, &vm, &script);
+    }
+}
+
+/// Collecting on every allocation frees anything the VM forgot to root, which
+/// shows up as a different result or a crash.
+#[test]
+fn gc_stress_doesnt_change_any_program() {
+    for script in programs() {
+        let vm = run("--backend=vm", &script);
+        let stressed = run("--gc-stress", &script);
+        assert_same(&vm, &stressed, &script);
+    }
+}
+
+#[test]
+fn vm_reports_script_arguments() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--backend=vm", "-", "a", "b"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"print argc(); print arg(1); print arg(2);")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\nb\nnil\n");
+}
+
+#[test]
+fn eval_passes_the_arguments_after_it_to_the_code() {
+    for backend in ["--backend=tree", "--backend=vm"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .args([
+                backend,
+                "-e",
+                "print argc(); print arg(0); print arg(1);",
+                "a",
+                "-b",
+            ])
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(0), "{:?}", output);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "2\na\n-b\n");
+    }
+}
+
+#[test]
+fn vm_runs_deep_recursion_until_the_stack_overflows() {
+    // Not `return f(n + 1);`: that's a tail call, which runs forever in the
+    // same frame
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args([
+            "--backend=vm",
+            "-e",
+            "fun f(n) { return 1 + f(n + 1); } f(0);",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Runtime Error: Stack overflow at line 1\n    in f() at line 1"));
+}
+
+#[test]
+fn both_backends_stop_deep_recursion_at_the_same_depth_with_a_short_trace() {
+    for backend in ["--backend=tree", "--backend=vm"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .args([
+                backend,
+                "-e",
+                "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); }\nprint f(4000);\nf(100000);",
+            ])
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(70), "{}", backend);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "4000\n");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let frame = "    in f() at line 1\n";
+        assert_eq!(
+            stderr,
+            format!(
+                "Runtime Error: Stack overflow at line 1\n{}    ... 4075 more calls\n{}    in script at line 3\n",
+                frame.repeat(10),
+                frame.repeat(10)
+            ),
+            "{}",
+            backend
+        );
+    }
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::process::Output;
+
+use common::rlox;
+
+fn disasm(source: &str) -> Output {
+    rlox(&["disasm", "-"], source)
+}
+
+#[test]
+fn disasm_lists_instructions_with_lines_and_constants() {
+    let output = disasm("var a = 1;\nprint a + 2;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "== script ==\n\
+         0000    1 Constant            0 '1'\n\
+         0003    | DefineGlobal        1 'a'\n\
+         0006    2 GetGlobal           1 'a'\n\
+         0009    | Constant            2 '2'\n\
+         0012    | Add\n\
+         0013    | Print\n\
+         0014    | Nil\n\
+         0015    | Return\n"
+    );
+}
+
+#[test]
+fn disasm_lists_nested_functions_and_their_upvalues() {
+    let output = disasm("fun outer() {\n  var x = 1;\n  fun inner() { return x; }\n}\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Closure             1 <fn inner>\n"));
+    assert!(stdout.contains("|                     local 1\n"));
+    assert!(stdout.contains("\n== outer ==\n"));
+    assert!(stdout.contains("\n== inner ==\n"));
+    assert!(stdout.contains("GetUpvalue          0\n"));
+}
+
+#[test]
+fn disasm_reports_compile_errors() {
+    let output = disasm("print (;");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn trace_shows_the_stack_before_each_instruction() {
+    let output = rlox(&["--trace", "-e", "var a = 1; print a + 2;"], "");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("          [ <fn script> ][ 1 ][ 2 ]\n0012    | Add\n"));
+    assert!(stderr.contains("          [ <fn script> ][ 3 ]\n0013    | Print\n"));
+}
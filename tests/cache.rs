@@ -0,0 +1,246 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use common::{rlox, SharedBuffer};
+use rlox::vm::cache::{self, CacheError};
+use rlox::vm::chunk::{Chunk, Constant, Function, OpCode};
+use rlox::vm::{self, Vm};
+
+const SOURCE: &str = "fun twice(f, x) { return f(f(x)); }
+fun add(n) { fun adder(x) { return x + n; } return adder; }
+print twice(add(\"!\"), \"hi\");
+";
+
+/// A scratch directory of its own for each test, so they can run in
+/// parallel.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rlox-cache-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn script(body: Vec<u8>, constants: Vec<Constant>) -> Function {
+    let mut chunk = Chunk::new();
+    for byte in body {
+        chunk.write(byte, 1);
+    }
+    chunk.constants = constants;
+    Function {
+        name: "script".to_string(),
+        arity: 0,
+        upvalue_count: 0,
+        inline_caches: 0,
+        chunk,
+    }
+}
+
+#[test]
+fn bytecode_survives_a_round_trip() {
+    let function = vm::compile(SOURCE).unwrap();
+    let bytes = cache::encode(&function, SOURCE);
+    assert_eq!(&bytes[..4], b"LOXC");
+
+    let loaded = cache::decode(&bytes, Some(SOURCE)).unwrap();
+    assert_eq!(
+        vm::disassembler::disassemble(&loaded),
+        vm::disassembler::disassemble(&function)
+    );
+
+    let output = SharedBuffer::default();
+    let mut vm = Vm::new();
+    vm.set_output(output.clone());
+    vm.interpret(loaded).unwrap();
+    assert_eq!(output.contents(), "hi!!\n");
+}
+
+#[test]
+fn a_different_source_makes_the_cache_stale() {
+    let function = vm::compile(SOURCE).unwrap();
+    let bytes = cache::encode(&function, SOURCE);
+
+    assert_eq!(
+        cache::decode(&bytes, Some("print 1;")).unwrap_err(),
+        CacheError::Stale
+    );
+    assert!(cache::decode(&bytes, None).is_ok());
+}
+
+#[test]
+fn damaged_files_are_rejected() {
+    let function = vm::compile(SOURCE).unwrap();
+    let bytes = cache::encode(&function, SOURCE);
+
+    for len in 0..bytes.len() {
+        assert!(
+            cache::decode(&bytes[..len], None).is_err(),
+            "cut at {}",
+            len
+        );
+    }
+    // Bytes 6 to 13 are the source hash, only checked against a source
+    for i in (0..6).chain(14..bytes.len()) {
+        let mut damaged = bytes.clone();
+        damaged[i] ^= 0x40;
+        assert!(cache::decode(&damaged, None).is_err(), "byte {} flipped", i);
+    }
+
+    assert_eq!(
+        cache::decode(&bytes[..bytes.len() - 1], None).unwrap_err(),
+        CacheError::Truncated
+    );
+    let mut newer = bytes.clone();
+    newer[4] = 99;
+    assert_eq!(
+        cache::decode(&newer, None).unwrap_err(),
+        CacheError::UnsupportedVersion(99)
+    );
+}
+
+#[test]
+fn bytecode_the_vm_cant_run_is_rejected() {
+    let nil = OpCode::Nil as u8;
+    let ret = OpCode::Return as u8;
+    let invalid = [
+        // An unknown opcode
+        script(vec![250, nil, ret], vec![]),
+        // A constant that isn't there
+        script(vec![OpCode::Constant as u8, 0, 3, ret], vec![]),
+        // A global whose name is a number
+        script(
+            vec![OpCode::GetGlobal as u8, 0, 0, ret],
+            vec![Constant::Number(1.0)],
+        ),
+        // A jump past the end
+        script(vec![OpCode::Jump as u8, 0, 9, nil, ret], vec![]),
+        // A jump into the middle of an instruction
+        script(
+            vec![OpCode::Jump as u8, 0, 1, OpCode::Constant as u8, 0, 0, ret],
+            vec![Constant::Number(1.0)],
+        ),
+        // Running off the end
+        script(vec![nil, OpCode::Print as u8], vec![]),
+        // An upvalue the script doesn't have
+        script(vec![OpCode::GetUpvalue as u8, 0, ret], vec![]),
+        // A local past the top of the stack
+        script(vec![OpCode::GetLocal as u8, 1, ret], vec![]),
+        script(vec![nil, OpCode::SetLocal as u8, 7, ret], vec![]),
+        // Popping more than was pushed
+        script(vec![OpCode::Add as u8, ret], vec![]),
+        script(vec![OpCode::Call as u8, 3, ret], vec![]),
+        // Paths that meet with different amounts on the stack
+        script(vec![nil, OpCode::JumpIfFalse as u8, 0, 1, nil, ret], vec![]),
+        // Capturing a local that isn't there
+        script(
+            vec![OpCode::Closure as u8, 0, 0, 1, 4, ret],
+            vec![Constant::Function(Rc::new(Function {
+                upvalue_count: 1,
+                ..script(vec![nil, ret], vec![])
+            }))],
+        ),
+    ];
+
+    for function in invalid {
+        let bytes = cache::encode(&function, "");
+        assert!(
+            matches!(cache::decode(&bytes, None), Err(CacheError::Invalid(_))),
+            "{}",
+            vm::disassembler::disassemble(&function)
+        );
+    }
+}
+
+#[test]
+fn a_hand_made_loxc_that_would_crash_the_vm_is_reported() {
+    let dir = scratch("crafted");
+    let crafted = dir.join("crafted.loxc");
+    let function = script(
+        vec![
+            OpCode::GetLocal as u8,
+            200,
+            OpCode::Print as u8,
+            OpCode::Nil as u8,
+            OpCode::Return as u8,
+        ],
+        vec![],
+    );
+    fs::write(&crafted, cache::encode(&function, "")).unwrap();
+
+    let output = rlox(&[crafted.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Cache Error: Invalid bytecode: missing local at 0 in script\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compile_writes_a_cache_the_vm_keeps_up_to_date() {
+    let dir = scratch("fresh");
+    let script = dir.join("script.lox");
+    let cached = dir.join("script.loxc");
+    fs::write(&script, SOURCE).unwrap();
+
+    let output = rlox(&["compile", script.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(cached.exists());
+
+    let output = rlox(&["--backend=vm", script.to_str().unwrap()], "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi!!\n");
+
+    fs::write(&script, "print \"edited\";").unwrap();
+    let output = rlox(&["--backend=vm", script.to_str().unwrap()], "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "edited\n");
+
+    // The stale cache was rebuilt from the new source
+    let bytes = fs::read(&cached).unwrap();
+    assert!(cache::decode(&bytes, Some("print \"edited\";")).is_ok());
+
+    let output = rlox(&[cached.to_str().unwrap()], "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "edited\n");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_corrupted_cache_is_rebuilt_and_a_corrupted_loxc_reported() {
+    let dir = scratch("corrupt");
+    let script = dir.join("script.lox");
+    let cached = dir.join("script.loxc");
+    fs::write(&script, SOURCE).unwrap();
+    fs::write(&cached, b"LOXC\x01\x00garbage").unwrap();
+
+    let output = rlox(&["--backend=vm", script.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi!!\n");
+    assert!(cache::decode(&fs::read(&cached).unwrap(), Some(SOURCE)).is_ok());
+
+    let bytes = fs::read(&cached).unwrap();
+    fs::write(&cached, &bytes[..bytes.len() / 2]).unwrap();
+    let output = rlox(&[cached.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Cache Error: File is truncated\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compile_reports_errors_without_writing_anything() {
+    let dir = scratch("error");
+    let script = dir.join("script.lox");
+    fs::write(&script, "print (;").unwrap();
+
+    let output = rlox(&["compile", script.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(!dir.join("script.loxc").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
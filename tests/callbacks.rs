@@ -0,0 +1,142 @@
+use rlox::interpreter::{Interpreter, TracedError};
+use rlox::{Engine, Error, Value};
+
+fn runtime_error<T: std::fmt::Debug>(result: Result<T, Error>) -> TracedError {
+    match result {
+        Err(Error::Runtime(err)) => err,
+        result => panic!("expected a runtime error, got {:?}", result),
+    }
+}
+
+#[test]
+fn calls_global_functions_with_converted_arguments() {
+    let mut engine = Engine::new();
+    engine
+        .run("fun add(a, b) { return a + b; } fun shout(s) { return s + \"!\"; }")
+        .unwrap();
+
+    let sum: f64 = engine.call_global("add", (1.0, 2.0)).unwrap();
+    assert_eq!(sum, 3.0);
+    let shouted: String = engine.call_global("shout", ("hey",)).unwrap();
+    assert_eq!(shouted, "hey!");
+    let value: Value = engine
+        .call_global("add", vec![Value::Number(2.0), Value::Number(3.0)])
+        .unwrap();
+    assert_eq!(value, Value::Number(5.0));
+}
+
+#[test]
+fn calls_methods_on_instances() {
+    let mut engine = Engine::new();
+    engine
+        .run(
+            "class Counter {
+                init() { this.count = 0; }
+                add(n) { this.count = this.count + n; return this.count; }
+            }
+            var counter = Counter();",
+        )
+        .unwrap();
+
+    let counter = engine.get_global("counter").unwrap();
+    let _: f64 = engine.call_method(&counter, "add", (2.0,)).unwrap();
+    let count: f64 = engine.call_method(&counter, "add", (3.0,)).unwrap();
+    assert_eq!(count, 5.0);
+    assert_eq!(engine.eval("counter.count").unwrap(), Value::Number(5.0));
+}
+
+#[test]
+fn reports_bad_host_calls() {
+    let mut engine = Engine::new();
+    engine
+        .run("fun one() { return 1; } var answer = 42;")
+        .unwrap();
+
+    let err = runtime_error(engine.call_global::<Value>("missing", ()));
+    assert_eq!(err.to_string(), "Runtime Error: Undefined global 'missing'");
+    let err = runtime_error(engine.call_global::<Value>("answer", ()));
+    assert_eq!(err.to_string(), "Runtime Error: Can't call number");
+    let err = runtime_error(engine.call_global::<Value>("one", (1.0,)));
+    assert_eq!(
+        err.to_string(),
+        "Runtime Error: 'one' can't take 1 arguments"
+    );
+    let err = runtime_error(engine.call_global::<String>("one", ()));
+    assert_eq!(
+        err.to_string(),
+        "Runtime Error: Expected 'one' to return a string, got number"
+    );
+    let answer = engine.get_global("answer").unwrap();
+    let err = runtime_error(engine.call_method::<Value>(&answer, "go", ()));
+    assert_eq!(
+        err.to_string(),
+        "Runtime Error: Only instances have methods, got number"
+    );
+}
+
+#[test]
+fn errors_carry_a_stack_trace() {
+    let mut engine = Engine::new();
+    engine
+        .run("fun inner() { return nil + 1; }\nfun outer() {\n  return inner();\n}")
+        .unwrap();
+
+    let err = runtime_error(engine.call_global::<Value>("outer", ()));
+    let functions: Vec<&str> = err
+        .trace
+        .iter()
+        .map(|frame| frame.function.as_str())
+        .collect();
+    assert_eq!(functions, ["inner", "outer"]);
+    assert_eq!(err.trace[1].called_from, None);
+    assert_eq!(
+        err.to_string(),
+        "Runtime Error: Operands of + must be two numbers or two strings at line 1\n    \
+         in inner() at line 1\n    \
+         in outer() at line 3"
+    );
+
+    // The session is still usable afterwards
+    assert_eq!(engine.eval("1 + 1").unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn natives_can_call_back_into_lox() {
+    let mut engine = Engine::new();
+    engine.register(
+        "apply",
+        |interpreter: &mut Interpreter, function: Value, x: f64| {
+            interpreter.call::<f64>(&function, (x,))
+        },
+    );
+    engine
+        .run("fun twice(x) { return x * 2; }\nfun local() { var n = 10; return apply(twice, n) + n; }")
+        .unwrap();
+
+    assert_eq!(engine.eval("local()").unwrap(), Value::Number(30.0));
+}
+
+#[test]
+fn errors_from_callbacks_keep_their_trace() {
+    let mut engine = Engine::new();
+    engine.register(
+        "each",
+        |interpreter: &mut Interpreter, function: Value, items: Vec<Value>| {
+            for item in items {
+                interpreter.call::<Value>(&function, (item,))?;
+            }
+            Ok::<_, TracedError>(())
+        },
+    );
+
+    let err = runtime_error(
+        engine.run("fun check(n) {\n  if (n > 1) return -\"big\";\n}\neach(check, 1, 2);"),
+    );
+    assert_eq!(
+        err.to_string(),
+        "Runtime Error: Operand of - must be a number at line 2\n    \
+         in check() at line 2\n    \
+         in each()\n    \
+         in script at line 4"
+    );
+}
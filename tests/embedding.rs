@@ -0,0 +1,106 @@
+mod common;
+
+use std::thread;
+
+use common::SharedBuffer;
+use rlox::interpreter::RuntimeError;
+use rlox::{Engine, Error, Value};
+
+#[test]
+fn eval_returns_the_value_of_an_expression() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.eval("1 + 2").unwrap(), Value::Number(3.0));
+    assert_eq!(
+        engine.eval("var a = \"lo\"; \"hel\" + a;").unwrap(),
+        Value::LoxString("hello".into())
+    );
+    assert_eq!(engine.eval("var b = 1;").unwrap(), Value::Nil);
+}
+
+#[test]
+fn globals_persist_between_calls() {
+    let mut engine = Engine::new();
+    engine.eval("fun double(n) { return n * 2; }").unwrap();
+    assert_eq!(engine.eval("double(21)").unwrap(), Value::Number(42.0));
+}
+
+#[test]
+fn globals_are_shared_with_the_host() {
+    let mut engine = Engine::new();
+    engine.set_global("name", "world");
+    engine.set_global("count", 2.0);
+    engine
+        .run("var greeting = \"hello \" + name; count = count + 1;")
+        .unwrap();
+
+    assert_eq!(
+        engine.get_global("greeting"),
+        Some(Value::LoxString("hello world".into()))
+    );
+    assert_eq!(engine.get_global("count"), Some(Value::Number(3.0)));
+    assert_eq!(engine.get_global("missing"), None);
+}
+
+#[test]
+fn print_goes_to_the_configured_output() {
+    let output = SharedBuffer::default();
+    let mut engine = Engine::new();
+    engine.set_output(output.clone());
+
+    engine.run("print 1; print \"two\";").unwrap();
+    assert_eq!(output.contents(), "1\ntwo\n");
+}
+
+#[test]
+fn errors_are_typed_by_phase() {
+    let mut engine = Engine::new();
+    assert!(matches!(engine.eval("@"), Err(Error::Scan(_))));
+    assert!(matches!(engine.eval("print (;"), Err(Error::Parse(_))));
+    assert!(matches!(engine.eval("return 1;"), Err(Error::Resolve(_))));
+    assert!(matches!(engine.eval("nil()"), Err(Error::Runtime(_))));
+    assert!(matches!(
+        engine.run_file("does/not/exist.lox"),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn a_runtime_error_leaves_the_engine_usable() {
+    let mut engine = Engine::new();
+    assert!(engine.eval("{ var a = 1; a(); }").is_err());
+    assert_eq!(engine.eval("1").unwrap(), Value::Number(1.0));
+}
+
+const DEEP: &str = "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); }";
+
+#[test]
+fn deep_recursion_is_an_error_instead_of_overflowing_the_hosts_stack() {
+    // Test threads only get the 2 MiB stack of any spawned thread
+    let mut engine = Engine::new();
+    engine.run(DEEP).unwrap();
+
+    match engine.eval("f(100000)") {
+        Err(Error::Runtime(err)) => assert!(matches!(err.error, RuntimeError::StackOverflow(1))),
+        result => panic!("expected a stack overflow, got {:?}", result),
+    }
+    assert_eq!(engine.eval("f(10)").unwrap(), Value::Number(10.0));
+}
+
+#[test]
+fn a_bigger_stack_limit_allows_deeper_recursion() {
+    let reached = thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            let mut engine = Engine::new();
+            engine.run(DEEP).unwrap();
+            assert!(engine.eval("f(3000)").is_err());
+
+            engine.set_stack_limit(56 * 1024 * 1024);
+            engine.eval("f(3000)").unwrap() == Value::Number(3000.0)
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    assert!(reached);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::process::Output;
+
+use common::rlox;
+
+/// Runs `source` as a script read from standard input.
+fn run(source: &str) -> Output {
+    rlox(&["-"], source)
+}
+
+fn assert_fails(output: &Output, code: i32) {
+    assert_eq!(output.status.code(), Some(code), "{:?}", output);
+    assert!(output.stdout.is_empty(), "{:?}", output);
+    assert!(!output.stderr.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn success_exits_zero() {
+    let output = run("print 1 + 2;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn scanner_error_exits_65() {
+    assert_fails(&run("var a = @;"), 65);
+}
+
+#[test]
+fn parser_error_exits_65() {
+    assert_fails(&run("print (1 + ;"), 65);
+}
+
+#[test]
+fn parser_error_stops_the_whole_program() {
+    assert_fails(&run("print 1;\nprint (;"), 65);
+}
+
+#[test]
+fn resolver_errors_exit_65() {
+    for source in [
+        "return 1;",
+        "{ var a = 1; var a = 2; }",
+        "{ var a = a; }",
+        "print this;",
+        "print super.x;",
+        "class A { f() { return super.f(); } }",
+        "class A < A {}",
+        "class A { init() { return 1; } }",
+    ] {
+        assert_fails(&run(source), 65);
+    }
+}
+
+#[test]
+fn runtime_error_exits_70() {
+    assert_fails(&run("print -\"a\";"), 70);
+    assert_fails(&run("print undefined;"), 70);
+}
+
+#[test]
+fn output_before_a_runtime_error_is_kept() {
+    let output = run("print 1;\nprint nil();");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn eval_uses_the_same_codes() {
+    assert_fails(&rlox(&["-e", "print (;"], ""), 65);
+    assert_fails(&rlox(&["-e", "print 1 < nil;"], ""), 70);
+}
+
+#[test]
+fn unreadable_input_exits_66() {
+    assert_fails(&rlox(&["does/not/exist.lox"], ""), 66);
+    assert_fails(&rlox(&["ast", "does/not/exist.lox"], ""), 66);
+}
+
+#[test]
+fn bad_usage_exits_64() {
+    assert_fails(&rlox(&["--no-such-flag"], ""), 64);
+    assert_fails(&rlox(&["ast", "--json", "--dot", "-"], ""), 64);
+}
+
+#[test]
+fn help_and_version_exit_zero() {
+    assert_eq!(rlox(&["--help"], "").status.code(), Some(0));
+    assert_eq!(rlox(&["--version"], "").status.code(), Some(0));
+}
+
+#[test]
+fn check_reports_static_errors() {
+    assert_eq!(rlox(&["check", "-"], "print 1;").status.code(), Some(0));
+    assert_fails(&rlox(&["check", "-"], "print (;"), 65);
+    assert_fails(&rlox(&["check", "-"], "return;"), 65);
+}
+
+#[test]
+fn tools_exit_65_on_syntax_errors() {
+    assert_fails(&rlox(&["tokens", "-"], "@"), 65);
+    assert_fails(&rlox(&["ast", "-"], "print (;"), 65);
+    assert_fails(&rlox(&["fmt", "-"], "print (;"), 65);
+}
+
+#[test]
+fn unwritable_output_exits_73() {
+    let output = rlox(&["compile", "-o", "does/not/exist.loxc", "-"], "print 1;");
+    assert_fails(&output, 73);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("rlox: could not write does/not/exist.loxc"));
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::rlox;
+
+/// A file of its own for each test, so they can run in parallel.
+fn scratch(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rlox-fmt-{}-{}.lox", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn fmt(args: &[&str], stdin: &str) -> (Output, String) {
+    let output = rlox(&[&["fmt"], args].concat(), stdin);
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    (output, stdout)
+}
+
+#[test]
+fn prints_the_formatted_program() {
+    let (output, stdout) = fmt(
+        &["-"],
+        "var a=1;fun f(x){if(x>a)return x;else{print -x;}}\nclass B<A{m(){this.y=super.m();}}",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout,
+        "var a = 1;\n\
+         \n\
+         fun f(x) {\n    if (x > a)\n        return x;\n    else {\n        print -x;\n    }\n}\n\
+         \n\
+         class B < A {\n    m() {\n        this.y = super.m();\n    }\n}\n"
+    );
+}
+
+#[test]
+fn formatting_twice_changes_nothing() {
+    let (_, once) = fmt(&["-"], "while(true){var s=\"a b\";print s+\"c\";}");
+    let (_, twice) = fmt(&["-"], &once);
+
+    assert_eq!(once, twice);
+}
+
+#[test]
+fn printing_shows_what_comments_and_for_loops_become() {
+    let (output, stdout) = fmt(
+        &["-"],
+        "// note\nfor (var i = 0; i < 2; i = i + 1) print i; // end",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout,
+        "{\n    var i = 0;\n    while (i < 2) {\n        print i;\n        i = i + 1;\n    }\n}\n"
+    );
+}
+
+#[test]
+fn write_rewrites_the_file_in_place() {
+    let path = scratch("write", "print   1+2 ;");
+    let (output, stdout) = fmt(&["-w", path.to_str().unwrap()], "");
+    let rewritten = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout, "");
+    assert_eq!(rewritten, "print 1 + 2;\n");
+}
+
+#[test]
+fn write_wont_drop_comments() {
+    let source = "print 1;\n// important comment\nprint   2;\n";
+    let path = scratch("comments", source);
+    let (output, _) = fmt(&["-w", path.to_str().unwrap()], "");
+    let after = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the comment on line 2"));
+    assert_eq!(after, source);
+}
+
+#[test]
+fn write_wont_turn_for_loops_into_while_loops() {
+    let source = "var n = 0;\nfor (;;) n = n+1;\n";
+    let path = scratch("for", source);
+    let (output, _) = fmt(&["--write", path.to_str().unwrap()], "");
+    let after = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the for loop on line 2"));
+    assert_eq!(after, source);
+}
+
+#[test]
+fn write_leaves_a_file_with_syntax_errors_alone() {
+    let source = "print (;\n";
+    let path = scratch("invalid", source);
+    let (output, _) = fmt(&["-w", path.to_str().unwrap()], "");
+    let after = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(after, source);
+}
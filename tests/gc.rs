@@ -0,0 +1,157 @@
+use rlox::vm::Vm;
+use rlox::{Engine, Value};
+
+/// Objects that refer to themselves, the kind reference counting never frees.
+const CYCLES: &str = "
+class Node {
+  init() {
+    this.self = this;
+    this.method = this.get;
+  }
+  get() { return this; }
+}
+fun churn(n) {
+  for (var i = 0; i < n; i = i + 1) {
+    var node = Node();
+    var label = \"node \" + \"number\";
+  }
+}
+";
+
+fn live_after(iterations: usize) -> usize {
+    let mut vm = Vm::new();
+    vm.run(CYCLES).unwrap();
+    vm.run(&format!("churn({});", iterations)).unwrap();
+    vm.collect_garbage();
+    vm.heap().object_count()
+}
+
+#[test]
+fn unreachable_cycles_are_freed() {
+    assert_eq!(live_after(10), live_after(1000));
+}
+
+#[test]
+fn collection_keeps_what_globals_and_closures_reach() {
+    let mut vm = Vm::new();
+    vm.run(
+        "
+        fun make() {
+          var greeting = \"hello\" + \" world\";
+          fun get() { return greeting; }
+          return get;
+        }
+        var get = make();
+        class Box { init(v) { this.v = v; } }
+        var box = Box(\"boxed\" + \"!\");
+        ",
+    )
+    .unwrap();
+    vm.collect_garbage();
+    vm.run("for (var i = 0; i < 100; i = i + 1) { var s = \"x\" + \"y\"; }")
+        .unwrap();
+    vm.collect_garbage();
+
+    vm.run("if (get() != \"hello world\") undefined; if (box.v != \"boxed!\") undefined;")
+        .unwrap();
+}
+
+#[test]
+fn the_heap_collects_on_its_own_as_it_grows() {
+    let mut vm = Vm::new();
+    vm.run("var s = \"\"; for (var i = 0; i < 5000; i = i + 1) { s = s + \"a\"; }")
+        .unwrap();
+
+    // Each iteration allocates a longer string, but far fewer are still around
+    assert!(vm.heap().object_count() < 2500);
+}
+
+#[test]
+fn stress_mode_collects_on_every_allocation() {
+    let mut vm = Vm::new();
+    vm.set_gc_stress(true);
+    vm.run(CYCLES).unwrap();
+    vm.run("churn(50);").unwrap();
+
+    // The last collection ran just before the final allocation
+    let mut fresh = Vm::new();
+    fresh.run(CYCLES).unwrap();
+    fresh.collect_garbage();
+    assert!(vm.heap().object_count() <= fresh.heap().object_count() + 8);
+}
+
+fn engine_live_after(iterations: usize) -> usize {
+    let mut engine = Engine::new();
+    engine.run(CYCLES).unwrap();
+    engine
+        .run("fun closure() { fun self() { return self; } return self; }")
+        .unwrap();
+    for _ in 0..iterations {
+        engine.run("churn(1); closure();").unwrap();
+    }
+    engine.collect_garbage();
+    engine.object_count()
+}
+
+#[test]
+fn an_engine_session_does_not_grow() {
+    assert_eq!(engine_live_after(10), engine_live_after(1000));
+}
+
+#[test]
+fn an_engine_collects_cycles_on_its_own() {
+    let mut engine = Engine::new();
+    engine.run(CYCLES).unwrap();
+    engine.run("churn(20000);").unwrap();
+
+    // Each node is an instance and two scopes, for the call to init and
+    // for the loop body, and the collector runs well before 60000
+    assert!(engine.object_count() < 5000, "{}", engine.object_count());
+}
+
+#[test]
+fn engine_collection_keeps_what_globals_the_stack_and_the_host_reach() {
+    let mut engine = Engine::new();
+    engine.run(CYCLES).unwrap();
+    engine
+        .run(
+            "
+            var kept = Node();
+            fun make() {
+              var greeting = \"hello\";
+              fun get() { return greeting; }
+              return get;
+            }
+            var get = make();
+            var held = Node();
+            ",
+        )
+        .unwrap();
+    let held = engine.get_global("held").unwrap();
+    engine.run("held = nil;").unwrap();
+    engine.collect_garbage();
+
+    // A call running while the collector does keeps its scope too
+    engine
+        .run(
+            "
+            fun check(node) {
+              var local = Node();
+              churn(2000);
+              if (local.self != local) undefined;
+              return node.get() == node;
+            }
+            ",
+        )
+        .unwrap();
+    assert_eq!(
+        engine.eval("get()").unwrap(),
+        Value::LoxString("hello".into())
+    );
+    assert_eq!(
+        engine.eval("kept.method() == kept").unwrap(),
+        Value::Bool(true)
+    );
+    engine.set_global("held", held);
+    assert_eq!(engine.eval("check(held)").unwrap(), Value::Bool(true));
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{rlox, SharedBuffer};
+use rlox::vm::Vm;
+
+const COUNTER: &str = "
+class Counter {
+  init() { this.count = 0; }
+  add(n) { this.count = this.count + n; }
+}
+var counter = Counter();
+";
+
+/// Objects alive after calling a method `calls` times, without collecting.
+fn objects_after(calls: usize) -> usize {
+    let mut vm = Vm::new();
+    vm.run(COUNTER).unwrap();
+    vm.run(&format!(
+        "for (var i = 0; i < {}; i = i + 1) counter.add(1);",
+        calls
+    ))
+    .unwrap();
+    vm.heap().object_count()
+}
+
+#[test]
+fn invoking_a_method_allocates_no_bound_method() {
+    assert_eq!(objects_after(10), objects_after(1000));
+}
+
+#[test]
+fn a_method_taken_off_an_instance_is_still_bound() {
+    let mut vm = Vm::new();
+    vm.run(COUNTER).unwrap();
+    vm.run("var add = counter.add; add(2); add(3); if (counter.count != 5) undefined;")
+        .unwrap();
+}
+
+#[test]
+fn method_calls_compile_to_invoke() {
+    let output = rlox(&["disasm", "-"], &format!("{}counter.add(1);", COUNTER));
+    let listing = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        listing.contains("Invoke              4 'add' (1 args) cache 0"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("GetProperty         0 'count' cache 0"));
+    assert!(listing.contains("SetProperty         0 'count' cache 1"));
+}
+
+#[test]
+fn invoking_a_missing_method_is_an_undefined_property() {
+    let mut vm = Vm::new();
+    vm.run(COUNTER).unwrap();
+    let err = vm.run("counter.missing();").unwrap_err().to_string();
+    assert!(err.contains("Undefined property 'missing'"), "{}", err);
+
+    let err = vm.run("var n = 1; n.add(1);").unwrap_err().to_string();
+    assert!(err.contains("Only instances have properties"), "{}", err);
+}
+
+#[test]
+fn instructions_sharing_the_last_cache_keep_their_names_apart() {
+    // A function only has 65,536 caches, so everything after this many
+    // property instructions shares the last one
+    let mut source = String::from("class A { m() { return \"m\"; } }\nvar a = A();\n");
+    source.push_str(&"a.x = 1;\n".repeat(u16::MAX as usize));
+    source.push_str("a.y = 2; print a.x; print a.y; a.z = 3; print a.z; print a.m();");
+
+    let output = SharedBuffer::default();
+    let mut vm = Vm::new();
+    vm.set_output(output.clone());
+    vm.run(&source).unwrap();
+
+    assert_eq!(output.contents(), "1\n2\n3\nm\n");
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::SharedBuffer;
+use rlox::scanner::{self, Scanner};
+use rlox::symbol::Symbol;
+use rlox::token::TokenType;
+use rlox::vm::Vm;
+use rlox::{Engine, Value};
+
+fn string(engine: &mut Engine, source: &str) -> Rc<str> {
+    match engine.eval(source).unwrap() {
+        Value::LoxString(string) => string,
+        other => panic!("expected a string, got {}", other),
+    }
+}
+
+#[test]
+fn every_keyword_scans_to_its_token() {
+    for word in scanner::KEYWORDS {
+        let tokens = Scanner::new(word).scan_tokens().unwrap();
+        assert_eq!(Some(tokens[0].token_type.clone()), scanner::keyword(word));
+    }
+
+    let tokens = Scanner::new("classy orchid").scan_tokens().unwrap();
+    assert_eq!(
+        tokens[0].token_type,
+        TokenType::Identifier(Symbol::intern("classy"))
+    );
+    assert_eq!(
+        tokens[1].token_type,
+        TokenType::Identifier(Symbol::intern("orchid"))
+    );
+}
+
+#[test]
+fn identifiers_share_one_copy() {
+    let tokens = Scanner::new("name \"name\" name").scan_tokens().unwrap();
+    let symbols: Vec<Symbol> = tokens
+        .iter()
+        .filter_map(|token| match token.token_type {
+            TokenType::Identifier(symbol) => Some(symbol),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(symbols.len(), 2);
+    assert!(std::ptr::eq(symbols[0].as_str(), symbols[1].as_str()));
+    assert_eq!(tokens[1].token_type, TokenType::LoxString("name".into()));
+}
+
+#[test]
+fn string_literals_are_not_interned_for_good() {
+    let literal = "a literal only this test uses";
+    let tokens = Scanner::new(&format!("print \"{}\";", literal))
+        .scan_tokens()
+        .unwrap();
+    let TokenType::LoxString(string) = &tokens[1].token_type else {
+        panic!("expected a string, got {:?}", tokens[1].token_type);
+    };
+    let string = Rc::downgrade(string);
+    drop(tokens);
+
+    assert!(string.upgrade().is_none());
+    assert_eq!(Symbol::find(literal), None);
+
+    let mut engine = Engine::new();
+    engine
+        .run("var folded = \"folded only \" + \"in this test\";")
+        .unwrap();
+    engine.run("var built = folded + \" and built\";").unwrap();
+    assert_eq!(Symbol::find("folded only in this test"), None);
+    assert_eq!(Symbol::find("folded only in this test and built"), None);
+
+    Vm::new()
+        .run("var v = \"a vm literal only this test uses\";")
+        .unwrap();
+    assert_eq!(Symbol::find("a vm literal only this test uses"), None);
+}
+
+#[test]
+fn host_lookups_of_unknown_names_intern_nothing() {
+    let mut engine = Engine::new();
+    engine.run("class A {} var a = A();").unwrap();
+    let a = engine.get_global("a").unwrap();
+
+    assert!(engine
+        .call_method::<Value>(&a, "no method by this name", ())
+        .is_err());
+    assert!(engine
+        .call_global::<Value>("no global by this name", ())
+        .is_err());
+    assert_eq!(engine.get_global("nor by this one"), None);
+    for name in [
+        "no method by this name",
+        "no global by this name",
+        "nor by this one",
+    ] {
+        assert_eq!(Symbol::find(name), None);
+    }
+}
+
+#[test]
+fn the_vm_keeps_one_object_per_string() {
+    let output = SharedBuffer::default();
+    let mut vm = Vm::new();
+    vm.set_output(output.clone());
+    vm.run("var a = \"ab\"; var b = \"a\" + \"b\"; print a == b;")
+        .unwrap();
+    let before = vm.heap().object_count();
+
+    vm.run("for (var i = 0; i < 1000; i = i + 1) { var s = \"a\" + \"b\"; }")
+        .unwrap();
+
+    assert_eq!(output.contents(), "true\n");
+    // Only the new script and its constants, not a string per iteration
+    assert!(vm.heap().object_count() - before < 20);
+}
+
+#[test]
+fn the_tree_walker_keeps_one_copy_of_each_string() {
+    let mut engine = Engine::new();
+    let literal = string(&mut engine, "\"ab\"");
+    let built = string(&mut engine, "var a = \"a\"; a + \"b\";");
+
+    assert!(Rc::ptr_eq(&literal, &built));
+    assert!(Rc::ptr_eq(&built, &string(&mut engine, "a + \"b\"")));
+    assert!(Rc::ptr_eq(&literal, &string(&mut engine, "\"ab\"")));
+}
+
+#[test]
+fn the_tree_walker_frees_strings_nothing_holds() {
+    let mut engine = Engine::new();
+    let held = string(&mut engine, "var a = \"held\"; a + \"!\";");
+    let dropped = Rc::downgrade(&string(&mut engine, "a + \"?\""));
+
+    engine
+        .run("var s = \"\"; for (var i = 0; i < 3000; i = i + 1) s = s + \"x\";")
+        .unwrap();
+
+    assert!(dropped.upgrade().is_none());
+    assert!(Rc::ptr_eq(&held, &string(&mut engine, "a + \"!\"")));
+}
@@ -0,0 +1,178 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rlox::callable::LoxInstance;
+use rlox::native::{Handle, NativeClass};
+use rlox::{Engine, Error, Value};
+
+/// Stands in for a real database connection.
+struct Connection {
+    url: String,
+    queries: Vec<String>,
+}
+
+fn connection_class() -> NativeClass {
+    NativeClass::new("Connection")
+        .method("init", |this: Rc<RefCell<LoxInstance>>, url: String| {
+            this.borrow_mut()
+                .fields
+                .insert("url".into(), Value::from(url.as_str()));
+            Handle::new(Connection {
+                url,
+                queries: Vec::new(),
+            })
+        })
+        .method("query", |this: Handle<Connection>, sql: String| {
+            this.borrow_mut().queries.push(sql);
+            this.borrow().queries.len() as f64
+        })
+        .method("describe", |this: Handle<Connection>| {
+            format!("connection to {}", this.borrow().url)
+        })
+}
+
+fn string(s: &str) -> Value {
+    Value::LoxString(s.into())
+}
+
+fn eval(engine: &mut Engine, source: &str) -> Value {
+    engine.eval(source).unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[test]
+fn native_classes_have_constructors_methods_and_fields() {
+    let mut engine = Engine::new();
+    engine.define_class(connection_class());
+
+    eval(&mut engine, "var db = Connection(\"db://local\");");
+    assert_eq!(eval(&mut engine, "db.url"), string("db://local"));
+    assert_eq!(eval(&mut engine, "db.query(\"a\")"), Value::Number(1.0));
+    assert_eq!(eval(&mut engine, "db.query(\"b\")"), Value::Number(2.0));
+    assert_eq!(
+        eval(&mut engine, "Connection"),
+        eval(&mut engine, "Connection")
+    );
+    assert_eq!(
+        eval(&mut engine, "var describe = db.describe; describe();"),
+        string("connection to db://local")
+    );
+}
+
+#[test]
+fn lox_classes_inherit_from_native_classes() {
+    let mut engine = Engine::new();
+    engine.define_class(connection_class());
+
+    eval(
+        &mut engine,
+        "class Logged < Connection {
+             init(url) { super.init(url); this.log = \"\"; }
+             query(sql) { this.log = this.log + sql; return super.query(sql) * 10; }
+         }
+         var db = Logged(\"db://x\");",
+    );
+    assert_eq!(eval(&mut engine, "db.query(\"q\")"), Value::Number(10.0));
+    assert_eq!(eval(&mut engine, "db.log"), string("q"));
+    assert_eq!(
+        eval(&mut engine, "db.describe()"),
+        string("connection to db://x")
+    );
+}
+
+#[test]
+fn native_classes_inherit_from_lox_classes() {
+    let mut engine = Engine::new();
+    eval(
+        &mut engine,
+        "class Base { greet() { return \"hello \" + this.name(); } }",
+    );
+    let base = match engine.get_global("Base") {
+        Some(Value::Class(class)) => class,
+        value => panic!("expected a class, got {:?}", value),
+    };
+
+    engine.define_class(
+        NativeClass::new("Named")
+            .superclass(base)
+            .method("name", |_this: Value| "native"),
+    );
+    assert_eq!(eval(&mut engine, "Named().greet()"), string("hello native"));
+}
+
+#[test]
+fn userdata_is_opaque_to_scripts() {
+    let mut engine = Engine::new();
+    let connection = Handle::new(Connection {
+        url: "db://shared".to_string(),
+        queries: Vec::new(),
+    });
+    engine.set_global("conn", connection.clone());
+    engine.register("run", |conn: Handle<Connection>, sql: String| {
+        conn.borrow_mut().queries.push(sql);
+    });
+
+    assert_eq!(
+        eval(&mut engine, "conn"),
+        eval(&mut engine, "var copy = conn; copy;")
+    );
+    assert_eq!(
+        eval(&mut engine, "\"\" + \"x\"; conn;").to_string(),
+        "<userdata Connection>"
+    );
+    eval(&mut engine, "run(copy, \"select 1\");");
+    assert_eq!(connection.borrow().queries, ["select 1"]);
+
+    match engine.eval("conn.url") {
+        Err(Error::Runtime(err)) => assert!(err.to_string().contains("Only instances")),
+        result => panic!("expected a runtime error, got {:?}", result),
+    }
+}
+
+#[test]
+fn conversion_errors_name_the_method_argument() {
+    let mut engine = Engine::new();
+    engine.define_class(connection_class());
+    engine.set_global("other", Handle::new(0.0_f64));
+    engine.register("run", |conn: Handle<Connection>| {
+        conn.borrow().queries.len() as f64
+    });
+
+    let message = |engine: &mut Engine, source: &str| match engine.eval(source) {
+        Err(Error::Runtime(err)) => err.to_string(),
+        result => panic!("expected a runtime error, got {:?}", result),
+    };
+    assert_eq!(
+        message(&mut engine, "Connection(\"db\").query(1)"),
+        "Runtime Error: Argument 1 of 'query' must be a string, got number at line 1"
+    );
+    assert_eq!(
+        message(&mut engine, "run(other)"),
+        "Runtime Error: Argument 1 of 'run' must be userdata, got userdata at line 1"
+    );
+}
+
+#[test]
+#[should_panic(expected = "method tick of Clock must take the instance as its first parameter")]
+fn methods_without_a_parameter_for_the_instance_are_rejected() {
+    NativeClass::new("Clock").method("tick", || 1.0);
+}
+
+#[test]
+#[should_panic(expected = "method sum of Adder must take the instance as its first parameter")]
+fn variadic_methods_need_a_parameter_for_the_instance_too() {
+    NativeClass::new("Adder").method("sum", |numbers: Vec<f64>| numbers.iter().sum::<f64>());
+}
+
+#[test]
+fn variadic_methods_get_only_their_arguments() {
+    let mut engine = Engine::new();
+    engine.define_class(
+        NativeClass::new("Adder").method("sum", |_this: Value, numbers: Vec<f64>| {
+            numbers.iter().sum::<f64>()
+        }),
+    );
+    assert_eq!(
+        eval(&mut engine, "Adder().sum(1, 2, 3)"),
+        Value::Number(6.0)
+    );
+}
@@ -0,0 +1,122 @@
+use rlox::native::Arity;
+use rlox::{Engine, Error, Value};
+
+fn runtime_error(result: Result<Value, Error>) -> String {
+    match result {
+        Err(Error::Runtime(err)) => err.to_string(),
+        result => panic!("expected a runtime error, got {:?}", result),
+    }
+}
+
+#[test]
+fn closures_are_callable_with_converted_arguments() {
+    let mut engine = Engine::new();
+    engine.register("add", |a: f64, b: f64| a + b);
+    engine.register("shout", |s: String| s.to_uppercase());
+    engine.register("not", |b: bool| !b);
+
+    assert_eq!(engine.eval("add(1, 2)").unwrap(), Value::Number(3.0));
+    assert_eq!(
+        engine.eval("shout(\"hi\")").unwrap(),
+        Value::LoxString("HI".into())
+    );
+    assert_eq!(engine.eval("not(false)").unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn options_map_to_nil() {
+    let mut engine = Engine::new();
+    engine.register("or_default", |n: Option<f64>| n.unwrap_or(7.0));
+    engine.register("find", |n: f64| if n > 0.0 { Some(n) } else { None });
+
+    assert_eq!(engine.eval("or_default(nil)").unwrap(), Value::Number(7.0));
+    assert_eq!(engine.eval("or_default(1)").unwrap(), Value::Number(1.0));
+    assert_eq!(engine.eval("find(-1)").unwrap(), Value::Nil);
+}
+
+#[test]
+fn a_trailing_vec_makes_a_native_variadic() {
+    let mut engine = Engine::new();
+    engine.register("sum", |numbers: Vec<f64>| numbers.iter().sum::<f64>());
+    engine.register("join", |separator: String, parts: Vec<String>| {
+        parts.join(&separator)
+    });
+
+    assert_eq!(engine.eval("sum()").unwrap(), Value::Number(0.0));
+    assert_eq!(engine.eval("sum(1, 2, 3)").unwrap(), Value::Number(6.0));
+    assert_eq!(
+        engine.eval("join(\"-\", \"a\", \"b\")").unwrap(),
+        Value::LoxString("a-b".into())
+    );
+    assert_eq!(
+        runtime_error(engine.eval("join()")),
+        "Runtime Error: Expected at least 1 arguments but got 0 at line 1"
+    );
+}
+
+#[test]
+fn arity_is_checked() {
+    let mut engine = Engine::new();
+    engine.register("add", |a: f64, b: f64| a + b);
+
+    assert_eq!(
+        runtime_error(engine.eval("\n\nadd(1)")),
+        "Runtime Error: Expected 2 arguments but got 1 at line 3"
+    );
+}
+
+#[test]
+fn bad_arguments_are_runtime_errors_at_the_call_site() {
+    let mut engine = Engine::new();
+    engine.register("add", |a: f64, b: f64| a + b);
+
+    assert_eq!(
+        runtime_error(engine.eval("var x = 1;\nadd(x, \"2\");")),
+        "Runtime Error: Argument 2 of 'add' must be a number, got string at line 2"
+    );
+}
+
+#[test]
+fn errors_returned_by_natives_are_runtime_errors() {
+    let mut engine = Engine::new();
+    engine.register("sqrt", |n: f64| {
+        if n < 0.0 {
+            Err(format!("Can't take the square root of {}", n))
+        } else {
+            Ok(n.sqrt())
+        }
+    });
+
+    assert_eq!(engine.eval("sqrt(9)").unwrap(), Value::Number(3.0));
+    assert_eq!(
+        runtime_error(engine.eval("\nsqrt(-4)")),
+        "Runtime Error: Can't take the square root of -4 at line 2"
+    );
+}
+
+#[test]
+fn raw_natives_see_values_directly() {
+    let mut engine = Engine::new();
+    engine.define_native("kind", Arity::Fixed(1), |_, arguments| {
+        Ok(Value::LoxString(arguments[0].type_name().into()))
+    });
+
+    assert_eq!(
+        engine.eval("kind(kind)").unwrap(),
+        Value::LoxString("function".into())
+    );
+}
+
+#[test]
+fn natives_can_capture_host_state() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    let mut engine = Engine::new();
+    engine.register("tick", move || counter.set(counter.get() + 1));
+
+    engine.run("tick(); tick(); tick();").unwrap();
+    assert_eq!(calls.get(), 3);
+}
@@ -0,0 +1,67 @@
+use rlox::formatter::SourceFormatter;
+use rlox::vm::{self, disassembler};
+use rlox::{Engine, Error};
+
+/// The optimized program, printed back as source.
+fn optimized(source: &str) -> String {
+    let stmts = Engine::new().compile(source).unwrap();
+    SourceFormatter::new().format(&stmts)
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    assert_eq!(optimized("print 60 * 60 * 24;"), "print 86400;\n");
+    assert_eq!(optimized("print (1 + 2) * -3 >= -9;"), "print true;\n");
+    assert_eq!(optimized("print \"con\" + \"cat\";"), "print \"concat\";\n");
+    assert_eq!(optimized("print !nil == (1 != \"1\");"), "print true;\n");
+    assert_eq!(optimized("print nil or \"default\";"), "print \"default\";\n");
+    assert_eq!(optimized("print false and x;"), "print false;\n");
+}
+
+#[test]
+fn only_the_constant_parts_of_an_expression_are_folded() {
+    assert_eq!(optimized("var x; print x * (2 + 3);"), "var x;\nprint x * 5;\n");
+    assert_eq!(optimized("var x; print true and x;"), "var x;\nprint x;\n");
+}
+
+#[test]
+fn branches_that_cant_run_are_removed() {
+    assert_eq!(
+        optimized("if (1 > 2) print \"then\"; else print \"else\";"),
+        "print \"else\";\n"
+    );
+    assert_eq!(optimized("if (false) print \"never\";"), "");
+    assert_eq!(optimized("while (nil) print \"never\";"), "");
+}
+
+#[test]
+fn code_after_a_return_is_removed() {
+    assert_eq!(
+        optimized("fun f() { print 1; return 2; print 3; }"),
+        "fun f() {\n    print 1;\n    return 2;\n}\n"
+    );
+}
+
+#[test]
+fn folded_constants_reach_the_bytecode() {
+    let script = vm::compile("print 60 * 60 * 24;").unwrap();
+    let listing = disassembler::disassemble(&script);
+
+    assert!(listing.contains("Constant            0 '86400'\n"));
+    assert!(!listing.contains("Multiply"));
+}
+
+#[test]
+fn operations_that_fail_are_left_to_fail_at_runtime() {
+    assert_eq!(optimized("print \"a\" - 1;"), "print \"a\" - 1;\n");
+
+    let source = "var a = 1 + 2;\nprint \"a\" - 1;\n";
+    let Err(Error::Runtime(tree)) = Engine::new().run(source) else {
+        panic!("expected a runtime error");
+    };
+    let Err(Error::Runtime(vm)) = vm::Vm::new().run(source) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(tree.to_string(), vm.to_string());
+    assert!(tree.to_string().ends_with("at line 2"), "{}", tree);
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use std::fs;
+
+use common::{rlox, SharedBuffer};
+use rlox::repl::Repl;
+
+struct Session {
+    repl: Repl,
+    output: SharedBuffer,
+    errors: SharedBuffer,
+}
+
+impl Session {
+    fn new() -> Session {
+        let output = SharedBuffer::default();
+        let errors = SharedBuffer::default();
+        Session {
+            repl: Repl::with_output(output.clone(), errors.clone()),
+            output,
+            errors,
+        }
+    }
+
+    /// Types each line in turn, returning the prompt shown after each one.
+    fn type_lines(&mut self, lines: &[&str]) -> Vec<&'static str> {
+        lines
+            .iter()
+            .map(|line| {
+                self.repl.read_line(line).unwrap();
+                self.repl.prompt()
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn definitions_persist_between_inputs() {
+    let mut session = Session::new();
+    session.type_lines(&[
+        "var a = 1;",
+        "fun inc(n) { return n + 1; }",
+        "a = inc(a);",
+        "inc(a)",
+    ]);
+
+    assert_eq!(session.output.contents(), "3\n");
+    assert_eq!(session.repl.global_names(), ["a", "clock", "inc"]);
+    assert_eq!(session.errors.contents(), "");
+}
+
+#[test]
+fn only_bare_expressions_are_echoed() {
+    let mut session = Session::new();
+    session.type_lines(&["1 + 2", "3 + 4;", "var b = 5;", "b = 6;", "print b;", "b"]);
+
+    assert_eq!(session.output.contents(), "3\n6\n6\n");
+}
+
+#[test]
+fn unfinished_input_waits_for_more_lines() {
+    let mut session = Session::new();
+    assert_eq!(session.repl.prompt(), "> ");
+
+    let prompts = session.type_lines(&["fun twice(n) {", "  return n * 2;", "}"]);
+    assert_eq!(prompts, ["... ", "... ", "> "]);
+
+    let prompts = session.type_lines(&["print twice(", "  21", ");"]);
+    assert_eq!(prompts, ["... ", "... ", "> "]);
+
+    let prompts = session.type_lines(&["\"two", "lines\""]);
+    assert_eq!(prompts, ["... ", "> "]);
+
+    assert_eq!(session.output.contents(), "42\ntwo\nlines\n");
+}
+
+#[test]
+fn an_empty_line_gives_up_on_unfinished_input() {
+    let mut session = Session::new();
+    let prompts = session.type_lines(&["print (1 +", ""]);
+
+    assert_eq!(prompts, ["... ", "> "]);
+    assert!(session.errors.contents().contains("Error"));
+    assert_eq!(session.output.contents(), "");
+
+    session.type_lines(&["2"]);
+    assert_eq!(session.output.contents(), "2\n");
+}
+
+#[test]
+fn interrupting_drops_unfinished_input() {
+    let mut session = Session::new();
+    session.type_lines(&["var a = {"]);
+    session.repl.interrupt();
+
+    assert_eq!(session.repl.prompt(), "> ");
+    session.type_lines(&["1"]);
+    assert_eq!(session.output.contents(), "1\n");
+    assert_eq!(session.errors.contents(), "");
+}
+
+#[test]
+fn errors_are_reported_and_the_session_goes_on() {
+    let mut session = Session::new();
+    session.type_lines(&["var a = 1;", "a + nil", "print a;"]);
+
+    assert_eq!(session.output.contents(), "1\n");
+    assert!(session.errors.contents().contains("Runtime Error"));
+}
+
+#[test]
+fn env_lists_globals_and_reset_forgets_them() {
+    let mut session = Session::new();
+    session.type_lines(&["var a = 1;", "var s = \"x\";", ":env", ":reset", ":env"]);
+
+    assert_eq!(
+        session.output.contents(),
+        "a = 1\nclock = <native fn clock>\ns = x\nclock = <native fn clock>\n"
+    );
+    assert_eq!(session.repl.global_names(), ["clock"]);
+
+    // `print` still goes to the same place after a reset
+    session.type_lines(&["print a;", "print 2;"]);
+    assert!(session.errors.contents().contains("Undefined variable 'a'"));
+    assert!(session.output.contents().ends_with("clock>\n2\n"));
+}
+
+#[test]
+fn tokens_and_ast_show_the_front_end_output() {
+    let mut session = Session::new();
+    session.type_lines(&[":tokens a + 1", ":ast print -a;", ":ast (1)"]);
+
+    assert_eq!(
+        session.output.contents(),
+        "TokenType: Identifier(\"a\") at Line 1\n\
+         TokenType: Plus at Line 1\n\
+         TokenType: Number(1.0) at Line 1\n\
+         TokenType: Eof at Line 1\n\
+         (print (- a))\n\
+         (group 1)\n"
+    );
+}
+
+#[test]
+fn time_runs_its_argument_and_reports_how_long_it_took() {
+    let mut session = Session::new();
+    session.type_lines(&[":time 6 * 7"]);
+
+    let output = session.output.contents();
+    assert!(output.starts_with("42\nElapsed: "), "{}", output);
+}
+
+#[test]
+fn load_runs_a_file_in_the_session() {
+    let path = std::env::temp_dir().join(format!("rlox-repl-load-{}.lox", std::process::id()));
+    fs::write(&path, "var loaded = 40;").unwrap();
+
+    let mut session = Session::new();
+    session.type_lines(&[&format!(":load {}", path.display()), "loaded + 2"]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(session.output.contents(), "42\n");
+}
+
+#[test]
+fn help_lists_the_commands_and_unknown_ones_are_reported() {
+    let mut session = Session::new();
+    session.type_lines(&[":help", ":nope"]);
+
+    let output = session.output.contents();
+    for command in [
+        ":tokens", ":ast", ":env", ":load", ":reset", ":time", ":help",
+    ] {
+        assert!(output.contains(&format!("\n{} ", command)) || output.starts_with(command));
+    }
+    assert_eq!(
+        session.errors.contents(),
+        "Unknown command :nope, try :help\n"
+    );
+}
+
+#[test]
+fn commands_are_only_recognized_at_the_start_of_an_input() {
+    let mut session = Session::new();
+    let prompts = session.type_lines(&["print", ":env"]);
+
+    assert_eq!(prompts, ["... ", "> "]);
+    assert_eq!(session.output.contents(), "");
+    assert!(session.errors.contents().contains("Scanner Error"));
+}
+
+#[test]
+fn the_binary_reads_repl_input_from_a_pipe() {
+    let output = rlox(
+        &["repl"],
+        "var a = 2;\na * 3\nfun f() {\nreturn a;\n}\nf();\nf()\n",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "6\n2\n");
+}
@@ -0,0 +1,55 @@
+use rlox::parser::Parser;
+use rlox::rpn_printer::{rpn, RPNVisitor};
+use rlox::visit::Visitor;
+use rlox::scanner::Scanner;
+
+fn rpn_of(source: &str) -> String {
+    let tokens = Scanner::new(source).scan_tokens().unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+    rpn(&expr)
+}
+
+#[test]
+fn operands_come_before_their_operator() {
+    assert_eq!(rpn_of("1 + 2"), "1 2 +");
+    assert_eq!(rpn_of("a = b = 3"), "3 b = a =");
+    assert_eq!(rpn_of("x and y or z"), "x y and z or");
+}
+
+#[test]
+fn precedence_decides_the_order() {
+    assert_eq!(rpn_of("1 + 2 * 3"), "1 2 3 * +");
+    assert_eq!(rpn_of("1 * 2 + 3"), "1 2 * 3 +");
+    assert_eq!(rpn_of("1 - 2 - 3"), "1 2 - 3 -");
+    assert_eq!(rpn_of("1 < 2 == 3 >= 4"), "1 2 < 3 4 >= ==");
+}
+
+#[test]
+fn groupings_disappear_into_the_order() {
+    assert_eq!(rpn_of("(1 + 2) * 3"), "1 2 + 3 *");
+    assert_eq!(rpn_of("(1 + 2) * (4 - 3)"), "1 2 + 4 3 - *");
+    assert_eq!(rpn_of("1 - (2 - 3)"), "1 2 3 - -");
+}
+
+#[test]
+fn unary_operators_have_names_of_their_own() {
+    assert_eq!(rpn_of("-1 - -2"), "1 neg 2 neg -");
+    assert_eq!(rpn_of("!!true"), "true not not");
+}
+
+#[test]
+fn calls_and_properties() {
+    assert_eq!(rpn_of("f(1, 2 + 3)"), "f 1 2 3 + call/2");
+    assert_eq!(rpn_of("f()"), "f call/0");
+    assert_eq!(rpn_of("point.x = point.y"), "point point .y .x =");
+    assert_eq!(rpn_of("this.greet(\"hi\")"), "this .greet \"hi\" call/1");
+}
+
+#[test]
+fn the_visitor_can_be_used_directly() {
+    let tokens = Scanner::new("-(1 + 2)").scan_tokens().unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+
+    assert_eq!(RPNVisitor.visit_expression(&expr), rpn(&expr));
+    assert_eq!(RPNVisitor.visit_expression(&expr), "1 2 + neg");
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::rlox;
+
+const MILLION: &str = "
+fun loop(n, total) {
+  if (n == 0) return total;
+  return loop(n - 1, total + 1);
+}
+print loop(1000000, 0);
+";
+
+#[test]
+fn a_million_tail_calls_run_in_constant_stack_on_both_backends() {
+    for backend in ["--backend=tree", "--backend=vm"] {
+        let output = rlox(&[backend, "-"], MILLION);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "1000000\n",
+            "{}: {}",
+            backend,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn only_returned_calls_compile_to_tail_calls() {
+    let output = rlox(
+        &["disasm", "-"],
+        "fun f(n) { if (n == 0) return 0; return f(n - 1); }\n\
+         fun g(n) { if (n == 0) return 0; return 1 + g(n - 1); }\n",
+    );
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let (f, g) = listing.split_once("== g ==").unwrap();
+
+    assert!(f.contains("TailCall            1\n"), "{}", listing);
+    assert!(!g.contains("TailCall"), "{}", listing);
+    assert!(g.contains("Call                1\n"), "{}", listing);
+}
+
+#[test]
+fn a_tail_call_with_the_wrong_arity_is_reported_in_its_caller() {
+    let source = "fun f(a) { return a; }\nfun g() {\n  return f(1, 2);\n}\ng();\n";
+    let tree = rlox(&["--backend=tree", "-"], source);
+    let vm = rlox(&["--backend=vm", "-"], source);
+
+    let stderr = String::from_utf8_lossy(&tree.stderr);
+    assert!(
+        stderr.starts_with("Runtime Error: Expected 1 arguments but got 2 at line 3\n    in g()"),
+        "{}",
+        stderr
+    );
+    assert_eq!(stderr, String::from_utf8_lossy(&vm.stderr));
+}
+
+/// Stack traces from both backends, which have to agree.
+fn traces(source: &str) -> String {
+    let tree = rlox(&["--backend=tree", "-"], source);
+    let vm = rlox(&["--backend=vm", "-"], source);
+    let stderr = String::from_utf8_lossy(&tree.stderr).into_owned();
+    assert_eq!(stderr, String::from_utf8_lossy(&vm.stderr));
+    stderr
+}
+
+#[test]
+fn functions_replaced_by_tail_calls_stay_in_the_trace() {
+    let stderr = traces(
+        "fun inner() { return nil + 1; }\n\
+         fun middle() {\n  return inner();\n}\n\
+         fun outer() {\n  return middle();\n}\n\
+         outer();\n",
+    );
+    assert_eq!(
+        stderr,
+        "Runtime Error: Operands of + must be two numbers or two strings at line 1\n    \
+         in inner() at line 1\n    \
+         in middle() at line 3\n    \
+         in outer() at line 6\n    \
+         in script at line 8\n"
+    );
+}
+
+#[test]
+fn a_repeated_tail_call_is_traced_once() {
+    let stderr = traces(
+        "fun count(n) {\n  if (n == 0) return nil + 1;\n  return count(n - 1);\n}\n\
+         count(100000);\n",
+    );
+    assert_eq!(
+        stderr,
+        "Runtime Error: Operands of + must be two numbers or two strings at line 2\n    \
+         in count() at line 2\n    \
+         in count() at line 3\n    \
+         in script at line 5\n"
+    );
+}
+
+#[test]
+fn only_the_latest_alternating_tail_calls_are_traced() {
+    let stderr = traces(
+        "fun even(n) {\n  if (n == 0) return nil + 1;\n  return odd(n - 1);\n}\n\
+         fun odd(n) {\n  return even(n - 1);\n}\n\
+         even(100000);\n",
+    );
+    let frames: Vec<&str> = stderr.lines().skip(1).collect();
+
+    // The call made from the script is kept, along with the last 64
+    // functions tail calls replaced and the one that failed. All but ten
+    // from each end of those are only counted
+    assert_eq!(frames.len(), 10 + 1 + 10 + 1, "{}", stderr);
+    assert_eq!(frames[0], "    in even() at line 2");
+    assert_eq!(frames[1], "    in odd() at line 6");
+    assert_eq!(frames[10], "    ... 46 more calls");
+    assert_eq!(frames[frames.len() - 2], "    in even() at line 3");
+    assert_eq!(frames[frames.len() - 1], "    in script at line 8");
+}
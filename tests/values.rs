@@ -0,0 +1,76 @@
+//! The VM's values have to behave the same in both representations, so
+//! these run under `cargo test` and `cargo test --features nan-boxing`.
+
+use rlox::vm::object::Heap;
+use rlox::vm::value::{Unpacked, Value};
+
+#[test]
+fn values_unpack_to_what_they_were_made_from() {
+    let mut heap = Heap::new();
+    let string = heap.alloc_string("boxed");
+    let obj = string.as_obj().unwrap();
+
+    assert!(matches!(Value::NIL.unpack(), Unpacked::Nil));
+    assert!(matches!(Value::bool(true).unpack(), Unpacked::Bool(true)));
+    assert!(matches!(Value::bool(false).unpack(), Unpacked::Bool(false)));
+    assert!(matches!(Value::obj(obj).unpack(), Unpacked::Obj(o) if o == obj));
+    for number in [0.0, -0.0, 1.5, -2e300, f64::INFINITY, f64::NEG_INFINITY] {
+        assert_eq!(
+            Value::number(number).as_number().map(f64::to_bits),
+            Some(number.to_bits())
+        );
+    }
+
+    assert_eq!(Value::NIL.as_number(), None);
+    assert_eq!(Value::bool(true).as_obj(), None);
+    assert_eq!(Value::number(1.0).as_obj(), None);
+    assert_eq!(heap.as_str(string), Some("boxed"));
+}
+
+#[test]
+fn every_nan_is_a_number() {
+    let payloads = [
+        f64::NAN,
+        -f64::NAN,
+        f64::from_bits(0x7ffc_0000_0000_0001),
+        f64::from_bits(u64::MAX),
+    ];
+    for nan in payloads {
+        let value = Value::number(nan);
+        assert!(value.as_number().unwrap().is_nan());
+        assert!(value.as_obj().is_none());
+        assert!(value.is_truthy());
+    }
+}
+
+#[test]
+fn only_nil_and_false_are_falsey() {
+    let mut heap = Heap::new();
+    let empty = heap.alloc_string("");
+
+    assert!(!Value::NIL.is_truthy());
+    assert!(!Value::bool(false).is_truthy());
+    assert!(Value::bool(true).is_truthy());
+    assert!(Value::number(0.0).is_truthy());
+    assert!(empty.is_truthy());
+}
+
+#[test]
+fn equality_matches_the_tree_walker() {
+    let mut heap = Heap::new();
+    let a = heap.alloc_string("a");
+    let also_a = heap.alloc_string("a");
+    let nan = Value::number(f64::NAN);
+
+    assert!(heap.values_equal(a, also_a));
+    assert!(heap.values_equal(Value::number(0.0), Value::number(-0.0)));
+    assert!(!heap.values_equal(nan, nan));
+    assert!(!heap.values_equal(Value::NIL, Value::bool(false)));
+    assert!(!heap.values_equal(Value::number(1.0), Value::bool(true)));
+}
+
+#[cfg(feature = "nan-boxing")]
+#[test]
+fn nan_boxed_values_fit_in_a_word() {
+    assert_eq!(size_of::<Value>(), 8);
+}
@@ -0,0 +1,126 @@
+use rlox::formatter::SourceFormatter;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+use rlox::syntax::{Expr, LiteralExpr, LiteralValue, Stmt, Variable};
+use rlox::visit::{Fold, MutVisitor, Visitor};
+
+fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source).scan_tokens().unwrap();
+    Parser::new(tokens).parse().unwrap()
+}
+
+/// Records the leaves it reaches and leaves everything else to the defaults.
+#[derive(Default)]
+struct Leaves(Vec<String>);
+
+impl MutVisitor for Leaves {
+    type E = ();
+
+    fn visit_literal(&mut self, expr: &LiteralExpr) {
+        self.0.push(expr.value.to_string());
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) {
+        self.0.push(expr.name.symbol().to_string());
+    }
+}
+
+fn leaves(source: &str) -> Vec<String> {
+    let mut visitor = Leaves::default();
+    for stmt in &parse(source) {
+        visitor.visit_statement(stmt);
+    }
+    visitor.0
+}
+
+#[test]
+fn the_default_walk_visits_children_in_source_order() {
+    assert_eq!(leaves("print a + b * c;"), ["a", "b", "c"]);
+    assert_eq!(
+        leaves("if (a) { var b = c; } else while (d) e(f, g.h);"),
+        ["a", "c", "d", "e", "f", "g"]
+    );
+    assert_eq!(
+        leaves("o.p = q and r or !s; x = -1;"),
+        ["o", "q", "r", "s", "1"]
+    );
+}
+
+#[test]
+fn the_default_walk_reaches_into_functions_and_classes() {
+    let source = "fun f(a) { return b; }\n\
+                  class C < D { m() { print e; } n() { this.x = super.y; } }";
+
+    // `this` and `super` are leaves without names, so only `D` and `e` show
+    assert_eq!(leaves(source), ["b", "D", "e"]);
+}
+
+/// Stops at the first variable called `stop`.
+struct FindStop;
+
+impl Visitor for FindStop {
+    type E = Result<(), usize>;
+
+    fn visit_variable(&self, expr: &Variable) -> Self::E {
+        if expr.name.symbol().as_str() == "stop" {
+            Err(expr.name.line)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn a_break_value_stops_the_walk() {
+    let stmts = parse("print a;\n{ f(b, stop);\n print stop; }\n");
+    let found = stmts
+        .iter()
+        .try_for_each(|stmt| FindStop.visit_statement(stmt));
+    assert_eq!(found, Err(2));
+
+    assert_eq!(FindStop.visit_statement(&parse("print a + b;")[0]), Ok(()));
+}
+
+/// Replaces every variable with the number of letters in its name.
+struct NameLengths;
+
+impl Fold for NameLengths {
+    fn fold_variable(&mut self, expr: Variable) -> Expr {
+        Expr::Literal(LiteralExpr {
+            value: LiteralValue::Float(expr.name.symbol().as_str().len() as f64),
+            line: expr.name.line,
+        })
+    }
+}
+
+fn fold(source: &str) -> String {
+    let stmts = NameLengths.fold_statements(parse(source));
+    SourceFormatter::new().format(&stmts)
+}
+
+#[test]
+fn a_fold_rewrites_nested_expressions() {
+    assert_eq!(fold("print (ab + abc) * -a;"), "print (2 + 3) * -1;\n");
+    assert_eq!(
+        fold("if (a) f(ab, o.abc = abcd);"),
+        "if (1)\n    1(2, 1.abc = 4);\n"
+    );
+}
+
+#[test]
+fn a_fold_rewrites_function_and_method_bodies() {
+    assert_eq!(
+        fold("fun f() { return abc; }"),
+        "fun f() {\n    return 3;\n}\n"
+    );
+    assert_eq!(
+        fold("class C { m() { while (ab) print this; } }"),
+        "class C {\n    m() {\n        while (2)\n            print this;\n    }\n}\n"
+    );
+}
+
+#[test]
+fn a_fold_keeps_what_it_doesnt_touch() {
+    let source = "var x = \"s\";\nprint true and nil;\n";
+    assert_eq!(fold(source), "var x = \"s\";\nprint true and nil;\n");
+}